@@ -0,0 +1,301 @@
+use crate::text::to_ascii;
+use crate::{Error, OutputSink};
+use flate2::read::GzDecoder;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{DateTime, ZipWriter};
+
+/// Returns true if the output path names a zip archive rather than a directory.
+pub fn is_zip_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Returns true if the input path names a tar archive, optionally gzipped, to convert the
+/// contents of.
+pub fn is_tar_path(path: &str) -> bool {
+    let path = path.to_lowercase();
+    [".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+/// Whether a file in an archive looks like something that can be converted. The `._` files
+/// that macOS adds to archives for resource forks are skipped, despite their extensions.
+fn is_route_file(name: &str) -> bool {
+    let path = Path::new(name);
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("._"));
+    let convertible = path.extension().is_some_and(|ext| {
+        ["json", "geojson", "gpx", "kml"]
+            .iter()
+            .any(|known| ext.eq_ignore_ascii_case(known))
+    });
+    convertible && !hidden
+}
+
+/// Reads the files in a tar archive that look like routes one at a time, in the order they're
+/// stored, passing `f` the name of each within the archive and its contents. A problem with
+/// the archive, such as a file larger than `max_size` bytes failing with
+/// [`Error::InputTooLarge`], is passed to `f` in the same way, and ends the reading. An error
+/// returned by `f` also ends it, and is returned.
+pub fn read_tar(
+    path: &str,
+    max_size: Option<u64>,
+    mut f: impl FnMut(Result<(String, Vec<u8>), Error>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let file_error = |source| Error::FileError {
+        path: path.to_string(),
+        source,
+    };
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return f(Err(file_error(e))),
+    };
+    let reader: Box<dyn Read> = if path.to_lowercase().ends_with(".tar") {
+        Box::new(file)
+    } else {
+        Box::new(GzDecoder::new(file))
+    };
+    let mut archive = tar::Archive::new(reader);
+    let entries = match archive.entries() {
+        Ok(entries) => entries,
+        Err(e) => return f(Err(file_error(e))),
+    };
+    for entry in entries {
+        let read = entry.map_err(file_error).and_then(|mut entry| {
+            if !entry.header().entry_type().is_file() {
+                return Ok(None);
+            }
+            let name = entry.path().map_err(file_error)?.display().to_string();
+            if !is_route_file(&name) {
+                return Ok(None);
+            }
+            if let Some(limit) = max_size.filter(|&limit| entry.size() > limit) {
+                return Err(Error::InputTooLarge { limit });
+            }
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).map_err(file_error)?;
+            Ok(Some((name, contents)))
+        });
+        match read {
+            Ok(Some(document)) => f(Ok(document))?,
+            Ok(None) => {}
+            Err(e) => return f(Err(e)),
+        }
+    }
+    Ok(())
+}
+
+/// Derives the output file name for an input, e.g. "trails/foo.json" becomes "foo.gpx".
+pub fn output_file_name(input: &str, extension: &str) -> String {
+    let stem = match input {
+        "-" => "stdin",
+        _ => input_stem(input).unwrap_or("route"),
+    };
+    format!("{stem}.{extension}")
+}
+
+/// Fills in a --route-file-name template for one of `count` routes: `{name}` becomes the
+/// route's name, `{n}` its number counting from one, and `{count}` the number of routes.
+/// Path separators in the name are replaced, so that it can't put the file in a directory.
+pub fn route_file_stem(template: &str, name: &str, number: usize, count: usize) -> String {
+    template
+        .replace("{n}", &number.to_string())
+        .replace("{count}", &count.to_string())
+        .replace("{name}", &name.replace(['/', '\\'], "-"))
+}
+
+/// Transliterates a file name to ASCII, keeping anything that comes out as a path separator,
+/// such as "½" becoming "1/2", from putting the file in a directory.
+pub fn ascii_file_name(name: &str) -> String {
+    to_ascii(name).replace(['/', '\\'], "-")
+}
+
+/// The input's file name without its extension, e.g. "foo" for "trails/foo.json", or `None`
+/// for stdin.
+pub fn input_stem(input: &str) -> Option<&str> {
+    match input {
+        "-" => None,
+        _ => Path::new(input).file_stem().and_then(|stem| stem.to_str()),
+    }
+}
+
+/// Where the results of a multi-file conversion go: either loose files in a directory, or
+/// entries in a single zip archive.
+pub enum BatchOutput {
+    Directory(PathBuf),
+    Zip {
+        path: String,
+        zip: Box<ZipWriter<File>>,
+        options: SimpleFileOptions,
+    },
+}
+
+impl BatchOutput {
+    /// When `deterministic` is set, zip entries are stamped with the zip epoch (1980-01-01)
+    /// rather than the current time, so that the same inputs always give the same archive.
+    pub fn create(path: &str, deterministic: bool) -> Result<Self, Error> {
+        let file_error = |source| Error::FileError {
+            path: path.to_string(),
+            source,
+        };
+
+        if is_zip_path(path) {
+            let file = File::create(path).map_err(file_error)?;
+            let mut options = SimpleFileOptions::default();
+            if deterministic {
+                options = options.last_modified_time(DateTime::default());
+            }
+            Ok(Self::Zip {
+                path: path.to_string(),
+                zip: Box::new(ZipWriter::new(file)),
+                options,
+            })
+        } else {
+            fs::create_dir_all(path).map_err(file_error)?;
+            Ok(Self::Directory(PathBuf::from(path)))
+        }
+    }
+
+    /// Where to write a file of the output called `name`, which says where it ended up when
+    /// displayed.
+    pub fn sink(&mut self, name: &str) -> OutputSink<'_> {
+        match self {
+            Self::Directory(dir) => OutputSink::File(dir.join(name).display().to_string()),
+            Self::Zip { path, zip, options } => OutputSink::ZipEntry {
+                archive: path.clone(),
+                name: name.to_string(),
+                zip,
+                options: *options,
+            },
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        if let Self::Zip { zip, .. } = self {
+            zip.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Hands out output names, appending a numeric suffix when two inputs share a file stem (for
+/// example "a/route.json" and "b/route.json").
+#[derive(Default)]
+pub struct UniqueNames(HashSet<String>);
+
+impl UniqueNames {
+    pub fn claim(&mut self, name: String) -> String {
+        if self.0.insert(name.clone()) {
+            return name;
+        }
+
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) => (stem, format!(".{ext}")),
+            None => (name.as_str(), String::new()),
+        };
+        let unique = (2..)
+            .map(|n| format!("{stem}-{n}{ext}"))
+            .find(|candidate| !self.0.contains(candidate))
+            .expect("unbounded range always yields a free name");
+        self.0.insert(unique.clone());
+        unique
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_file_name() {
+        assert_eq!(output_file_name("trails/foo.json", "gpx"), "foo.gpx");
+        assert_eq!(output_file_name("-", "gpx"), "stdin.gpx");
+        assert_eq!(output_file_name("foo.json", "html"), "foo.html");
+        assert_eq!(ascii_file_name("Zürich ½.gpx"), "Zurich 1-2.gpx");
+        assert_eq!(
+            route_file_stem("{name} {n} of {count}", "Leigh Woods / {n}", 2, 3),
+            "Leigh Woods - {n} 2 of 3"
+        );
+        assert!(is_zip_path("out/Archive.ZIP"));
+        assert!(!is_zip_path("out"));
+    }
+
+    #[test]
+    fn test_tar_inputs() {
+        assert!(is_tar_path("backups/trails.tar.gz"));
+        assert!(is_tar_path("trails.TGZ"));
+        assert!(!is_tar_path("trails.json"));
+        assert!(is_route_file("export/abbots.json"));
+        assert!(!is_route_file("export/._abbots.json"));
+        assert!(!is_route_file("export/README"));
+    }
+
+    #[test]
+    fn test_read_tar() {
+        let path = std::env::temp_dir().join(format!("alltrailsgpx-{}.tar", std::process::id()));
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        for (name, contents) in [
+            ("walks/abbots.json", "{}"),
+            ("walks/README", "Walks"),
+            ("walks/leigh-woods.gpx", "<gpx/>"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        builder.finish().unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut names = Vec::new();
+        read_tar(path, None, |entry| {
+            names.push(entry?.0);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(names, ["walks/abbots.json", "walks/leigh-woods.gpx"]);
+
+        // Reading stops at the first file that's too large.
+        let mut entries = Vec::new();
+        read_tar(path, Some(4), |entry| {
+            entries.push(entry.map(|(name, _)| name));
+            Ok(())
+        })
+        .unwrap();
+        assert!(matches!(
+            entries.as_slice(),
+            [Ok(_), Err(Error::InputTooLarge { limit: 4 })]
+        ));
+
+        // As it does when told to.
+        let mut calls = 0;
+        let stopped = read_tar(path, None, |_| {
+            calls += 1;
+            Err(Error::NoRouteLines)
+        });
+        assert!(matches!(stopped, Err(Error::NoRouteLines)));
+        assert_eq!(calls, 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let mut names = UniqueNames::default();
+        assert_eq!(names.claim("route.gpx".to_string()), "route.gpx");
+        assert_eq!(names.claim("route.gpx".to_string()), "route-2.gpx");
+        assert_eq!(names.claim("route.gpx".to_string()), "route-3.gpx");
+        assert_eq!(names.claim("README".to_string()), "README");
+        assert_eq!(names.claim("README".to_string()), "README-2");
+    }
+}
@@ -0,0 +1,575 @@
+use crate::{Error, GPX_CREATOR};
+use gpx::{Link, Metadata, Person, Time, Track, Waypoint};
+use std::io::{self, Write};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+pub(crate) const GPX_NAMESPACE: &str = "http://www.topografix.com/GPX/1/1";
+const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+const GPX_SCHEMA_LOCATION: &str =
+    "http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd";
+
+/// Assembles a GPX 1.1 document. Unlike `gpx::write`, this can also write extensions, which
+/// are given as raw XML fragments along with any namespaces they use.
+///
+/// Only the parts of the gpx crate's types that alltrailsgpx produces are written: for
+/// waypoints that's position, elevation, time, name, comment, description, source, links,
+/// symbol and type.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct GpxBuilder {
+    creator: String,
+    metadata: Option<Metadata>,
+    namespaces: Vec<(String, String)>,
+    waypoints: Vec<Waypoint>,
+    /// Each track, with its extensions and those of each of its points.
+    tracks: Vec<(Track, Vec<String>, Vec<Vec<String>>)>,
+    extensions: Vec<String>,
+}
+
+impl Default for GpxBuilder {
+    fn default() -> Self {
+        Self {
+            creator: GPX_CREATOR.to_string(),
+            metadata: None,
+            namespaces: Vec::new(),
+            waypoints: Vec::new(),
+            tracks: Vec::new(),
+            extensions: Vec::new(),
+        }
+    }
+}
+
+impl GpxBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = creator.into();
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Sets the document description, creating metadata if there isn't any yet.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.metadata
+            .get_or_insert_with(Metadata::default)
+            .description = Some(description.into());
+        self
+    }
+
+    /// Sets the document time, creating metadata if there isn't any yet.
+    pub fn time(mut self, time: Time) -> Self {
+        self.metadata.get_or_insert_with(Metadata::default).time = Some(time);
+        self
+    }
+
+    /// Names the document's author, creating metadata if there isn't any yet.
+    pub fn author(mut self, name: impl Into<String>) -> Self {
+        self.metadata.get_or_insert_with(Metadata::default).author = Some(Person {
+            name: Some(name.into()),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Adds a link to the document's metadata, creating metadata if there isn't any yet.
+    pub fn link(mut self, href: impl Into<String>) -> Self {
+        self.metadata
+            .get_or_insert_with(Metadata::default)
+            .links
+            .push(Link {
+                href: href.into(),
+                ..Default::default()
+            });
+        self
+    }
+
+    /// Declares an XML namespace on the root element, for use by extensions.
+    pub fn namespace(mut self, prefix: impl Into<String>, uri: impl Into<String>) -> Self {
+        self.namespaces.push((prefix.into(), uri.into()));
+        self
+    }
+
+    pub fn waypoint(mut self, waypoint: Waypoint) -> Self {
+        self.waypoints.push(waypoint);
+        self
+    }
+
+    pub fn track(self, track: Track) -> Self {
+        self.track_with_extensions(track, Vec::new())
+    }
+
+    /// Adds a track with extensions of its own, each a raw XML fragment.
+    pub fn track_with_extensions(self, track: Track, extensions: Vec<String>) -> Self {
+        self.track_with_point_extensions(track, extensions, Vec::new())
+    }
+
+    /// Like [`Self::track_with_extensions`], but also with extensions for each track point, in
+    /// order through the segments. Points beyond the end of `point_extensions` get none.
+    pub fn track_with_point_extensions(
+        mut self,
+        track: Track,
+        extensions: Vec<String>,
+        point_extensions: Vec<Vec<String>>,
+    ) -> Self {
+        self.tracks.push((track, extensions, point_extensions));
+        self
+    }
+
+    /// Numbers the tracks added so far in order, starting from `start`.
+    pub fn number_tracks(mut self, start: u32) -> Self {
+        for (i, (track, _, _)) in self.tracks.iter_mut().enumerate() {
+            track.number = Some(start.saturating_add(i as u32));
+        }
+        self
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Adds a document level extension, as a raw XML fragment.
+    pub fn extension(mut self, xml: impl Into<String>) -> Self {
+        self.extensions.push(xml.into());
+        self
+    }
+
+    pub fn write(&self, writer: impl Write) -> Result<(), Error> {
+        self.write_document(&mut XmlWriter::new(writer))
+            .map_err(Error::OutputWriteError)
+    }
+
+    fn write_document<W: Write>(&self, xml: &mut XmlWriter<W>) -> io::Result<()> {
+        xml.declaration()?;
+        xml.open("gpx", &root_attributes(&self.creator, &self.namespaces))?;
+
+        if let Some(metadata) = &self.metadata {
+            write_metadata(xml, metadata)?;
+        }
+        for waypoint in &self.waypoints {
+            write_waypoint(xml, "wpt", waypoint, &[])?;
+        }
+        for (track, extensions, point_extensions) in &self.tracks {
+            write_track(xml, track, extensions, point_extensions)?;
+        }
+        write_extensions(xml, &self.extensions)?;
+
+        xml.close("gpx")?;
+        xml.flush()
+    }
+}
+
+/// Writes a GPX document a point at a time, for tracks too long to comfortably hold in memory
+/// as a [`Track`]. Only the document's tracks, their names and their points are written.
+pub struct GpxStreamWriter<W: Write> {
+    xml: XmlWriter<W>,
+    in_track: bool,
+    in_segment: bool,
+}
+
+impl<W: Write> GpxStreamWriter<W> {
+    /// Starts the document, writing everything before the first track.
+    pub fn new(writer: W) -> Result<Self, Error> {
+        let mut xml = XmlWriter::new(writer);
+        xml.declaration()
+            .and_then(|()| xml.open("gpx", &root_attributes(GPX_CREATOR, &[])))
+            .map_err(Error::OutputWriteError)?;
+        Ok(Self {
+            xml,
+            in_track: false,
+            in_segment: false,
+        })
+    }
+
+    /// Starts a new track, ending the one before it.
+    pub fn track(&mut self, name: &str) -> Result<(), Error> {
+        self.end_track()
+            .and_then(|()| self.xml.open("trk", &[]))
+            .and_then(|()| self.xml.element("name", name))
+            .map_err(Error::OutputWriteError)?;
+        self.in_track = true;
+        Ok(())
+    }
+
+    /// Starts a new segment of the current track, ending the one before it.
+    pub fn segment(&mut self) -> Result<(), Error> {
+        self.end_segment()
+            .and_then(|()| self.xml.open("trkseg", &[]))
+            .map_err(Error::OutputWriteError)?;
+        self.in_segment = true;
+        Ok(())
+    }
+
+    /// Adds a point to the current segment.
+    pub fn point(&mut self, point: &Waypoint) -> Result<(), Error> {
+        write_waypoint(&mut self.xml, "trkpt", point, &[]).map_err(Error::OutputWriteError)
+    }
+
+    /// Ends the document, returning the writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.end_track()
+            .and_then(|()| self.xml.close("gpx"))
+            .and_then(|()| self.xml.flush())
+            .map_err(Error::OutputWriteError)?;
+        Ok(self.xml.out)
+    }
+
+    fn end_segment(&mut self) -> io::Result<()> {
+        if std::mem::take(&mut self.in_segment) {
+            self.xml.close("trkseg")?;
+        }
+        Ok(())
+    }
+
+    fn end_track(&mut self) -> io::Result<()> {
+        self.end_segment()?;
+        if std::mem::take(&mut self.in_track) {
+            self.xml.close("trk")?;
+        }
+        Ok(())
+    }
+}
+
+fn root_attributes(creator: &str, namespaces: &[(String, String)]) -> Vec<(String, String)> {
+    let mut attributes = vec![
+        ("version".to_string(), "1.1".to_string()),
+        ("creator".to_string(), creator.to_string()),
+        ("xmlns".to_string(), GPX_NAMESPACE.to_string()),
+        ("xmlns:xsi".to_string(), XSI_NAMESPACE.to_string()),
+        (
+            "xsi:schemaLocation".to_string(),
+            GPX_SCHEMA_LOCATION.to_string(),
+        ),
+    ];
+    attributes.extend(
+        namespaces
+            .iter()
+            .map(|(prefix, uri)| (format!("xmlns:{prefix}"), uri.clone())),
+    );
+    attributes
+}
+
+fn write_metadata<W: Write>(xml: &mut XmlWriter<W>, metadata: &Metadata) -> io::Result<()> {
+    xml.open("metadata", &[])?;
+    xml.optional("name", metadata.name.as_deref())?;
+    xml.optional("desc", metadata.description.as_deref())?;
+    if let Some(author) = &metadata.author {
+        write_person(xml, "author", author)?;
+    }
+    for link in &metadata.links {
+        write_link(xml, link)?;
+    }
+    if let Some(time) = &metadata.time {
+        xml.element("time", &format_time(time)?)?;
+    }
+    xml.optional("keywords", metadata.keywords.as_deref())?;
+    if let Some(bounds) = &metadata.bounds {
+        let (min, max) = (bounds.min(), bounds.max());
+        xml.empty(
+            "bounds",
+            &[
+                ("minlat".to_string(), min.y.to_string()),
+                ("minlon".to_string(), min.x.to_string()),
+                ("maxlat".to_string(), max.y.to_string()),
+                ("maxlon".to_string(), max.x.to_string()),
+            ],
+        )?;
+    }
+    xml.close("metadata")
+}
+
+fn write_person<W: Write>(xml: &mut XmlWriter<W>, tag: &str, person: &Person) -> io::Result<()> {
+    xml.open(tag, &[])?;
+    xml.optional("name", person.name.as_deref())?;
+    if let Some((id, domain)) = person.email.as_deref().and_then(|e| e.split_once('@')) {
+        xml.empty(
+            "email",
+            &[
+                ("id".to_string(), id.to_string()),
+                ("domain".to_string(), domain.to_string()),
+            ],
+        )?;
+    }
+    if let Some(link) = &person.link {
+        write_link(xml, link)?;
+    }
+    xml.close(tag)
+}
+
+fn write_link<W: Write>(xml: &mut XmlWriter<W>, link: &Link) -> io::Result<()> {
+    xml.open("link", &[("href".to_string(), link.href.clone())])?;
+    xml.optional("text", link.text.as_deref())?;
+    xml.optional("type", link.type_.as_deref())?;
+    xml.close("link")
+}
+
+fn write_track<W: Write>(
+    xml: &mut XmlWriter<W>,
+    track: &Track,
+    extensions: &[String],
+    point_extensions: &[Vec<String>],
+) -> io::Result<()> {
+    xml.open("trk", &[])?;
+    xml.optional("name", track.name.as_deref())?;
+    xml.optional("cmt", track.comment.as_deref())?;
+    xml.optional("desc", track.description.as_deref())?;
+    xml.optional("src", track.source.as_deref())?;
+    for link in &track.links {
+        write_link(xml, link)?;
+    }
+    if let Some(number) = track.number {
+        xml.element("number", &number.to_string())?;
+    }
+    xml.optional("type", track.type_.as_deref())?;
+    write_extensions(xml, extensions)?;
+
+    let mut point_extensions = point_extensions.iter();
+    for segment in &track.segments {
+        xml.open("trkseg", &[])?;
+        for point in &segment.points {
+            let extensions = point_extensions.next().map_or(&[][..], Vec::as_slice);
+            write_waypoint(xml, "trkpt", point, extensions)?;
+        }
+        xml.close("trkseg")?;
+    }
+
+    xml.close("trk")
+}
+
+fn write_waypoint<W: Write>(
+    xml: &mut XmlWriter<W>,
+    tag: &str,
+    waypoint: &Waypoint,
+    extensions: &[String],
+) -> io::Result<()> {
+    let point = waypoint.point();
+    let attributes = [
+        ("lat".to_string(), point.y().to_string()),
+        ("lon".to_string(), point.x().to_string()),
+    ];
+
+    let has_children = waypoint.elevation.is_some()
+        || waypoint.time.is_some()
+        || waypoint.name.is_some()
+        || waypoint.comment.is_some()
+        || waypoint.description.is_some()
+        || waypoint.source.is_some()
+        || !waypoint.links.is_empty()
+        || waypoint.symbol.is_some()
+        || waypoint.type_.is_some()
+        || !extensions.is_empty();
+    if !has_children {
+        return xml.empty(tag, &attributes);
+    }
+
+    xml.open(tag, &attributes)?;
+    if let Some(elevation) = waypoint.elevation {
+        xml.element("ele", &elevation.to_string())?;
+    }
+    if let Some(time) = &waypoint.time {
+        xml.element("time", &format_time(time)?)?;
+    }
+    xml.optional("name", waypoint.name.as_deref())?;
+    xml.optional("cmt", waypoint.comment.as_deref())?;
+    xml.optional("desc", waypoint.description.as_deref())?;
+    xml.optional("src", waypoint.source.as_deref())?;
+    for link in &waypoint.links {
+        write_link(xml, link)?;
+    }
+    xml.optional("sym", waypoint.symbol.as_deref())?;
+    xml.optional("type", waypoint.type_.as_deref())?;
+    write_extensions(xml, extensions)?;
+    xml.close(tag)
+}
+
+fn write_extensions<W: Write>(xml: &mut XmlWriter<W>, extensions: &[String]) -> io::Result<()> {
+    if extensions.is_empty() {
+        return Ok(());
+    }
+    xml.open("extensions", &[])?;
+    for extension in extensions {
+        xml.raw(extension)?;
+    }
+    xml.close("extensions")
+}
+
+fn format_time(time: &Time) -> io::Result<String> {
+    OffsetDateTime::from(*time)
+        .format(&Rfc3339)
+        .map_err(io::Error::other)
+}
+
+/// Just enough of an XML writer for GPX: elements are indented one per line, and text content
+/// stays on the line of its element.
+struct XmlWriter<W: Write> {
+    out: W,
+    depth: usize,
+}
+
+impl<W: Write> XmlWriter<W> {
+    fn new(out: W) -> Self {
+        Self { out, depth: 0 }
+    }
+
+    fn declaration(&mut self) -> io::Result<()> {
+        writeln!(self.out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)
+    }
+
+    fn indent(&mut self) -> io::Result<()> {
+        write!(self.out, "{:1$}", "", self.depth * 2)
+    }
+
+    fn start_tag(&mut self, tag: &str, attributes: &[(String, String)]) -> io::Result<()> {
+        self.indent()?;
+        write!(self.out, "<{tag}")?;
+        for (name, value) in attributes {
+            write!(self.out, " {name}=\"{}\"", escape(value))?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, tag: &str, attributes: &[(String, String)]) -> io::Result<()> {
+        self.start_tag(tag, attributes)?;
+        writeln!(self.out, ">")?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn empty(&mut self, tag: &str, attributes: &[(String, String)]) -> io::Result<()> {
+        self.start_tag(tag, attributes)?;
+        writeln!(self.out, "/>")
+    }
+
+    fn close(&mut self, tag: &str) -> io::Result<()> {
+        self.depth -= 1;
+        self.indent()?;
+        writeln!(self.out, "</{tag}>")
+    }
+
+    fn element(&mut self, tag: &str, text: &str) -> io::Result<()> {
+        self.indent()?;
+        writeln!(self.out, "<{tag}>{}</{tag}>", escape(text))
+    }
+
+    fn optional(&mut self, tag: &str, text: Option<&str>) -> io::Result<()> {
+        match text {
+            Some(text) => self.element(tag, text),
+            None => Ok(()),
+        }
+    }
+
+    fn raw(&mut self, fragment: &str) -> io::Result<()> {
+        for line in fragment.lines() {
+            self.indent()?;
+            writeln!(self.out, "{line}")?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::TrackSegment;
+
+    #[test]
+    fn test_builder_round_trips_through_gpx_reader() {
+        let mut waypoint = Waypoint::new(Point::new(-2.6, 51.45));
+        waypoint.name = Some("Car park & café".to_string());
+        waypoint.symbol = Some("Parking Area".to_string());
+
+        let track = Track {
+            name: Some("Loop <1>".to_string()),
+            segments: vec![TrackSegment {
+                points: vec![
+                    Waypoint::new(Point::new(-2.6, 51.45)),
+                    Waypoint::new(Point::new(-2.61, 51.46)),
+                ],
+            }],
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        GpxBuilder::new()
+            .creator("test")
+            .description("A description")
+            .namespace("at", "https://example.com/alltrails")
+            .waypoint(waypoint)
+            .track_with_point_extensions(
+                track,
+                vec!["<at:color>red</at:color>".to_string()],
+                vec![Vec::new(), vec!["<at:hr>128</at:hr>".to_string()]],
+            )
+            .extension("<at:source>unit test</at:source>")
+            .write(&mut output)
+            .unwrap();
+
+        let text = String::from_utf8(output.clone()).unwrap();
+        assert!(text.contains(r#"xmlns:at="https://example.com/alltrails""#));
+        assert!(text.contains("<at:color>red</at:color>"));
+        let second = text.find(r#"lat="51.46""#).unwrap();
+        assert_eq!(
+            text.find("<at:hr>128</at:hr>").map(|hr| hr > second),
+            Some(true)
+        );
+
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_eq!(gpx.creator.as_deref(), Some("test"));
+        assert_eq!(
+            gpx.metadata.unwrap().description.as_deref(),
+            Some("A description")
+        );
+        assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Car park & café"));
+        assert_eq!(gpx.tracks[0].name.as_deref(), Some("Loop <1>"));
+        assert_eq!(gpx.tracks[0].segments[0].points.len(), 2);
+    }
+
+    #[test]
+    fn test_stream_writer() {
+        let mut writer = GpxStreamWriter::new(Vec::new()).unwrap();
+        writer.track("Loop").unwrap();
+        writer.segment().unwrap();
+        for i in 0..3 {
+            let point = Waypoint::new(Point::new(-2.6, 51.45 + f64::from(i) * 0.01));
+            writer.point(&point).unwrap();
+        }
+        writer.track("Spur").unwrap();
+        writer.segment().unwrap();
+        writer
+            .point(&Waypoint::new(Point::new(-2.7, 51.5)))
+            .unwrap();
+        let output = writer.finish().unwrap();
+
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_eq!(gpx.tracks.len(), 2);
+        assert_eq!(gpx.tracks[0].name.as_deref(), Some("Loop"));
+        assert_eq!(gpx.tracks[0].segments[0].points.len(), 3);
+        assert_eq!(gpx.tracks[1].segments[0].points.len(), 1);
+    }
+}
@@ -0,0 +1,80 @@
+//! Offline map packs, which sometimes carry more trails than the one they're for, nested
+//! under `bundledTrails` or `nearbyTrails` either beside `trails` or inside the trail itself.
+
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// The keys bundled trails are found under.
+const BUNDLED_KEYS: &[&str] = &["bundledTrails", "nearbyTrails"];
+
+/// Where bundled trails are looked for, in the offline and deep layouts.
+const PARENT_PATHS: &[&str] = &["/trails/0", "/maps/0", ""];
+
+/// Takes the trails bundled with the first trail out of the document, returning each as a
+/// document of its own in the offline layout. Trails with the same ID as the first trail or
+/// one already taken are dropped.
+pub(crate) fn take(json: &mut Value) -> Vec<Value> {
+    let bundled = peek(json)
+        .into_iter()
+        .map(|trail| json!({ "trails": [trail] }))
+        .collect();
+    for path in PARENT_PATHS {
+        if let Some(parent) = json.pointer_mut(path).and_then(Value::as_object_mut) {
+            for key in BUNDLED_KEYS {
+                parent.remove(*key);
+            }
+        }
+    }
+    bundled
+}
+
+/// The trails bundled with the first trail, as [`take`] would find them, without taking them.
+pub(crate) fn peek(json: &Value) -> Vec<&Value> {
+    let mut seen: HashSet<u64> = crate::extract_trail_id(json).into_iter().collect();
+    let mut bundled = Vec::new();
+    for path in PARENT_PATHS {
+        for key in BUNDLED_KEYS {
+            let trails = json
+                .pointer(path)
+                .and_then(|parent| parent.get(*key))
+                .and_then(Value::as_array);
+            for trail in trails.into_iter().flatten() {
+                let id = trail.get("id").and_then(Value::as_u64);
+                let duplicate = id.is_some_and(|id| !seen.insert(id));
+                if trail.is_object() && !duplicate {
+                    bundled.push(trail);
+                }
+            }
+        }
+    }
+    bundled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take() {
+        let mut json = json!({
+            "trails": [
+                {
+                    "id": 1,
+                    "name": "Main",
+                    "bundledTrails": [{ "id": 2, "name": "Bundled" }, { "id": 1 }]
+                }
+            ],
+            "nearbyTrails": [{ "id": 2 }, { "id": 3, "name": "Nearby" }, "junk"]
+        });
+        let bundled = take(&mut json);
+        let names: Vec<_> = bundled
+            .iter()
+            .map(|doc| doc.pointer("/trails/0/name").and_then(Value::as_str))
+            .collect();
+        assert_eq!(names, [Some("Bundled"), Some("Nearby")]);
+        assert_eq!(json.pointer("/trails/0/bundledTrails"), None);
+        assert_eq!(json.get("nearbyTrails"), None);
+
+        assert!(take(&mut json!({ "trails": [{ "id": 1 }] })).is_empty());
+    }
+}
@@ -0,0 +1,859 @@
+//! The command line interface. This is behind the `cli` feature, so that using the library
+//! doesn't mean pulling in clap.
+
+use crate::color::TrackColor;
+use crate::extractor::Extractors;
+use crate::filter::Filter;
+use crate::format::{self, Format, InputFormat, Layout};
+use crate::geofence::Geofence;
+use crate::jsonpath::JsonPath;
+use crate::log::LogFormat;
+use crate::poi::SymbolMap;
+use crate::preset::Preset;
+use crate::simplify::{self, SimplifyOptions, Target};
+use crate::source::InputSource;
+use crate::stats::DEFAULT_ELEVATION_THRESHOLD;
+use crate::timing::Pace;
+use crate::units::Units;
+use crate::{Error, Options, DEFAULT_MIN_POINTS};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Any option can also be set by an environment variable of this followed by its long name,
+/// such as ALLTRAILSGPX_COORD_DECIMALS.
+const ENV_PREFIX: &str = "ALLTRAILSGPX_";
+
+/// Options that only make sense on the command line itself.
+const UNSETTABLE: &[&str] = &["config", "show-config", "scan", "help", "version"];
+
+#[derive(clap::Parser, Debug)]
+#[command(author, version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The input JSON file containing the polyline data. Defaults to stdin. May be given
+    /// multiple times to convert several files in one go, and may be a .tar or .tar.gz
+    /// archive to convert every route in.
+    #[arg(short, long)]
+    pub input: Vec<String>,
+
+    /// What kind of file the input is. GPX, KML, GeoJSON and AllTrails JSON are recognised
+    /// automatically, but CSV must be asked for.
+    #[arg(long, value_enum)]
+    pub input_format: Option<InputFormat>,
+
+    /// Which layout to read when an AllTrails response has both a trails and a maps root,
+    /// rather than reading trails and warning about it.
+    #[arg(long, value_enum, value_name = "LAYOUT")]
+    pub prefer_format: Option<Layout>,
+
+    /// Refuse inputs larger than this, rather than reading them all into memory. Takes a
+    /// number of bytes with an optional K, M or G suffix, or 0 for no limit.
+    #[arg(long, value_name = "SIZE", default_value = "256M", value_parser = parse_size)]
+    pub max_input_size: u64,
+
+    /// Convert this encoded polyline, rather than reading an input file.
+    #[arg(long, value_name = "ENCODED", conflicts_with = "input")]
+    pub polyline: Option<String>,
+
+    /// Name the route this, rather than using the name from the input.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Which trail to convert when the AllTrails response has several, counting from zero in
+    /// the order they appear.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub trail_index: usize,
+
+    /// Which of the trail's routes to convert, by the route's ID in the response, when it has
+    /// several. Defaults to the first.
+    #[arg(long, value_name = "ID")]
+    pub route_id: Option<u64>,
+
+    /// Look for the route's polylines here before the usual places, as a JSON pointer such
+    /// as /route/encoded or a JSONPath such as $..pointsData. Each string matched is a line
+    /// segment of the route, in order. May be given multiple times to try several.
+    #[arg(long, value_name = "PATH")]
+    pub json_path: Vec<JsonPath>,
+
+    /// Reshape JSON input with this jq-like filter before reading the route from it, such as
+    /// '.response.body | fromjson' to unwrap a captured envelope. Supports keys, indexes,
+    /// .[], fromjson and pipes.
+    #[arg(long, value_name = "EXPR")]
+    pub filter: Option<Filter>,
+
+    /// The GPX file to create. Defaults to stdout. When converting multiple inputs, this is
+    /// a directory to write into, or a .zip archive to bundle the GPX files into.
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// The kinds of file to write, separated by commas. When writing more than one, each
+    /// gets the output path with its own extension. Defaults to the format matching the
+    /// output file's extension, or GPX.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub format: Vec<Format>,
+
+    /// Simplify the track, dropping detail smaller than this many meters.
+    #[arg(long, value_name = "METERS")]
+    pub simplify: Option<f64>,
+
+    /// Simplify the track down to at most this many points, for devices with a hard limit
+    /// on points per track.
+    #[arg(long, value_name = "N", conflicts_with = "simplify")]
+    pub target_points: Option<usize>,
+
+    /// The algorithm to use with --simplify or --target-points.
+    #[arg(long, value_enum, default_value_t)]
+    pub simplify_algorithm: simplify::Algorithm,
+
+    /// Join all of the route's line segments into a single track segment, for devices that
+    /// handle multi-segment tracks poorly.
+    #[arg(long)]
+    pub single_segment: bool,
+
+    /// With --single-segment, leave segments more than this many meters apart unjoined
+    /// rather than bridging them with a straight line.
+    #[arg(long, value_name = "METERS", requires = "single_segment")]
+    pub max_bridge: Option<f64>,
+
+    /// Follow the route back to its start, turning a one-way route into an out and back.
+    /// The way back is a separate track segment, unless --single-segment is given.
+    #[arg(long)]
+    pub round_trip: bool,
+
+    /// Swap latitude and longitude, for routes that were encoded with their axes the wrong
+    /// way around. Without this, swapped axes are corrected only when they're detected.
+    #[arg(long)]
+    pub swap_latlon: bool,
+
+    /// Write a route with no points rather than failing, for inputs whose polylines are
+    /// known to be empty.
+    #[arg(long)]
+    pub allow_empty: bool,
+
+    /// Refuse to convert routes with fewer points than this, which are usually truncated
+    /// downloads. Routes with no points at all are governed by --allow-empty instead.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MIN_POINTS)]
+    pub min_points: usize,
+
+    /// Warn about routes that look corrupted, such as ones thousands of kilometers long or
+    /// with points on different continents, rather than refusing to convert them.
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Refuse AllTrails routes with no name, rather than calling them after the trail's ID or
+    /// "Unnamed AllTrails route".
+    #[arg(long, conflicts_with = "lenient")]
+    pub strict: bool,
+
+    /// Transliterate the names of files written when converting several inputs to ASCII,
+    /// such as Zurich.gpx for Zürich.json, for filesystems that mangle anything else.
+    #[arg(long)]
+    pub ascii_file_names: bool,
+
+    /// Transliterate route names to ASCII, for devices that can't show anything else.
+    #[arg(long)]
+    pub ascii_names: bool,
+
+    /// Start a new track segment wherever consecutive points are more than this many meters
+    /// apart, rather than drawing a long straight line across the gap.
+    #[arg(long, value_name = "METERS")]
+    pub split_gap: Option<f64>,
+
+    /// Keep only the parts of the route inside the polygon in this GeoJSON file, such as a
+    /// park boundary. The route is split into separate segments where it crosses the edge.
+    #[arg(long, value_name = "GEOJSON_FILE")]
+    pub within: Option<String>,
+
+    /// Override which GPX symbols waypoints get, from a file of `kind = symbol` lines such as
+    /// `parking = Parking Area`. Kinds match the waypoint's type, or a word of its name.
+    #[arg(long, value_name = "FILE")]
+    pub symbols: Option<String>,
+
+    /// Also draw an elevation profile of the route as an SVG chart at this path, if the route
+    /// has elevation data.
+    #[arg(long, value_name = "SVG_FILE")]
+    pub profile: Option<String>,
+
+    /// Also write a cue sheet of the route to this CSV file, listing the distance to each
+    /// significant turn and point of interest, for following it from a printout.
+    #[arg(long, value_name = "CSV_FILE")]
+    pub cues: Option<String>,
+
+    /// Write a Markdown summary of each converted trail to this file.
+    #[arg(long, value_name = "MARKDOWN_FILE")]
+    pub summary: Option<String>,
+
+    /// Write a JSON report of the run to this file, or to stdout if it's -, saying which
+    /// inputs converted and which failed and why, with the total points and distance.
+    #[arg(long, value_name = "JSON_FILE")]
+    pub report: Option<String>,
+
+    /// Remove this many meters from each end of the route, so that shared files don't give
+    /// away exactly where it starts and finishes.
+    #[arg(long, value_name = "METERS")]
+    pub trim_ends: Option<f64>,
+
+    /// Move the start and finish to somewhere within this many meters of where they really
+    /// are, hiding the route in between, like a privacy zone.
+    #[arg(long, value_name = "METERS")]
+    pub fuzz_ends: Option<f64>,
+
+    /// Round written coordinates to this many decimal places. Five is about a meter, which
+    /// is all the precision AllTrails' polylines have anyway.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(0..=15))]
+    pub coord_decimals: Option<u32>,
+
+    /// Record whether the route is a loop, out and back or point to point in the GPX
+    /// metadata, working it out from the route's shape if the input doesn't say.
+    #[arg(long)]
+    pub detect_route_type: bool,
+
+    /// Split the route into several files of at most this many points each, for devices
+    /// that can't handle longer tracks. Each file is numbered, e.g. route-1.gpx.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(2..))]
+    pub max_points_per_file: Option<u64>,
+
+    /// The number to give the first track when several are written, in GPX's <number>
+    /// element, which some devices sort tracks by instead of by name.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub start_number: u32,
+
+    /// Write each route on the trail's map to its own file, for apps that only import the
+    /// first track of a file. --output is then a directory to write into, or a .zip archive.
+    #[arg(long, conflicts_with_all = ["polyline", "stream"])]
+    pub split_routes: bool,
+
+    /// How to name the files written by --split-routes, without the extension. {name} is the
+    /// route's name, {n} its number counting from one, and {count} the number of routes.
+    #[arg(long, value_name = "TEMPLATE", default_value = "{name}-{n}")]
+    pub route_file_name: String,
+
+    /// Make output byte-for-byte identical for identical input, so that it can be kept in
+    /// version control or content-addressed. This only affects zip archives, --provenance and
+    /// --add-times, as other output never has anything in it that varies between runs.
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Also write what was extracted from each route, such as its name, ID, statistics and
+    /// where in the input its geometry came from, to a JSON file next to it. The file is
+    /// named after the output, e.g. route.metadata.json.
+    #[arg(long)]
+    pub metadata_out: bool,
+
+    /// When converting several inputs, convert every one, rather than skipping those that
+    /// are the same trail as an earlier one by trail ID or shape.
+    #[arg(long)]
+    pub keep_duplicates: bool,
+
+    /// When converting several inputs, stop at the first that fails to convert, rather than
+    /// converting the rest and failing only if none of them converted.
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Also convert the trails an offline map pack bundles with the chosen one, under
+    /// bundledTrails or nearbyTrails, writing each as a track of the same GPX file.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "polyline",
+            "stream",
+            "split_routes",
+            "max_points_per_file",
+            "metadata_out",
+        ]
+    )]
+    pub include_bundled: bool,
+
+    /// Record where each route came from in its GPX track's extensions: the trail's ID and
+    /// page on AllTrails, when it was converted, and the version of alltrailsgpx.
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Set up the output for this app or device, such as by splitting tracks that are too
+    /// long for Garmin units. Options given explicitly take precedence.
+    #[arg(long, value_enum)]
+    pub preset: Option<Preset>,
+
+    /// Give each point a time, as though the route were walked at 5 km/h starting now (or at
+    /// the Unix epoch with --deterministic), for apps that won't import a track without them.
+    #[arg(long)]
+    pub add_times: bool,
+
+    /// Start the times given by --add-times at this RFC 3339 time, such as
+    /// 2024-05-01T09:00:00+01:00, so that the activity lands on the right day.
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    pub start_time: Option<OffsetDateTime>,
+
+    /// How the pace of --add-times varies: steady, or by Tobler's hiking function, which
+    /// slows down on climbs and speeds up on gentle descents.
+    #[arg(long, value_enum, default_value_t)]
+    pub pace: Pace,
+
+    /// Name this person or organisation as the author of the GPX, such as a club publishing
+    /// routes for its members.
+    #[arg(long, value_name = "NAME")]
+    pub author: Option<String>,
+
+    /// Link the GPX to this URL, such as the website of whoever is publishing it.
+    #[arg(long, value_name = "URL")]
+    pub link: Option<String>,
+
+    /// Write an estimate of how long the route takes to walk, by Naismith's rule, into the
+    /// GPX metadata's description.
+    #[arg(long)]
+    pub write_estimate: bool,
+
+    /// Write only the points of interest, such as parking and viewpoints, without the route.
+    #[arg(long)]
+    pub waypoints_only: bool,
+
+    /// Check GPX output against the GPX 1.1 schema before writing it, failing rather than
+    /// writing a file that strict importers might refuse.
+    #[arg(long)]
+    pub validate_gpx: bool,
+
+    /// Color the track, for apps that show every imported track in the same color. Either
+    /// #rrggbb, a Garmin color name such as DarkRed, or `auto` to give each trail its own
+    /// color from a palette. Written for OsmAnd, Locus Map and Garmin devices.
+    #[arg(long, value_name = "COLOR")]
+    pub color: Option<TrackColor>,
+
+    /// Print statistics about each converted route to stderr.
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Units for distances and heights in statistics, summaries, elevation profiles, cue
+    /// sheets and the scale bars of SVG maps.
+    /// Defaults to miles and feet in locales that use them, such as en_US, and kilometers and
+    /// meters elsewhere.
+    #[arg(long, value_enum)]
+    pub units: Option<Units>,
+
+    /// Don't print anything to stderr except errors.
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// How to print messages to stderr. JSON gives one object per line, for log pipelines.
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+
+    /// Read settings from this file of `option = value` lines, instead of
+    /// ~/.config/alltrailsgpx/config. Options given on the command line or in ALLTRAILSGPX_*
+    /// environment variables take precedence over it.
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<String>,
+
+    /// Print the settings in effect, grouped by where each came from, and exit.
+    #[arg(long)]
+    pub show_config: bool,
+
+    /// Instead of converting, try every long string in the input as an encoded polyline and
+    /// list the ones that could be routes, most plausible first. For finding the geometry in
+    /// responses of unfamiliar shapes.
+    #[arg(long)]
+    pub scan: bool,
+
+    /// Write GPX points as they're decoded, rather than building the whole track in memory
+    /// first, for very long recordings. Only the route's name and points are written, so
+    /// options that change the route other than --swap-latlon and --coord-decimals can't be
+    /// given with it.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "polyline",
+            "simplify",
+            "target_points",
+            "single_segment",
+            "round_trip",
+            "split_gap",
+            "within",
+            "symbols",
+            "profile",
+            "cues",
+            "summary",
+            "report",
+            "trim_ends",
+            "fuzz_ends",
+            "detect_route_type",
+            "max_points_per_file",
+            "metadata_out",
+            "provenance",
+            "preset",
+            "add_times",
+            "start_time",
+            "author",
+            "link",
+            "write_estimate",
+            "waypoints_only",
+            "validate_gpx",
+            "color",
+            "stats",
+        ]
+    )]
+    #[cfg_attr(feature = "osrm", arg(conflicts_with = "match_osrm"))]
+    pub stream: bool,
+
+    /// Read the input as a Mapbox Vector Tile with these coordinates instead of AllTrails
+    /// JSON, converting each LineString feature in it to a track.
+    #[cfg(feature = "mvt")]
+    #[arg(long, value_name = "Z/X/Y")]
+    pub mvt_tile: Option<crate::mvt::TileId>,
+
+    /// Only convert vector tile features from this layer.
+    #[cfg(feature = "mvt")]
+    #[arg(long, requires = "mvt_tile")]
+    pub mvt_layer: Option<String>,
+
+    /// Only convert vector tile features passing through this area, given as
+    /// min_lon,min_lat,max_lon,max_lat.
+    #[cfg(feature = "mvt")]
+    #[arg(long, requires = "mvt_tile", allow_hyphen_values = true)]
+    pub bbox: Option<crate::geo::BoundingBox>,
+
+    /// Snap the route onto OpenStreetMap paths using the OSRM server at this URL, such as
+    /// http://localhost:5000.
+    #[cfg(feature = "osrm")]
+    #[arg(long, value_name = "URL")]
+    pub match_osrm: Option<String>,
+
+    /// Ignore elevation changes smaller than this many meters when totalling ascent and
+    /// descent, to avoid inflating them with noise.
+    #[arg(long, value_name = "METERS", default_value_t = DEFAULT_ELEVATION_THRESHOLD)]
+    pub elevation_threshold: f64,
+}
+
+/// What to do. Options can be given before or after the command, and when there isn't one,
+/// routes are converted.
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Convert routes to GPX or another format.
+    Convert,
+
+    /// Print statistics about each route, without writing anything.
+    Stats,
+
+    /// List the trails in an AllTrails response, for choosing one with --trail-index.
+    List,
+
+    /// Combine the routes into one GPX file, with a track for each.
+    Merge,
+
+    /// Check that each route converts, reporting any problems, without writing anything.
+    Validate,
+
+    /// Compare two routes, for example to see whether AllTrails has changed a trail since it
+    /// was last downloaded. Both are read like --input, and conversion options apply to both.
+    Diff {
+        /// The earlier version of the route.
+        old: InputSource,
+        /// The later version of the route.
+        new: InputSource,
+    },
+}
+
+impl Args {
+    /// The inputs to read, with stdin standing in when none are given.
+    pub fn inputs(&self) -> Vec<InputSource> {
+        match self.input.as_slice() {
+            [] => vec![InputSource::Stdin],
+            inputs => inputs.iter().map(|input| input.as_str().into()).collect(),
+        }
+    }
+
+    /// The units to report in, either as given or from the locale.
+    pub fn units(&self) -> Units {
+        self.units.unwrap_or_else(Units::from_locale)
+    }
+
+    /// The formats to write, either as given or inferred from the output path.
+    pub fn formats(&self) -> Vec<Format> {
+        if !self.format.is_empty() {
+            return self.format.clone();
+        }
+        let inferred = self.output.as_deref().and_then(format::from_path);
+        vec![inferred.unwrap_or_default()]
+    }
+
+    pub fn options(&self) -> Result<Options, Error> {
+        let target = self
+            .simplify
+            .map(Target::Tolerance)
+            .or(self.target_points.map(Target::Points));
+
+        let mut options = Options {
+            format: self.formats()[0],
+            name: self.name.clone(),
+            trail_index: self.trail_index,
+            route_id: self.route_id,
+            input_format: self.input_format,
+            prefer_format: self.prefer_format,
+            max_input_size: Some(self.max_input_size).filter(|&size| size > 0),
+            simplify: target.map(|target| SimplifyOptions {
+                algorithm: self.simplify_algorithm,
+                target,
+            }),
+            swap_latlon: self.swap_latlon,
+            allow_empty: self.allow_empty,
+            min_points: self.min_points,
+            lenient: self.lenient,
+            strict: self.strict,
+            fallback_name: None,
+            ascii_file_names: self.ascii_file_names,
+            ascii_names: self.ascii_names,
+            merge_segments: self.single_segment,
+            round_trip: self.round_trip,
+            max_bridge: self.max_bridge,
+            split_gap: self.split_gap,
+            within: self.within.as_deref().map(Geofence::load).transpose()?,
+            symbols: self
+                .symbols
+                .as_deref()
+                .map(SymbolMap::load)
+                .transpose()?
+                .unwrap_or_default(),
+            trim_ends: self.trim_ends,
+            fuzz_ends: self.fuzz_ends,
+            coord_decimals: self.coord_decimals,
+            detect_route_type: self.detect_route_type,
+            max_points_per_file: self
+                .max_points_per_file
+                .map(|max| usize::try_from(max).unwrap_or(usize::MAX)),
+            start_number: self.start_number,
+            deterministic: self.deterministic,
+            metadata_sidecars: self.metadata_out,
+            keep_duplicates: self.keep_duplicates,
+            fail_fast: self.fail_fast,
+            include_bundled: self.include_bundled,
+            provenance: self.provenance,
+            color: self.color,
+            synthetic_times: self.add_times,
+            start_time: self.start_time,
+            pace: self.pace,
+            author: self.author.clone(),
+            publisher_link: self.link.clone(),
+            write_estimate: self.write_estimate,
+            waypoints_only: self.waypoints_only,
+            validate_gpx: self.validate_gpx,
+            units: self.units(),
+            #[cfg(feature = "osrm")]
+            match_osrm: self
+                .match_osrm
+                .as_deref()
+                .map(crate::osrm::Endpoint::parse)
+                .transpose()?,
+            elevation_threshold: self.elevation_threshold,
+            extractors: Extractors::default(),
+            filter: self.filter.clone(),
+        };
+        for path in &self.json_path {
+            options.extractors.register(path.clone());
+        }
+        if let Some(preset) = self.preset {
+            preset.apply(&mut options);
+        }
+        Ok(options)
+    }
+}
+
+/// Settings from a config file, as pairs of long option name and value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    path: String,
+    entries: Vec<(String, String)>,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::FileError {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::parse(path, &contents)
+    }
+
+    /// Parses `option = value` lines, where the option is the long name of a command line
+    /// option and flags take `true` or `false`. Blank lines and lines starting with `#` are
+    /// ignored.
+    fn parse(path: &str, contents: &str) -> Result<Self, Error> {
+        let settable = settable(&Args::command());
+        let mut entries = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason: String| Error::InvalidConfig {
+                path: path.to_string(),
+                line: i + 1,
+                reason,
+            };
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `option = value`".to_string()))?;
+            let name = name.trim();
+            if !settable.iter().any(|(long, _)| long == name) {
+                return Err(error(format!("--{name} can't be set here")));
+            }
+            entries.push((name.to_string(), value.trim().to_string()));
+        }
+        Ok(Self {
+            path: path.to_string(),
+            entries,
+        })
+    }
+
+    /// The value for an option, the last one winning if it's given more than once.
+    fn get(&self, long: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(name, _)| name == long)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// ~/.config/alltrailsgpx/config, or the same under $XDG_CONFIG_HOME if that's set.
+    fn default_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("alltrailsgpx").join("config"))
+    }
+}
+
+/// Parses the command line. Anything not given there comes from the environment, then the
+/// config file, then the built-in defaults. With --show-config, this prints the result and
+/// exits, like --help does.
+pub fn parse() -> Result<Args, Error> {
+    let argv: Vec<OsString> = env::args_os().collect();
+    let environment = environment();
+    let config = match config_path(&argv, &environment) {
+        Some(path) => Some(Config::load(&path)?),
+        None => Config::default_path()
+            .filter(|path| path.exists())
+            .map(|path| Config::load(&path.to_string_lossy()))
+            .transpose()?,
+    };
+
+    let command = command(config.as_ref(), &environment);
+    let matches = command.clone().get_matches_from(argv);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if args.show_config {
+        print!(
+            "{}",
+            describe(&command, &matches, config.as_ref(), &environment)
+        );
+        std::process::exit(0);
+    }
+    Ok(args)
+}
+
+/// Where --config or ALLTRAILSGPX_CONFIG says the config file is. This has to be found before
+/// parsing properly, since the config file changes how the rest is parsed.
+fn config_path(argv: &[OsString], environment: &HashMap<String, String>) -> Option<String> {
+    let mut argv = argv.iter().skip(1).map(|arg| arg.to_string_lossy());
+    while let Some(arg) = argv.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            return argv.next().map(|path| path.into_owned());
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.to_string());
+        }
+    }
+    environment.get(&env_var("config")).cloned()
+}
+
+/// The environment variables that could set options. They're read once, up front, rather
+/// than by clap, so that what they set can be told apart from the config file's settings.
+fn environment() -> HashMap<String, String> {
+    env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect()
+}
+
+fn env_var(long: &str) -> String {
+    format!("{ENV_PREFIX}{}", long.to_uppercase().replace('-', "_"))
+}
+
+fn parse_time(s: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(s, &Rfc3339)
+        .map_err(|_| format!("expected an RFC 3339 time like 2024-05-01T09:00:00Z, not {s:?}"))
+}
+
+/// Parses a size in bytes such as "512K" or "1G", in powers of 1024.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, shift) = match s.strip_suffix(['K', 'k']) {
+        Some(digits) => (digits, 10),
+        None => match s.strip_suffix(['M', 'm']) {
+            Some(digits) => (digits, 20),
+            None => match s.strip_suffix(['G', 'g']) {
+                Some(digits) => (digits, 30),
+                None => (s, 0),
+            },
+        },
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| format!("expected a size like 256M, not {s:?}"))
+}
+
+/// The long names and IDs of the options that the environment and config files can set.
+fn settable(command: &clap::Command) -> Vec<(String, String)> {
+    command
+        .get_arguments()
+        .filter_map(|arg| Some((arg.get_long()?.to_string(), arg.get_id().to_string())))
+        .filter(|(long, _)| !UNSETTABLE.contains(&long.as_str()))
+        .collect()
+}
+
+/// The parser for [`Args`], falling back to `environment` and then `config` for options not
+/// given on the command line.
+fn command(config: Option<&Config>, environment: &HashMap<String, String>) -> clap::Command {
+    let mut command = Args::command();
+    let options: Vec<_> = command
+        .get_arguments()
+        .filter(|arg| arg.get_long().is_some())
+        .map(|arg| arg.get_id().clone())
+        .collect();
+    for id in options {
+        command = command.mut_arg(id, |arg| arg.global(true));
+    }
+    for (long, id) in settable(&command) {
+        let value = environment
+            .get(&env_var(&long))
+            .map(String::as_str)
+            .or_else(|| config.and_then(|config| config.get(&long)))
+            .map(str::to_string);
+        command = command.mut_arg(id, |arg| match value {
+            Some(value) if arg.get_value_delimiter().is_some() => {
+                arg.default_values(value.split(',').map(|v| v.trim().to_string()))
+            }
+            Some(value) => arg.default_value(value),
+            None => arg,
+        });
+    }
+    command
+}
+
+/// The settings in effect as config file lines, grouped by where they came from.
+fn describe(
+    command: &clap::Command,
+    matches: &ArgMatches,
+    config: Option<&Config>,
+    environment: &HashMap<String, String>,
+) -> String {
+    let config_source = config.map(|config| format!("the config file {}", config.path));
+    let mut groups: Vec<(String, Vec<String>)> = [
+        Some("the command line".to_string()),
+        Some("the environment".to_string()),
+        config_source,
+        Some("the defaults".to_string()),
+    ]
+    .into_iter()
+    .map(|source| (source.unwrap_or_default(), Vec::new()))
+    .collect();
+
+    for (long, id) in settable(command) {
+        let Some(values) = matches.get_raw(&id) else {
+            continue;
+        };
+        let value: Vec<_> = values.map(|value| value.to_string_lossy()).collect();
+        let group = match matches.value_source(&id) {
+            Some(ValueSource::CommandLine) => 0,
+            _ if environment.contains_key(&env_var(&long)) => 1,
+            _ if config.is_some_and(|config| config.get(&long).is_some()) => 2,
+            _ => 3,
+        };
+        groups[group]
+            .1
+            .push(format!("{long} = {}", value.join(",")));
+    }
+
+    let mut described = String::new();
+    for (source, lines) in groups.iter().filter(|(_, lines)| !lines.is_empty()) {
+        let _ = writeln!(described, "# From {source}");
+        for line in lines {
+            let _ = writeln!(described, "{line}");
+        }
+    }
+    described
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("256M"), Ok(256 << 20));
+        assert_eq!(parse_size("512k"), Ok(512 << 10));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert!(parse_size("1T").is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let config = Config::parse(
+            "config",
+            "# Bristol walks\nname = From config\ntrail-index = 1\ncoord-decimals = 3\n",
+        )
+        .unwrap();
+        let environment = HashMap::from([
+            ("ALLTRAILSGPX_TRAIL_INDEX".to_string(), "2".to_string()),
+            ("ALLTRAILSGPX_COORD_DECIMALS".to_string(), "4".to_string()),
+        ]);
+        let command = command(Some(&config), &environment);
+        let matches = command
+            .clone()
+            .try_get_matches_from(["alltrailsgpx", "--coord-decimals", "5"])
+            .unwrap();
+
+        let args = Args::from_arg_matches(&matches).unwrap();
+        assert_eq!(args.coord_decimals, Some(5));
+        assert_eq!(args.trail_index, 2);
+        assert_eq!(args.name.as_deref(), Some("From config"));
+        assert!(!args.swap_latlon);
+
+        let described = describe(&command, &matches, Some(&config), &environment);
+        assert!(described.starts_with(
+            "# From the command line\ncoord-decimals = 5\n\
+             # From the environment\ntrail-index = 2\n\
+             # From the config file config\nname = From config\n"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(matches!(
+            Config::parse("config", "name = Ok\ncolour = red"),
+            Err(Error::InvalidConfig { line: 2, .. })
+        ));
+        assert!(Config::parse("config", "show-config = true").is_err());
+    }
+
+    #[test]
+    fn test_stream_conflicts() {
+        let parse = |args: &[&str]| {
+            command(None, &HashMap::new())
+                .try_get_matches_from([&["alltrailsgpx", "--stream"], args].concat())
+        };
+        assert!(parse(&["--coord-decimals", "5"]).is_ok());
+        assert!(parse(&["--trim-ends", "200"]).is_err());
+        assert!(parse(&["--simplify", "5"]).is_err());
+    }
+}
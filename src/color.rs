@@ -0,0 +1,174 @@
+//! Track colors, written in the display extensions of the apps that understand them so that
+//! several trails on one map can be told apart.
+
+use std::fmt;
+use std::str::FromStr;
+
+pub const OSMAND_PREFIX: &str = "osmand";
+pub const OSMAND_NAMESPACE: &str = "https://osmand.net";
+/// The gpx_style extension, which Locus Map reads.
+pub const GPX_STYLE_PREFIX: &str = "gpx_style";
+pub const GPX_STYLE_NAMESPACE: &str = "http://www.topografix.com/GPX/gpx_style/0/2";
+pub const GPXX_PREFIX: &str = "gpxx";
+pub const GPXX_NAMESPACE: &str = "http://www.garmin.com/xmlschemas/GpxExtensions/v3";
+
+/// The namespaces used by [`display_extensions`], as (prefix, URI) pairs.
+pub const DISPLAY_NAMESPACES: [(&str, &str); 3] = [
+    (OSMAND_PREFIX, OSMAND_NAMESPACE),
+    (GPX_STYLE_PREFIX, GPX_STYLE_NAMESPACE),
+    (GPXX_PREFIX, GPXX_NAMESPACE),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Garmin devices only take a color from this list, so others are shown as the nearest.
+const GARMIN_COLORS: &[(&str, Rgb)] = &[
+    ("Black", Rgb(0, 0, 0)),
+    ("DarkRed", Rgb(139, 0, 0)),
+    ("DarkGreen", Rgb(0, 100, 0)),
+    ("DarkYellow", Rgb(128, 128, 0)),
+    ("DarkBlue", Rgb(0, 0, 139)),
+    ("DarkMagenta", Rgb(139, 0, 139)),
+    ("DarkCyan", Rgb(0, 139, 139)),
+    ("LightGray", Rgb(211, 211, 211)),
+    ("DarkGray", Rgb(169, 169, 169)),
+    ("Red", Rgb(255, 0, 0)),
+    ("Green", Rgb(0, 255, 0)),
+    ("Yellow", Rgb(255, 255, 0)),
+    ("Blue", Rgb(0, 0, 255)),
+    ("Magenta", Rgb(255, 0, 255)),
+    ("Cyan", Rgb(0, 255, 255)),
+    ("White", Rgb(255, 255, 255)),
+];
+
+/// Colors for `--color auto`, picked to stand out from each other and from map backgrounds.
+const PALETTE: &[Rgb] = &[
+    Rgb(0xe6, 0x19, 0x4b),
+    Rgb(0x3c, 0xb4, 0x4b),
+    Rgb(0x43, 0x63, 0xd8),
+    Rgb(0xf5, 0x82, 0x31),
+    Rgb(0x91, 0x1e, 0xb4),
+    Rgb(0x42, 0xd4, 0xf4),
+    Rgb(0xf0, 0x32, 0xe6),
+    Rgb(0x9a, 0x63, 0x24),
+];
+
+impl Rgb {
+    /// The name of the nearest color a Garmin device can show.
+    pub fn garmin_name(self) -> &'static str {
+        let distance = |other: Rgb| {
+            [(self.0, other.0), (self.1, other.1), (self.2, other.2)]
+                .iter()
+                .map(|&(a, b)| (i32::from(a) - i32::from(b)).pow(2))
+                .sum::<i32>()
+        };
+        GARMIN_COLORS
+            .iter()
+            .min_by_key(|(_, rgb)| distance(*rgb))
+            .map_or("Blue", |(name, _)| name)
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    /// Parses "#rrggbb", with or without the "#", or a Garmin color name such as "DarkRed".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, rgb)) = GARMIN_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(*rgb);
+        }
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Self(r, g, b)),
+            _ => Err(format!("expected a color like #e6194b or Red, not {s:?}")),
+        }
+    }
+}
+
+/// What color to give tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackColor {
+    Fixed(Rgb),
+    /// A color from a palette, chosen from the route itself so that each trail always gets
+    /// the same one and different trails usually don't.
+    Auto,
+}
+
+impl TrackColor {
+    /// The color for a route with the given [`crate::fingerprint`].
+    pub fn for_route(self, fingerprint: u64) -> Rgb {
+        match self {
+            Self::Fixed(rgb) => rgb,
+            Self::Auto => PALETTE[(fingerprint % PALETTE.len() as u64) as usize],
+        }
+    }
+}
+
+impl FromStr for TrackColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            _ => s.parse().map(Self::Fixed),
+        }
+    }
+}
+
+/// Elements for a track's extensions that give it this color in OsmAnd, Locus Map and on
+/// Garmin devices.
+pub fn display_extensions(color: Rgb) -> Vec<String> {
+    vec![
+        format!("<{OSMAND_PREFIX}:color>{color}</{OSMAND_PREFIX}:color>"),
+        format!(
+            "<{GPX_STYLE_PREFIX}:line><{GPX_STYLE_PREFIX}:color>{}</{GPX_STYLE_PREFIX}:color></{GPX_STYLE_PREFIX}:line>",
+            color.to_string().trim_start_matches('#').to_uppercase()
+        ),
+        format!(
+            "<{GPXX_PREFIX}:TrackExtension><{GPXX_PREFIX}:DisplayColor>{}</{GPXX_PREFIX}:DisplayColor></{GPXX_PREFIX}:TrackExtension>",
+            color.garmin_name()
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("#e6194b".parse(), Ok(Rgb(0xe6, 0x19, 0x4b)));
+        assert_eq!("E6194B".parse(), Ok(Rgb(0xe6, 0x19, 0x4b)));
+        assert_eq!("darkred".parse(), Ok(Rgb(139, 0, 0)));
+        assert!("#e6194".parse::<Rgb>().is_err());
+        assert_eq!("auto".parse(), Ok(TrackColor::Auto));
+    }
+
+    #[test]
+    fn test_garmin_name() {
+        assert_eq!(Rgb(0xe6, 0x19, 0x4b).garmin_name(), "Red");
+        assert_eq!(Rgb(0x10, 0x10, 0x80).garmin_name(), "DarkBlue");
+    }
+
+    #[test]
+    fn test_display_extensions() {
+        let extensions = display_extensions(Rgb(0xe6, 0x19, 0x4b));
+        assert_eq!(extensions[0], "<osmand:color>#e6194b</osmand:color>");
+        assert!(extensions[1].contains("<gpx_style:color>E6194B</gpx_style:color>"));
+        assert!(extensions[2].contains("<gpxx:DisplayColor>Red</gpxx:DisplayColor>"));
+    }
+}
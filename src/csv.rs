@@ -0,0 +1,102 @@
+//! CSV output, with a row per track point, for spreadsheets and scripts.
+
+use gpx::{Track, Waypoint};
+use std::io::{self, Write};
+
+/// Writes a header and then a row per point. Segments are numbered from 1 within each track,
+/// and elevation is left empty where it isn't known.
+pub fn write_csv(tracks: &[Track], mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "track,segment,latitude,longitude,elevation")?;
+    for track in tracks {
+        let name = quote(track.name.as_deref().unwrap_or(""));
+        for (i, segment) in track.segments.iter().enumerate() {
+            for point in &segment.points {
+                let coord = point.point();
+                write!(writer, "{name},{},{},{},", i + 1, coord.y(), coord.x())?;
+                if let Some(elevation) = point.elevation {
+                    write!(writer, "{elevation}")?;
+                }
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Writes a header and then a row per waypoint, with the name and type left empty where they
+/// aren't known, as are elevations.
+pub fn write_waypoints(waypoints: &[Waypoint], mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "name,type,latitude,longitude,elevation")?;
+    for waypoint in waypoints {
+        let coord = waypoint.point();
+        write!(
+            writer,
+            "{},{},{},{},",
+            quote(waypoint.name.as_deref().unwrap_or("")),
+            quote(waypoint.type_.as_deref().unwrap_or("")),
+            coord.y(),
+            coord.x()
+        )?;
+        if let Some(elevation) = waypoint.elevation {
+            write!(writer, "{elevation}")?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+/// Quotes a field if it contains anything that would otherwise break the row.
+pub(crate) fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::TrackSegment;
+
+    #[test]
+    fn test_rows_per_point() {
+        let mut high = Waypoint::new(Point::new(-2.61, 51.46));
+        high.elevation = Some(120.5);
+        let track = Track {
+            name: Some("Up, \"over\" and back".to_string()),
+            segments: vec![TrackSegment {
+                points: vec![Waypoint::new(Point::new(-2.6, 51.45)), high],
+            }],
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        write_csv(&[track], &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "track,segment,latitude,longitude,elevation\n\
+             \"Up, \"\"over\"\" and back\",1,51.45,-2.6,\n\
+             \"Up, \"\"over\"\" and back\",1,51.46,-2.61,120.5\n"
+        );
+    }
+
+    #[test]
+    fn test_rows_per_waypoint() {
+        let mut parking = Waypoint::new(Point::new(-2.6, 51.45));
+        parking.name = Some("Main car park".to_string());
+        parking.type_ = Some("parking".to_string());
+        let mut view = Waypoint::new(Point::new(-2.61, 51.46));
+        view.elevation = Some(120.5);
+
+        let mut output = Vec::new();
+        write_waypoints(&[parking, view], &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "name,type,latitude,longitude,elevation\n\
+             Main car park,parking,51.45,-2.6,\n\
+             ,,51.46,-2.61,120.5\n"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Cue sheets: where the route turns and what it passes, by distance along it, for following
+//! it from a printout when devices die.
+
+use crate::csv::quote;
+use crate::geo::{bearing, haversine_distance};
+use crate::units::Units;
+use geo_types::Coord;
+use gpx::{Track, Waypoint};
+use std::io::{self, Write};
+
+/// How far before and after a point to look when working out how much the route turns there,
+/// in meters, so that the wobble between closely spaced points isn't taken for turns.
+const TURN_WINDOW_M: f64 = 30.0;
+
+/// How many degrees the route has to change direction by to get a cue.
+const MIN_TURN_DEGREES: f64 = 45.0;
+
+/// Turns sharper than this many degrees are called sharp.
+const SHARP_TURN_DEGREES: f64 = 120.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// How far along the route the cue is, in meters.
+    pub distance_m: f64,
+    /// What to do or look out for, such as "Turn left" or "Main car park".
+    pub text: String,
+}
+
+/// Lists the start and finish, each significant turn, and the waypoints at the nearest point
+/// of the route to them, in the order they come along the route. Distance doesn't accumulate
+/// across gaps between segments.
+pub fn cue_sheet(track: &Track, waypoints: &[Waypoint]) -> Vec<Cue> {
+    // Each point with how far along the route it is.
+    let mut along: Vec<(Coord<f64>, f64)> = Vec::new();
+    let mut cues = Vec::new();
+    for segment in &track.segments {
+        let start = along.len();
+        let mut distance = along.last().map_or(0.0, |&(_, d)| d);
+        for (i, point) in segment.points.iter().enumerate() {
+            let coord = point.point().0;
+            if i > 0 {
+                distance += haversine_distance(along[along.len() - 1].0, coord);
+            }
+            along.push((coord, distance));
+        }
+        cues.extend(turns(&along[start..]));
+    }
+
+    let Some(&(_, total)) = along.last() else {
+        return Vec::new();
+    };
+    cues.insert(0, cue(0.0, "Start"));
+    for waypoint in waypoints {
+        let position = waypoint.point().0;
+        let nearest = along.iter().min_by(|a, b| {
+            haversine_distance(a.0, position).total_cmp(&haversine_distance(b.0, position))
+        });
+        if let Some(&(_, distance)) = nearest {
+            let text = [&waypoint.name, &waypoint.type_]
+                .into_iter()
+                .find_map(|text| text.as_deref())
+                .unwrap_or("Waypoint");
+            cues.push(cue(distance, text));
+        }
+    }
+    cues.push(cue(total, "Finish"));
+    // A stable sort keeps the start first and the finish last among cues at the same distance.
+    cues.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+    cues
+}
+
+fn cue(distance_m: f64, text: &str) -> Cue {
+    Cue {
+        distance_m,
+        text: text.to_string(),
+    }
+}
+
+/// The significant turns along a segment, at the point of each where it turns the most.
+fn turns(points: &[(Coord<f64>, f64)]) -> Vec<Cue> {
+    let mut turns = Vec::new();
+    // The sharpest point of the turn being passed through, if the route is turning.
+    let mut turning: Option<(f64, f64)> = None;
+    for (i, &(coord, distance)) in points.iter().enumerate() {
+        let before = points[..i]
+            .iter()
+            .rev()
+            .find(|&&(_, d)| distance - d >= TURN_WINDOW_M);
+        let after = points[i + 1..]
+            .iter()
+            .find(|&&(_, d)| d - distance >= TURN_WINDOW_M);
+        let angle = match (before, after) {
+            (Some(&(before, _)), Some(&(after, _))) => {
+                (bearing(coord, after) - bearing(before, coord) + 540.0) % 360.0 - 180.0
+            }
+            _ => 0.0,
+        };
+
+        if angle.abs() >= MIN_TURN_DEGREES {
+            if !turning.is_some_and(|(_, sharpest)| sharpest.abs() >= angle.abs()) {
+                turning = Some((distance, angle));
+            }
+        } else if let Some((distance, angle)) = turning.take() {
+            turns.push(turn(distance, angle));
+        }
+    }
+    turns.extend(turning.map(|(distance, angle)| turn(distance, angle)));
+    turns
+}
+
+fn turn(distance_m: f64, angle: f64) -> Cue {
+    let side = if angle > 0.0 { "right" } else { "left" };
+    let text = if angle.abs() > SHARP_TURN_DEGREES {
+        format!("Sharp {side}")
+    } else {
+        format!("Turn {side}")
+    };
+    Cue { distance_m, text }
+}
+
+/// Writes the cues as CSV, with distances in `units` to two decimal places.
+pub fn write_cues_csv(cues: &[Cue], units: Units, mut writer: impl Write) -> io::Result<()> {
+    let (per_unit, symbol) = units.distance_unit();
+    writeln!(writer, "distance ({symbol}),cue")?;
+    for cue in cues {
+        writeln!(
+            writer,
+            "{:.2},{}",
+            cue.distance_m / per_unit,
+            quote(&cue.text)
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::TrackSegment;
+
+    #[test]
+    fn test_cue_sheet() {
+        // About 1.1 km north, then 0.7 km east: a right turn.
+        let points = (0..=10)
+            .map(|i| (-2.6, 51.45 + f64::from(i) * 0.001))
+            .chain((1..=10).map(|i| (-2.6 + f64::from(i) * 0.001, 51.46)))
+            .map(|(x, y)| Waypoint::new(Point::new(x, y)))
+            .collect();
+        let track = Track {
+            segments: vec![TrackSegment { points }],
+            ..Default::default()
+        };
+        let mut parking = Waypoint::new(Point::new(-2.6001, 51.4522));
+        parking.name = Some("Car park".to_string());
+
+        let cues = cue_sheet(&track, &[parking]);
+        let texts: Vec<&str> = cues.iter().map(|cue| cue.text.as_str()).collect();
+        assert_eq!(texts, ["Start", "Car park", "Turn right", "Finish"]);
+        assert!((cues[2].distance_m - 1112.0).abs() < 5.0, "{cues:?}");
+
+        let mut output = Vec::new();
+        write_cues_csv(&cues[..2], Units::Metric, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "distance (km),cue\n0.00,Start\n0.22,Car park\n"
+        );
+    }
+}
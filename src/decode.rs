@@ -0,0 +1,156 @@
+use geo_types::Coord;
+use thiserror::Error;
+
+/// A polyline couldn't be decoded. `offset` is the character at which decoding went wrong,
+/// which is approximate since the damage may have started earlier.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("invalid polyline data at character {offset}")]
+pub struct DecodeError {
+    pub offset: usize,
+}
+
+/// Decodes a Google encoded polyline into lon/lat coordinates.
+///
+/// Unlike the polyline crate, this doesn't insist on the coordinates being in range, so that
+/// routes with swapped axes can still be decoded and then corrected.
+pub fn decode_polyline(polyline: &str, precision: u32) -> Result<Vec<Coord<f64>>, DecodeError> {
+    Points::new(polyline, precision).collect()
+}
+
+/// Decodes a polyline one coordinate at a time. Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct Points<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    factor: f64,
+    lat: i64,
+    lon: i64,
+    failed: bool,
+}
+
+impl<'a> Points<'a> {
+    pub fn new(polyline: &'a str, precision: u32) -> Self {
+        Self {
+            bytes: polyline.as_bytes(),
+            offset: 0,
+            factor: 10_f64.powi(i32::try_from(precision).unwrap_or(i32::MAX)),
+            lat: 0,
+            lon: 0,
+            failed: false,
+        }
+    }
+}
+
+impl Iterator for Points<'_> {
+    type Item = Result<Coord<f64>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset >= self.bytes.len() {
+            return None;
+        }
+        let start = self.offset;
+        let position = decode_value(self.bytes, &mut self.offset)
+            .and_then(|lat| Ok((lat, decode_value(self.bytes, &mut self.offset)?)))
+            .and_then(|(lat, lon)| {
+                // Deltas can be large enough in corrupt input to overflow the running totals.
+                let overflow = DecodeError { offset: start };
+                Ok((
+                    self.lat.checked_add(lat).ok_or(overflow)?,
+                    self.lon.checked_add(lon).ok_or(overflow)?,
+                ))
+            });
+        match position {
+            Ok((lat, lon)) => {
+                self.lat = lat;
+                self.lon = lon;
+                Some(Ok(Coord {
+                    x: self.lon as f64 / self.factor,
+                    y: self.lat as f64 / self.factor,
+                }))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for Points<'_> {}
+
+fn decode_value(bytes: &[u8], offset: &mut usize) -> Result<i64, DecodeError> {
+    let mut result = 0_i64;
+    let mut shift = 0;
+
+    loop {
+        let invalid = DecodeError { offset: *offset };
+        let chunk = match bytes.get(*offset) {
+            Some(&byte @ 63..=126) => i64::from(byte - 63),
+            _ => return Err(invalid),
+        };
+        if shift > 60 {
+            return Err(invalid);
+        }
+
+        result |= (chunk & 0x1f) << shift;
+        shift += 5;
+        *offset += 1;
+
+        if chunk < 0x20 {
+            break;
+        }
+    }
+
+    Ok(if result & 1 == 1 {
+        !(result >> 1)
+    } else {
+        result >> 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_reference_polyline() {
+        // The example from Google's polyline algorithm documentation.
+        let coords = decode_polyline("_p~iF~ps|U_ulLnnqC_mqNvxq`@", 5).unwrap();
+        let expected = [(-120.2, 38.5), (-120.95, 40.7), (-126.453, 43.252)];
+        assert_eq!(coords.len(), expected.len());
+        for (coord, (x, y)) in coords.iter().zip(expected) {
+            assert!((coord.x - x).abs() < 1e-9 && (coord.y - y).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_points_stop_at_error() {
+        let mut points = Points::new("_p~iF~ps|U_ulL", 5);
+        assert!(points.next().unwrap().is_ok());
+        assert_eq!(points.next(), Some(Err(DecodeError { offset: 14 })));
+        assert_eq!(points.next(), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Each point adds half of i64::MAX to the latitude, so the third overflows it.
+        let polyline = "}~~~~~~~~~~~F?".repeat(3);
+        assert_eq!(
+            decode_polyline(&polyline, 5),
+            Err(DecodeError { offset: 28 })
+        );
+    }
+
+    #[test]
+    fn test_decode_reports_offset() {
+        // The second coordinate is missing its longitude.
+        assert_eq!(
+            decode_polyline("_p~iF~ps|U_ulL", 5),
+            Err(DecodeError { offset: 14 })
+        );
+        assert_eq!(
+            decode_polyline("_p~iF ps|U", 5),
+            Err(DecodeError { offset: 5 })
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Errors shown with the part of the input they're about, for working out why a response
+//! didn't convert without digging through it by hand.
+
+use crate::lookup::{pointer_span, Attempt, Divergence, LookupFailure};
+use crate::Error;
+use miette::{LabeledSpan, NamedSource};
+use serde_json::Value;
+use std::ops::Range;
+
+/// Lines longer than this are cut down around the part being pointed at, since responses are
+/// often minified onto a single line many megabytes long.
+const MAX_LINE_BYTES: usize = 200;
+
+/// How much of a long line to show either side of the part being pointed at.
+const CONTEXT_BYTES: usize = 60;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("{message}")]
+pub struct JsonDiagnostic {
+    message: String,
+    #[source_code]
+    snippet: NamedSource<String>,
+    #[label(collection)]
+    labels: Vec<LabeledSpan>,
+    #[help]
+    help: Option<String>,
+}
+
+/// Explains an error converting the JSON document `input`, read from `name`, by pointing at
+/// where in it things went wrong. Pointers in the error are taken to refer to the document as
+/// given, so this shouldn't be used after picking another trail or route or reshaping it with
+/// [`Options::filter`](crate::Options::filter) or
+/// [`Options::extractors`](crate::Options::extractors). Returns `None` for errors that aren't
+/// about the content of a JSON document, or for documents in a layout that's rewritten before
+/// being read, such as GraphQL responses.
+pub fn diagnose(error: &Error, name: &str, input: &[u8]) -> Option<JsonDiagnostic> {
+    let text = std::str::from_utf8(input).ok()?;
+    let json: Option<Value> = serde_json::from_str(text).ok();
+    if json.as_ref().is_some_and(is_rewritten) {
+        return None;
+    }
+    let (span, label) = match error {
+        Error::PolylineNotFound(failure) | Error::RouteNameNotFound(failure) => {
+            let attempt = closest_attempt(failure)?;
+            (pointer_span(text, &attempt.found), describe(attempt))
+        }
+        Error::PolylineNotString { pointer } | Error::RouteNameNotString { pointer } => (
+            pointer_span(text, pointer.as_str()),
+            "expected a string".to_string(),
+        ),
+        Error::InvalidPolyline { pointer, .. } | Error::CoordinateOutOfRange { pointer, .. } => (
+            pointer_span(text, pointer.as_str()),
+            "this polyline".to_string(),
+        ),
+        Error::InvalidPosition { pointer } => (
+            pointer_span(text, pointer.as_str()),
+            "expected [longitude, latitude]".to_string(),
+        ),
+        Error::JsonParseError(e) => {
+            let offset = offset_of(text, e.line(), e.column());
+            let reason = e.to_string();
+            let reason = reason.split(" at line ").next().unwrap_or_default();
+            (offset.map(|offset| offset..offset), reason.to_string())
+        }
+        _ => return None,
+    };
+
+    let (snippet, labels) = match span {
+        Some(span) => {
+            let (snippet, span) = excerpt(text, span);
+            let labels = vec![LabeledSpan::new(Some(label), span.start, span.len())];
+            (snippet, labels)
+        }
+        None => (String::new(), Vec::new()),
+    };
+    Some(JsonDiagnostic {
+        message: error.to_string(),
+        snippet: NamedSource::new(name, snippet),
+        labels,
+        help: json.as_ref().map(suggestion),
+    })
+}
+
+/// The attempt that got furthest into the document, which is usually the one the input was
+/// meant to match.
+fn closest_attempt(failure: &LookupFailure) -> Option<&Attempt> {
+    failure
+        .attempts
+        .iter()
+        .max_by_key(|attempt| attempt.found.matches('/').count())
+}
+
+fn describe(attempt: &Attempt) -> String {
+    let wanted = match &attempt.divergence {
+        Divergence::MissingKey(key) => format!("no \"{key}\" here"),
+        Divergence::MissingIndex { index, len } => {
+            format!("no element {index} here, only {len}")
+        }
+        Divergence::NotContainer(kind) => format!("this is {kind}"),
+    };
+    format!("{wanted}, looking for {}", attempt.path)
+}
+
+/// Whether the document is in a layout that's rewritten into another before anything is read
+/// from it, so that pointers in errors are into the rewritten document rather than this one.
+fn is_rewritten(json: &Value) -> bool {
+    crate::normalize_layout(json.clone()).map_or(true, |normalized| normalized != *json)
+}
+
+/// What to try next, going by what the document looks like.
+fn suggestion(json: &Value) -> String {
+    let kind = json.get("type").and_then(Value::as_str);
+    if matches!(kind, Some("Feature" | "FeatureCollection")) {
+        return "this looks like GeoJSON; pass --input-format geojson".to_string();
+    }
+    if json.get("activities").is_some() || json.pointer("/maps/0/activity").is_some() {
+        return "this looks like an activities response, which keeps the recorded line rather \
+                than the trail's; pass --json-path with where its polyline is, such as \
+                $..pointsData"
+            .to_string();
+    }
+    "pass --scan to look for anything in the input that decodes to a route".to_string()
+}
+
+/// The byte offset of a 1-based line and column, as serde_json reports them.
+fn offset_of(text: &str, line: usize, column: usize) -> Option<usize> {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    Some((start + column.saturating_sub(1)).min(text.len()))
+}
+
+/// The text to show for a span, and where the span is within it. Documents with reasonably
+/// short lines are shown whole, so that line numbers are right. Otherwise, just the part of
+/// the line around the start of the span is shown.
+fn excerpt(text: &str, span: Range<usize>) -> (String, Range<usize>) {
+    let line_start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[span.start..]
+        .find('\n')
+        .map_or(text.len(), |i| span.start + i);
+    if line_end - line_start <= MAX_LINE_BYTES {
+        return (text.to_string(), span);
+    }
+
+    let floor = |mut i: usize| {
+        while !text.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let start = floor(span.start.saturating_sub(CONTEXT_BYTES).max(line_start));
+    let end = floor((span.start + CONTEXT_BYTES).min(line_end));
+    let span_end = span.end.min(end).max(span.start);
+    (
+        text[start..end].to_string(),
+        span.start - start..span_end - start,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert, Options};
+    use miette::Diagnostic;
+
+    #[test]
+    fn test_missing_polyline() {
+        let input = r#"{"trails": [{"name": "Abbots Leigh", "defaultMap": {"routes": [{"lineSegments": []}]}}]}"#;
+        let error = convert(input.as_bytes(), &Options::default()).unwrap_err();
+        let diagnostic = diagnose(&error, "abbots.json", input.as_bytes()).unwrap();
+
+        let label = diagnostic.labels().unwrap().next().unwrap();
+        assert_eq!(&input[label.offset()..label.offset() + label.len()], "[]");
+        assert!(label
+            .label()
+            .unwrap()
+            .starts_with("no element 0 here, only 0"));
+        assert!(diagnostic.help().unwrap().to_string().contains("--scan"));
+    }
+
+    #[test]
+    fn test_activity_and_rewritten_layouts() {
+        let input = r#"{"maps": [{"activity": {"id": 1}, "routes": []}]}"#;
+        let error = convert(input.as_bytes(), &Options::default()).unwrap_err();
+        let diagnostic = diagnose(&error, "activity.json", input.as_bytes()).unwrap();
+        assert!(diagnostic
+            .help()
+            .unwrap()
+            .to_string()
+            .contains("--json-path"));
+
+        // Pointers into a GraphQL response are into the offline layout it's rewritten into.
+        let input = r#"{"data": {"trail": {"name": "Abbots Leigh"}}}"#;
+        let error = convert(input.as_bytes(), &Options::default()).unwrap_err();
+        assert!(diagnose(&error, "graphql.json", input.as_bytes()).is_none());
+    }
+
+    #[test]
+    fn test_excerpt() {
+        let text = format!("{{\"padding\": \"{}\", \"id\": 1}}", "x".repeat(300));
+        let start = text.find("1}").unwrap();
+        let (snippet, span) = excerpt(&text, start..start + 1);
+        assert!(snippet.len() <= 2 * CONTEXT_BYTES);
+        assert_eq!(&snippet[span], "1");
+    }
+}
@@ -0,0 +1,98 @@
+//! Comparing two versions of a route, such as an old download against a fresh one.
+
+use crate::geo::path_length;
+use crate::similarity::{frechet_distance, hausdorff_distance, segment_coords};
+use gpx::Track;
+use std::fmt;
+
+/// How two routes differ. Where there's a pair of values, the first is for the old route and
+/// the second for the new one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteDiff {
+    pub length_m: (f64, f64),
+    pub points: (usize, usize),
+    /// The furthest any point of either route is from the other route, in meters. This is
+    /// the Hausdorff distance between them.
+    pub max_deviation_m: f64,
+    /// The Fréchet distance between the routes in meters, which unlike the maximum deviation
+    /// also accounts for the order the routes go in.
+    pub frechet_m: f64,
+}
+
+pub fn diff(old: &Track, new: &Track) -> RouteDiff {
+    let length = |track: &Track| -> f64 {
+        segment_coords(track)
+            .map(|coords| path_length(&coords))
+            .sum()
+    };
+    let points = |track: &Track| track.segments.iter().map(|s| s.points.len()).sum();
+
+    RouteDiff {
+        length_m: (length(old), length(new)),
+        points: (points(old), points(new)),
+        max_deviation_m: hausdorff_distance(old, new),
+        frechet_m: frechet_distance(old, new),
+    }
+}
+
+impl fmt::Display for RouteDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (old_length, new_length) = self.length_m;
+        writeln!(
+            f,
+            "Length: {:.2} km -> {:.2} km ({:+.2} km)",
+            old_length / 1000.0,
+            new_length / 1000.0,
+            (new_length - old_length) / 1000.0
+        )?;
+        let (old_points, new_points) = self.points;
+        writeln!(
+            f,
+            "Points: {old_points} -> {new_points} ({:+})",
+            new_points as i64 - old_points as i64
+        )?;
+        writeln!(f, "Maximum deviation: {:.1} m", self.max_deviation_m)?;
+        write!(f, "Fréchet distance: {:.1} m", self.frechet_m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::{TrackSegment, Waypoint};
+
+    fn track(coords: &[(f64, f64)]) -> Track {
+        Track {
+            segments: vec![TrackSegment {
+                points: coords
+                    .iter()
+                    .map(|&(x, y)| Waypoint::new(Point::new(x, y)))
+                    .collect(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = track(&[(0.0, 0.0), (0.0, 0.01), (0.0, 0.02)]);
+        // The same line drawn with more points, plus a detour about 111 m east halfway.
+        let new = track(&[
+            (0.0, 0.0),
+            (0.0, 0.005),
+            (0.001, 0.01),
+            (0.0, 0.015),
+            (0.0, 0.02),
+        ]);
+
+        let changed = diff(&old, &new);
+        assert_eq!(changed.points, (3, 5));
+        assert!(changed.length_m.1 > changed.length_m.0);
+        assert!((changed.max_deviation_m - 111.2).abs() < 0.5);
+
+        let same = diff(&old, &old);
+        assert_eq!(same.max_deviation_m, 0.0);
+        assert_eq!(same.length_m.0, same.length_m.1);
+    }
+}
@@ -0,0 +1,175 @@
+//! A parsed AllTrails response, whatever API it came from, for library users who want to look
+//! inside one without depending on where each layout keeps things.
+
+use crate::stats::TrailStats;
+use crate::{json, poi, Conversion, Error, Options, Polyline, TrailListing};
+use geo_types::Coord;
+use gpx::Waypoint;
+use serde_json::Value;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// Which kind of response a document is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    /// detail=offline, with the trail under `trails`. Trail detail and sync responses are
+    /// rewritten into this layout, so they're offline documents too.
+    Offline,
+    /// detail=deep, with the trail's map under `maps`.
+    Deep,
+    /// A recorded activity, which comes in the deep layout with the recording under the map's
+    /// `activity`.
+    Activity,
+    /// A response from the GraphQL API, with the trail under `data`.
+    Graphql,
+    /// Something else, which may still convert with [`Options::extractors`].
+    Unknown,
+}
+
+/// An AllTrails response, rewritten into the layout everything is read from.
+#[derive(Debug, Clone)]
+pub struct AllTrailsDocument {
+    kind: DocumentKind,
+    json: Value,
+}
+
+impl AllTrailsDocument {
+    /// Classifies a response and rewrites it into the offline or deep layout, failing only if
+    /// it's a GraphQL response reporting that the query failed.
+    pub fn from_value(json: &Value) -> Result<Self, Error> {
+        let graphql = json.get("data").is_some()
+            && json.get("trails").is_none()
+            && json.get("maps").is_none();
+        let json = crate::normalize_layout(json.clone())?;
+        let kind = if graphql {
+            DocumentKind::Graphql
+        } else if json.get("trails").is_some() {
+            DocumentKind::Offline
+        } else if json.pointer("/maps/0/activity").is_some() {
+            DocumentKind::Activity
+        } else if json.get("maps").is_some() {
+            DocumentKind::Deep
+        } else {
+            DocumentKind::Unknown
+        };
+        Ok(Self { kind, json })
+    }
+
+    pub fn kind(&self) -> DocumentKind {
+        self.kind
+    }
+
+    /// The document in the offline or deep layout, for anything the accessors don't cover.
+    pub fn as_value(&self) -> &Value {
+        &self.json
+    }
+
+    /// The trails in the document, of which the rest of the accessors read the first.
+    pub fn trails(&self) -> Vec<TrailListing> {
+        crate::trail_listings(&self.json).unwrap_or_default()
+    }
+
+    pub fn trail_id(&self) -> Option<u64> {
+        crate::extract_trail_id(&self.json)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        crate::extract_route_name(&self.json).ok().map(|name| *name)
+    }
+
+    /// Where AllTrails says the trail is.
+    pub fn location(&self) -> Option<Coord<f64>> {
+        crate::extract_location(&self.json)
+    }
+
+    pub fn trail_stats(&self) -> Option<TrailStats> {
+        crate::extract_trail_stats(&self.json)
+    }
+
+    pub fn difficulty(&self) -> Option<String> {
+        crate::extract_difficulty(&self.json)
+    }
+
+    pub fn overview(&self) -> Option<String> {
+        crate::extract_overview(&self.json)
+    }
+
+    pub fn link(&self) -> Option<String> {
+        crate::extract_trail_link(&self.json)
+    }
+
+    /// The polylines of the first route on the trail's map, a segment at a time.
+    pub fn polylines(&self) -> Result<Vec<Polyline<'_>>, Error> {
+        crate::extract_polylines(&self.json)
+    }
+
+    /// The points of interest marked on the trail's map.
+    pub fn waypoints(&self) -> Vec<Waypoint> {
+        poi::extract_waypoints(&self.json)
+    }
+
+    /// Converts the route that `options` choose, as [`convert`](crate::convert) would.
+    pub fn convert(&self, options: &Options) -> Result<Conversion, Error> {
+        let started = Instant::now();
+        let route = crate::read_document(self.json.clone(), options)?;
+        crate::convert_route(route, options, started)
+    }
+}
+
+impl FromStr for AllTrailsDocument {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_value(&json::parse(s.as_bytes())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_kind() {
+        let kind = |json: Value| AllTrailsDocument::from_value(&json).unwrap().kind();
+        assert_eq!(kind(json!({ "trails": [] })), DocumentKind::Offline);
+        assert_eq!(kind(json!({ "maps": [] })), DocumentKind::Deep);
+        assert_eq!(
+            kind(json!({ "maps": [{ "activity": { "id": 1 } }] })),
+            DocumentKind::Activity
+        );
+        assert_eq!(
+            kind(json!({ "data": { "trail": { "name": "Abbots Leigh" } } })),
+            DocumentKind::Graphql
+        );
+        assert_eq!(kind(json!({ "routes": [] })), DocumentKind::Unknown);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let document: AllTrailsDocument = json!({
+            "trails": [{
+                "id": 10_236_086,
+                "name": "Abbots Leigh",
+                "defaultMap": {
+                    "routes": [{
+                        "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }]
+                    }]
+                }
+            }]
+        })
+        .to_string()
+        .parse()
+        .unwrap();
+        assert_eq!(document.kind(), DocumentKind::Offline);
+        assert_eq!(document.trail_id(), Some(10_236_086));
+        assert_eq!(document.name(), Some("Abbots Leigh"));
+        assert_eq!(document.trails().len(), 1);
+        assert_eq!(document.polylines().unwrap().len(), 1);
+        assert!(document.waypoints().is_empty());
+
+        let conversion = document.convert(&Options::default()).unwrap();
+        assert_eq!(conversion.name, "Abbots Leigh");
+        assert_eq!(conversion.stats.points, 2);
+    }
+}
@@ -0,0 +1,113 @@
+//! Extractors that library users can register to find the route in JSON that alltrailsgpx
+//! doesn't know the shape of, such as a private API's, without having to fork it.
+
+use crate::lookup::JsonPointer;
+use crate::Polyline;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// Finds the encoded polylines of a route in a JSON document.
+pub trait Extractor: Send + Sync {
+    /// The route's polylines, one per line segment in order, or `None` if the document isn't
+    /// one this extractor knows.
+    fn extract<'json>(&self, json: &'json Value) -> Option<Vec<Polyline<'json>>>;
+}
+
+/// Reads the polyline from the first of several JSON pointers that holds a string.
+#[derive(Debug, Clone)]
+pub struct PointerExtractor(Vec<String>);
+
+impl PointerExtractor {
+    pub fn new(pointers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self(pointers.into_iter().map(Into::into).collect())
+    }
+}
+
+impl Extractor for PointerExtractor {
+    fn extract<'json>(&self, json: &'json Value) -> Option<Vec<Polyline<'json>>> {
+        self.0.iter().find_map(|pointer| {
+            let data = json.pointer(pointer)?.as_str()?;
+            Some(vec![Polyline::new(
+                data,
+                JsonPointer::new(pointer.as_str()),
+            )])
+        })
+    }
+}
+
+struct FnExtractor<F>(F);
+
+impl<F> Extractor for FnExtractor<F>
+where
+    F: Fn(&Value) -> Option<Vec<Polyline<'_>>> + Send + Sync,
+{
+    fn extract<'json>(&self, json: &'json Value) -> Option<Vec<Polyline<'json>>> {
+        (self.0)(json)
+    }
+}
+
+/// The extractors to try, in the order they were registered, before the built-in ones.
+#[derive(Clone, Default)]
+pub struct Extractors(Vec<Arc<dyn Extractor>>);
+
+impl Extractors {
+    pub fn register(&mut self, extractor: impl Extractor + 'static) -> &mut Self {
+        self.0.push(Arc::new(extractor));
+        self
+    }
+
+    /// Registers a closure as an extractor.
+    pub fn register_fn<F>(&mut self, extract: F) -> &mut Self
+    where
+        F: Fn(&Value) -> Option<Vec<Polyline<'_>>> + Send + Sync + 'static,
+    {
+        self.register(FnExtractor(extract))
+    }
+
+    /// Registers a [`PointerExtractor`] for these pointers.
+    pub fn register_pointers(
+        &mut self,
+        pointers: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.register(PointerExtractor::new(pointers))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The polylines found by the first extractor that knows the document.
+    pub fn extract<'json>(&self, json: &'json Value) -> Option<Vec<Polyline<'json>>> {
+        self.0.iter().find_map(|extractor| extractor.extract(json))
+    }
+}
+
+impl fmt::Debug for Extractors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Extractors({} registered)", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extractors_in_order() {
+        let json = json!({ "shape": { "encoded": "_p~iF~ps|U" }, "other": "_ulLnnqC" });
+        let mut extractors = Extractors::default();
+        extractors
+            .register_pointers(["/missing", "/shape/encoded"])
+            .register_fn(|json| {
+                let data = json.get("other")?.as_str()?;
+                Some(vec![Polyline::new(data, JsonPointer::new("/other"))])
+            });
+
+        let polylines = extractors.extract(&json).unwrap();
+        assert_eq!(polylines.len(), 1);
+        assert_eq!(polylines[0].pointer().as_str(), "/shape/encoded");
+        assert!(Extractors::default().extract(&json).is_none());
+    }
+}
@@ -0,0 +1,255 @@
+//! A small subset of jq, for reshaping captures that wrap the response in something else,
+//! such as a HAR entry or an API envelope, before the route is looked for in them.
+//!
+//! Supported are the identity `.`, keys (`.name`, `."name"` or `.["name"]`), indexes (`.[0]`,
+//! or `.[-1]` from the end), iterating over arrays and objects (`.[]`), `fromjson` to parse a
+//! string holding JSON, and pipes between them (`|`). A filter that produces several values
+//! produces an array of them.
+
+use crate::Error;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Op {
+    Key(String),
+    Index(i64),
+    Iterate,
+    FromJson,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    source: String,
+    ops: Vec<Op>,
+}
+
+impl Filter {
+    /// Runs the filter over a document, failing with [`Error::FilterFailed`] when it produces
+    /// nothing or is applied to the wrong kind of value.
+    pub fn apply(&self, json: Value) -> Result<Value, Error> {
+        let mut values = vec![json];
+        for op in &self.ops {
+            let mut next = Vec::new();
+            for value in values {
+                next.extend(op.apply(value).map_err(Error::FilterFailed)?);
+            }
+            values = next;
+        }
+        match values.len() {
+            0 => Err(Error::FilterFailed(format!("{self} produced nothing"))),
+            1 => Ok(values.remove(0)),
+            _ => Ok(Value::Array(values)),
+        }
+    }
+}
+
+impl Op {
+    fn apply(&self, value: Value) -> Result<Vec<Value>, String> {
+        match (self, value) {
+            (Self::Key(_) | Self::Index(_), Value::Null) => Ok(vec![Value::Null]),
+            (Self::Key(key), Value::Object(mut fields)) => {
+                Ok(vec![fields.remove(key).unwrap_or(Value::Null)])
+            }
+            (Self::Index(index), Value::Array(mut items)) => {
+                let i = if *index < 0 {
+                    items.len().checked_sub(index.unsigned_abs() as usize)
+                } else {
+                    Some(*index as usize)
+                };
+                let item = i.filter(|&i| i < items.len()).map(|i| items.swap_remove(i));
+                Ok(vec![item.unwrap_or(Value::Null)])
+            }
+            (Self::Iterate, Value::Array(items)) => Ok(items),
+            (Self::Iterate, Value::Object(fields)) => Ok(fields.into_values().collect()),
+            (Self::FromJson, Value::String(text)) => serde_json::from_str(&text)
+                .map(|json| vec![json])
+                .map_err(|e| format!("fromjson: {e}")),
+            (Self::Key(key), other) => Err(format!("cannot index {} with {key:?}", kind(&other))),
+            (Self::Index(index), other) => {
+                Err(format!("cannot index {} with {index}", kind(&other)))
+            }
+            (Self::Iterate, other) => Err(format!("cannot iterate over {}", kind(&other))),
+            (Self::FromJson, other) => {
+                Err(format!("fromjson needs a string, not {}", kind(&other)))
+            }
+        }
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+            .map(|ops| Self {
+                source: s.to_string(),
+                ops,
+            })
+            .map_err(|reason| format!("invalid filter {s:?}: {reason}"))
+    }
+}
+
+fn parse(mut rest: &str) -> Result<Vec<Op>, &'static str> {
+    let mut ops = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("fromjson") {
+            ops.push(Op::FromJson);
+            rest = after;
+        } else if rest.starts_with('.') {
+            rest = parse_path(rest, &mut ops)?;
+        } else {
+            return Err("expected . or fromjson");
+        }
+
+        rest = rest.trim_start();
+        match rest.strip_prefix('|') {
+            Some(after) => rest = after,
+            None if rest.is_empty() => return Ok(ops),
+            None => return Err("expected |"),
+        }
+    }
+}
+
+/// Parses a path such as `.trail.routes[0]` off the front of `rest`, returning what follows it.
+fn parse_path<'a>(mut rest: &'a str, ops: &mut Vec<Op>) -> Result<&'a str, &'static str> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut first = true;
+    loop {
+        if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+            if rest.starts_with(is_name) {
+                let end = rest.find(|c| !is_name(c)).unwrap_or(rest.len());
+                ops.push(Op::Key(rest[..end].to_string()));
+                rest = &rest[end..];
+            } else if rest.starts_with('"') {
+                let (key, after) = parse_string(rest)?;
+                ops.push(Op::Key(key));
+                rest = after;
+            } else if rest.starts_with('.') || (!first && !rest.starts_with('[')) {
+                return Err("expected a key after .");
+            }
+        } else if let Some(inside) = rest.strip_prefix('[') {
+            let inside = inside.trim_start();
+            let (op, after) = if inside.starts_with('"') {
+                let (key, after) = parse_string(inside)?;
+                (Op::Key(key), after)
+            } else {
+                let end = inside.find(']').ok_or("unclosed [")?;
+                let op = match inside[..end].trim() {
+                    "" => Op::Iterate,
+                    index => Op::Index(index.parse().map_err(|_| "expected an index")?),
+                };
+                (op, &inside[end..])
+            };
+            rest = after.trim_start().strip_prefix(']').ok_or("unclosed [")?;
+            ops.push(op);
+        } else {
+            return Ok(rest);
+        }
+        first = false;
+    }
+}
+
+/// Parses a JSON string off the front of `rest`, returning it and what follows it.
+fn parse_string(rest: &str) -> Result<(String, &str), &'static str> {
+    let mut escaped = false;
+    for (i, c) in rest.char_indices().skip(1) {
+        match c {
+            '\\' => escaped = !escaped,
+            '"' if !escaped => {
+                let key = serde_json::from_str(&rest[..=i]).map_err(|_| "invalid string")?;
+                return Ok((key, &rest[i + 1..]));
+            }
+            _ => escaped = false,
+        }
+    }
+    Err("unclosed string")
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn apply(filter: &str, json: Value) -> Result<Value, Error> {
+        filter.parse::<Filter>().unwrap().apply(json)
+    }
+
+    #[test]
+    fn test_apply() {
+        let json = json!({
+            "log": { "entries": [
+                { "response": { "content": { "text": "{\"trails\": [{\"name\": \"Abbots Leigh\"}]}" } } },
+                { "response": { "content": { "text": "{}" } } }
+            ] }
+        });
+        assert_eq!(
+            apply(
+                ".log.entries[0].response.content.text | fromjson",
+                json.clone()
+            )
+            .unwrap(),
+            json!({ "trails": [{ "name": "Abbots Leigh" }] })
+        );
+        assert_eq!(
+            apply(
+                r#".["log"]."entries"[-1] | .response.content.text"#,
+                json.clone()
+            )
+            .unwrap(),
+            json!("{}")
+        );
+        assert_eq!(
+            apply(
+                ".log.entries[].response | .content.text | fromjson",
+                json.clone()
+            )
+            .unwrap()
+            .as_array()
+            .map(Vec::len),
+            Some(2)
+        );
+        assert_eq!(apply(".", json.clone()).unwrap(), json);
+        assert_eq!(apply(".missing.deeper", json.clone()).unwrap(), Value::Null);
+        assert!(apply(".log.entries.response", json).is_err());
+    }
+
+    #[test]
+    fn test_apply_nothing() {
+        assert!(matches!(
+            apply(".[]", json!([])),
+            Err(Error::FilterFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("log".parse::<Filter>().is_err());
+        assert!(".log.".parse::<Filter>().is_err());
+        assert!(".log[".parse::<Filter>().is_err());
+        assert!(".[\"log]".parse::<Filter>().is_err());
+        assert!(". .log".parse::<Filter>().is_err());
+        assert!("..log".parse::<Filter>().is_err());
+        assert!(".log[5:]".parse::<Filter>().is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Recognising the same trail when it's been fetched from different places.
+
+use crate::geo::haversine_distance;
+use geo_types::Coord;
+use gpx::Track;
+use std::collections::HashMap;
+
+/// How far apart to sample the route, in meters.
+const SAMPLE_INTERVAL_M: f64 = 50.0;
+
+/// Geohash length to quantize samples to. Seven characters is a cell of about 150 meters.
+const GEOHASH_PRECISION: usize = 7;
+
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Computes a fingerprint of the route's shape, for recognising the same trail fetched from
+/// different AllTrails endpoints or converted from different files.
+///
+/// The route is sampled every 50 meters and each sample reduced to its geohash cell, so the
+/// fingerprint doesn't depend on how many points the route has, how precise they are, or
+/// which way round it goes. Routes running right along a cell edge may still come out
+/// differently, so a mismatch doesn't prove two routes differ.
+pub fn fingerprint(track: &Track) -> u64 {
+    let mut cells = Vec::new();
+    for segment in &track.segments {
+        let coords: Vec<Coord<f64>> = segment.points.iter().map(|p| p.point().0).collect();
+        for sample in resample(&coords, SAMPLE_INTERVAL_M) {
+            let cell = geohash(sample, GEOHASH_PRECISION);
+            if cells.last() != Some(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+
+    let forward = hash_cells(cells.iter());
+    let backward = hash_cells(cells.iter().rev());
+    forward.min(backward)
+}
+
+/// Recognises routes that are the same trail as one seen before, by the trail's ID or by
+/// [`fingerprint`].
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    ids: HashMap<u64, usize>,
+    fingerprints: HashMap<u64, usize>,
+}
+
+impl Duplicates {
+    /// The index of the earlier route this one duplicates, or `None` after recording it under
+    /// `index` if it's new. Empty routes shouldn't be given a fingerprint, as they all have
+    /// the same one.
+    pub fn check(
+        &mut self,
+        index: usize,
+        trail_id: Option<u64>,
+        fingerprint: Option<u64>,
+    ) -> Option<usize> {
+        let earlier = trail_id
+            .and_then(|id| self.ids.get(&id))
+            .or_else(|| fingerprint.and_then(|f| self.fingerprints.get(&f)))
+            .copied();
+        if earlier.is_none() {
+            self.ids.extend(trail_id.map(|id| (id, index)));
+            self.fingerprints.extend(fingerprint.map(|f| (f, index)));
+        }
+        earlier
+    }
+}
+
+/// Points every `interval_m` meters along the line, plus its last point.
+fn resample(coords: &[Coord<f64>], interval_m: f64) -> Vec<Coord<f64>> {
+    let Some(&first) = coords.first() else {
+        return Vec::new();
+    };
+
+    let mut samples = vec![first];
+    // How far along the current leg the next sample falls.
+    let mut next = interval_m;
+    for pair in coords.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let length = haversine_distance(a, b);
+        while next <= length {
+            samples.push(a + (b - a) * (next / length));
+            next += interval_m;
+        }
+        next -= length;
+    }
+    if samples.last() != coords.last() {
+        samples.extend(coords.last());
+    }
+    samples
+}
+
+/// Encodes a coordinate as a geohash of the given number of characters.
+pub(crate) fn geohash(coord: Coord<f64>, precision: usize) -> String {
+    let (mut lon, mut lat) = ((-180.0, 180.0), (-90.0, 90.0));
+    let mut hash = String::with_capacity(precision);
+    let mut even = true;
+    while hash.len() < precision {
+        let mut index = 0;
+        for _ in 0..5 {
+            // Bits alternate between longitude and latitude, starting with longitude.
+            let (range, value) = if even {
+                (&mut lon, coord.x)
+            } else {
+                (&mut lat, coord.y)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            index <<= 1;
+            if value >= mid {
+                index |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even = !even;
+        }
+        hash.push(char::from(GEOHASH_ALPHABET[index]));
+    }
+    hash
+}
+
+/// FNV-1a, which unlike std's hasher is guaranteed not to change between Rust releases.
+fn hash_cells<'a>(cells: impl Iterator<Item = &'a String>) -> u64 {
+    cells
+        .flat_map(|cell| cell.bytes().chain([b'/']))
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::{TrackSegment, Waypoint};
+
+    fn track(coords: &[(f64, f64)]) -> Track {
+        Track {
+            segments: vec![TrackSegment {
+                points: coords
+                    .iter()
+                    .map(|&(x, y)| Waypoint::new(Point::new(x, y)))
+                    .collect(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_geohash() {
+        let coord = Coord {
+            x: 10.40744,
+            y: 57.64911,
+        };
+        assert_eq!(geohash(coord, 11), "u4pruydqqvj");
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_density_and_direction() {
+        let sparse = [(-2.6, 51.4), (-2.6, 51.42), (-2.58, 51.42)];
+        let dense: Vec<(f64, f64)> = (0..=20)
+            .map(|i| (-2.6, 51.4 + f64::from(i) * 0.001))
+            .chain((1..=20).map(|i| (-2.6 + f64::from(i) * 0.001, 51.42)))
+            .collect();
+        let reversed: Vec<(f64, f64)> = sparse.iter().rev().copied().collect();
+
+        let expected = fingerprint(&track(&sparse));
+        assert_eq!(fingerprint(&track(&dense)), expected);
+        assert_eq!(fingerprint(&track(&reversed)), expected);
+
+        let elsewhere = [(-2.6, 51.4), (-2.6, 51.42), (-2.62, 51.42)];
+        assert_ne!(fingerprint(&track(&elsewhere)), expected);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut duplicates = Duplicates::default();
+        assert_eq!(duplicates.check(0, Some(10236086), Some(1)), None);
+        assert_eq!(duplicates.check(1, None, Some(2)), None);
+        assert_eq!(duplicates.check(2, Some(10236086), Some(3)), Some(0));
+        assert_eq!(duplicates.check(3, None, Some(2)), Some(1));
+        assert_eq!(duplicates.check(4, Some(7), None), None);
+        assert_eq!(duplicates.check(5, None, None), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! The kinds of file alltrailsgpx can write.
+
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Format {
+    /// GPX 1.1, for GPS devices and mapping apps.
+    #[default]
+    Gpx,
+
+    /// A GeoJSON FeatureCollection, for web maps and GIS tools.
+    #[cfg_attr(feature = "cli", value(name = "geojson"))]
+    GeoJson,
+
+    /// A row per point, for spreadsheets.
+    Csv,
+
+    /// KML, for Google Earth.
+    Kml,
+
+    /// A standalone web page showing the route on a map, for checking a conversion in the
+    /// browser.
+    Html,
+
+    /// A static SVG image of the route with a scale bar, for embedding in documents.
+    Svg,
+}
+
+impl Format {
+    /// The file extension for this format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Gpx => "gpx",
+            Format::GeoJson => "geojson",
+            Format::Csv => "csv",
+            Format::Kml => "kml",
+            Format::Html => "html",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+/// The kinds of input alltrailsgpx can read. All but CSV are recognised automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum InputFormat {
+    /// An AllTrails API response.
+    #[cfg_attr(feature = "cli", value(name = "alltrails"))]
+    AllTrails,
+
+    Gpx,
+
+    Kml,
+
+    #[cfg_attr(feature = "cli", value(name = "geojson"))]
+    GeoJson,
+
+    /// Latitude, longitude and optionally elevation columns, with or without a header row.
+    Csv,
+}
+
+/// The layouts of AllTrails responses, which differ in where they keep the trail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Layout {
+    /// detail=offline, with trails under `trails` and each map as the trail's `defaultMap`.
+    Offline,
+
+    /// detail=deep, with each trail's map under `maps`.
+    Deep,
+}
+
+/// Picks the format matching a path's extension, e.g. KML for "route.kml".
+pub fn from_path(path: &str) -> Option<Format> {
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "gpx" => Some(Format::Gpx),
+        "geojson" => Some(Format::GeoJson),
+        "csv" => Some(Format::Csv),
+        "kml" => Some(Format::Kml),
+        "html" | "htm" => Some(Format::Html),
+        "svg" => Some(Format::Svg),
+        _ => None,
+    }
+}
+
+/// Derives the path to write a format to from the --output path, by swapping its extension,
+/// e.g. "route.gpx" becomes "route.geojson".
+pub fn output_path(template: &str, format: Format) -> String {
+    Path::new(template)
+        .with_extension(format.extension())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Numbers a path for one of several parts, e.g. "route.gpx" becomes "route-2.gpx".
+pub fn numbered_path(path: &str, number: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{number}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{number}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        assert_eq!(
+            output_path("out/route.gpx", Format::GeoJson),
+            "out/route.geojson"
+        );
+        assert_eq!(output_path("route", Format::Csv), "route.csv");
+        assert_eq!(numbered_path("out/route.gpx", 2), "out/route-2.gpx");
+        assert_eq!(numbered_path("route", 1), "route-1");
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(from_path("route.kml"), Some(Format::Kml));
+        assert_eq!(from_path("out/Route.HTM"), Some(Format::Html));
+        assert_eq!(from_path("routes.zip"), None);
+        assert_eq!(from_path("-"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! Garmin's TrackPointExtension, which is where Garmin devices and Connect expect sensor
+//! readings such as heart rate to be in a GPX file.
+
+use crate::xml::{self, Token};
+use std::fmt::Write;
+
+pub const TRACK_POINT_EXTENSION_PREFIX: &str = "gpxtpx";
+pub const TRACK_POINT_EXTENSION_NAMESPACE: &str =
+    "http://www.garmin.com/xmlschemas/TrackPointExtension/v2";
+
+/// Sensor readings recorded at a track point.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SensorReadings {
+    /// Air temperature in degrees Celsius.
+    pub temperature: Option<f64>,
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u8>,
+    /// Cadence in revolutions per minute.
+    pub cadence: Option<u8>,
+}
+
+impl SensorReadings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The readings as a TrackPointExtension element, for a track point's extensions.
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!("<{TRACK_POINT_EXTENSION_PREFIX}:TrackPointExtension>\n");
+        let mut element = |name: &str, value: String| {
+            let _ = writeln!(
+                xml,
+                "  <{TRACK_POINT_EXTENSION_PREFIX}:{name}>{value}</{TRACK_POINT_EXTENSION_PREFIX}:{name}>"
+            );
+        };
+        // The schema requires this order.
+        if let Some(temperature) = self.temperature {
+            element("atemp", temperature.to_string());
+        }
+        if let Some(heart_rate) = self.heart_rate {
+            element("hr", heart_rate.to_string());
+        }
+        if let Some(cadence) = self.cadence {
+            element("cad", cadence.to_string());
+        }
+        xml.push_str(&format!(
+            "</{TRACK_POINT_EXTENSION_PREFIX}:TrackPointExtension>"
+        ));
+        xml
+    }
+}
+
+/// Reads the sensor readings of each `trkpt` in a GPX document, in document order. Both
+/// versions of Garmin's extension are understood, since they use the same element names.
+pub(crate) fn read_track_point_readings(gpx: &str) -> Vec<SensorReadings> {
+    let mut readings = Vec::new();
+    let mut in_point = false;
+    let mut element = "";
+    for token in xml::tokens(gpx) {
+        match token {
+            Token::Start("trkpt") => {
+                readings.push(SensorReadings::default());
+                in_point = true;
+            }
+            Token::End("trkpt") => in_point = false,
+            Token::Start(name) => element = name,
+            Token::End(_) => element = "",
+            Token::Text(text) if in_point => {
+                let Some(point) = readings.last_mut() else {
+                    continue;
+                };
+                let text = text.trim();
+                match element {
+                    "atemp" => point.temperature = text.parse().ok(),
+                    "hr" => point.heart_rate = text.parse().ok(),
+                    "cad" => point.cadence = text.parse().ok(),
+                    _ => {}
+                }
+            }
+            Token::Text(_) => {}
+        }
+    }
+    readings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_track_point_readings() {
+        let gpx = r#"<gpx><trk><trkseg>
+            <trkpt lat="51.45" lon="-2.6"><ele>12</ele><extensions>
+              <gpxtpx:TrackPointExtension>
+                <gpxtpx:atemp>14.5</gpxtpx:atemp><gpxtpx:hr>131</gpxtpx:hr>
+              </gpxtpx:TrackPointExtension>
+            </extensions></trkpt>
+            <trkpt lat="51.46" lon="-2.61"/>
+        </trkseg></trk></gpx>"#;
+
+        let readings = read_track_point_readings(gpx);
+        assert_eq!(readings.len(), 2);
+        assert_eq!(
+            readings[0],
+            SensorReadings {
+                temperature: Some(14.5),
+                heart_rate: Some(131),
+                cadence: None,
+            }
+        );
+        assert!(readings[1].is_empty());
+    }
+
+    #[test]
+    fn test_to_xml() {
+        let readings = SensorReadings {
+            heart_rate: Some(131),
+            cadence: Some(80),
+            ..Default::default()
+        };
+        assert_eq!(
+            readings.to_xml(),
+            "<gpxtpx:TrackPointExtension>\n  <gpxtpx:hr>131</gpxtpx:hr>\n  \
+             <gpxtpx:cad>80</gpxtpx:cad>\n</gpxtpx:TrackPointExtension>"
+        );
+    }
+}
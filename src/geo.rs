@@ -0,0 +1,252 @@
+use geo_types::Coord;
+use std::str::FromStr;
+
+/// Mean Earth radius in meters, as used by the haversine formula.
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// A point along a route, which may carry more than where it is, such as the elevation the
+/// input gave. Routes are worked on generically over their points so that what a point
+/// carries stays with it, while points made up along the way, from a position alone, carry
+/// nothing.
+pub trait Located: Clone + From<Coord<f64>> {
+    fn coord(&self) -> Coord<f64>;
+}
+
+impl Located for Coord<f64> {
+    fn coord(&self) -> Coord<f64> {
+        *self
+    }
+}
+
+/// Great-circle distance in meters between two lon/lat coordinates.
+pub fn haversine_distance(a: Coord<f64>, b: Coord<f64>) -> f64 {
+    let (lat_a, lat_b) = (a.y.to_radians(), b.y.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.x - a.x).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Initial bearing in degrees clockwise from north, from 0 up to 360, of the great circle
+/// from `a` to `b`.
+pub fn bearing(a: Coord<f64>, b: Coord<f64>) -> f64 {
+    let (lat_a, lat_b) = (a.y.to_radians(), b.y.to_radians());
+    let d_lon = (b.x - a.x).to_radians();
+    let y = d_lon.sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Total length in meters of a path through the given coordinates.
+pub fn path_length(coords: &[Coord<f64>]) -> f64 {
+    coords
+        .windows(2)
+        .map(|pair| haversine_distance(pair[0], pair[1]))
+        .sum()
+}
+
+/// Projects lon/lat coordinates onto a local plane measured in meters, using an
+/// equirectangular projection centred on the first coordinate. This is accurate enough for
+/// comparing distances within a single route.
+pub fn project_local(coords: &[Coord<f64>]) -> Vec<Coord<f64>> {
+    let Some(origin) = coords.first() else {
+        return Vec::new();
+    };
+    let scale = EARTH_RADIUS_M.to_radians();
+    let lon_scale = scale * origin.y.to_radians().cos();
+
+    coords
+        .iter()
+        .map(|c| Coord {
+            x: (c.x - origin.x) * lon_scale,
+            y: (c.y - origin.y) * scale,
+        })
+        .collect()
+}
+
+/// Routes this far from where AllTrails says the trail is are considered misplaced.
+const LOCALITY_RADIUS_M: f64 = 100_000.0;
+
+/// Guesses whether some tool upstream swapped latitude and longitude before encoding. That's
+/// certain if "latitudes" exceed ±90 while the "longitudes" would be valid latitudes.
+/// Otherwise, if the trail's stated location is known, the axes are considered swapped when
+/// the route is nowhere near it but would be once swapped back. The coordinates are only
+/// iterated over once, so they needn't all be in memory.
+pub fn axes_look_swapped(
+    coords: impl IntoIterator<Item = Coord<f64>>,
+    reference: Option<Coord<f64>>,
+) -> bool {
+    let mut coords = coords.into_iter();
+    let Some(first) = coords.next() else {
+        return false;
+    };
+
+    let (mut lat_out_of_range, mut lon_fits_lat) = (false, true);
+    for c in std::iter::once(first).chain(coords) {
+        lat_out_of_range |= c.y.abs() > 90.0;
+        lon_fits_lat &= c.x.abs() <= 90.0;
+    }
+    if lat_out_of_range {
+        return lon_fits_lat;
+    }
+
+    reference.is_some_and(|reference| {
+        let swapped = Coord {
+            x: first.y,
+            y: first.x,
+        };
+        haversine_distance(first, reference) > LOCALITY_RADIUS_M
+            && haversine_distance(swapped, reference) <= LOCALITY_RADIUS_M
+    })
+}
+
+pub fn swap_axes(coords: &mut [Coord<f64>]) {
+    for c in coords {
+        std::mem::swap(&mut c.x, &mut c.y);
+    }
+}
+
+/// A lon/lat bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Coord<f64>,
+    pub max: Coord<f64>,
+}
+
+impl BoundingBox {
+    /// The smallest box containing all of the given coordinates, if there are any.
+    pub fn around(coords: impl IntoIterator<Item = Coord<f64>>) -> Option<Self> {
+        coords.into_iter().fold(None, |bbox, c| {
+            Some(match bbox {
+                None => Self { min: c, max: c },
+                Some(Self { min, max }) => Self {
+                    min: Coord {
+                        x: min.x.min(c.x),
+                        y: min.y.min(c.y),
+                    },
+                    max: Coord {
+                        x: max.x.max(c.x),
+                        y: max.y.max(c.y),
+                    },
+                },
+            })
+        })
+    }
+
+    pub fn contains(&self, c: Coord<f64>) -> bool {
+        (self.min.x..=self.max.x).contains(&c.x) && (self.min.y..=self.max.y).contains(&c.y)
+    }
+
+    /// Whether any part of the path passes through the box, even if none of its points lie
+    /// inside it.
+    pub fn intersects_path(&self, coords: &[Coord<f64>]) -> bool {
+        match coords {
+            [] => false,
+            [only] => self.contains(*only),
+            _ => coords
+                .windows(2)
+                .any(|pair| self.intersects_segment(pair[0], pair[1])),
+        }
+    }
+
+    /// Liang-Barsky clipping, reduced to a yes/no answer.
+    fn intersects_segment(&self, a: Coord<f64>, b: Coord<f64>) -> bool {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+
+        for (p, q) in [
+            (-dx, a.x - self.min.x),
+            (dx, self.max.x - a.x),
+            (-dy, a.y - self.min.y),
+            (dy, self.max.y - a.y),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return false;
+                }
+            } else {
+                let t = q / p;
+                if p < 0.0 {
+                    t0 = t0.max(t);
+                } else {
+                    t1 = t1.min(t);
+                }
+                if t0 > t1 {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl FromStr for BoundingBox {
+    type Err = String;
+
+    /// Parses "min_lon,min_lat,max_lon,max_lat".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid bounding box coordinate: {e}"))?;
+
+        match values[..] {
+            [min_x, min_y, max_x, max_y] if min_x <= max_x && min_y <= max_y => Ok(Self {
+                min: Coord { x: min_x, y: min_y },
+                max: Coord { x: max_x, y: max_y },
+            }),
+            [_, _, _, _] => Err("bounding box minimums must not exceed its maximums".to_string()),
+            _ => Err("expected min_lon,min_lat,max_lon,max_lat".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_haversine_distance() {
+        // One degree of latitude is roughly 111.2 km everywhere.
+        let d = haversine_distance(Coord { x: -2.6, y: 51.0 }, Coord { x: -2.6, y: 52.0 });
+        assert!((d - 111_195.0).abs() < 10.0, "got {d}");
+    }
+
+    #[test]
+    fn test_bearing() {
+        let origin = Coord { x: -2.6, y: 51.45 };
+        let north = bearing(origin, Coord { x: -2.6, y: 51.46 });
+        let west = bearing(origin, Coord { x: -2.61, y: 51.45 });
+        assert!(north.abs() < 0.01, "got {north}");
+        assert!((west - 270.0).abs() < 0.01, "got {west}");
+    }
+
+    #[test]
+    fn test_axes_look_swapped() {
+        let bristol = Coord { x: -2.6, y: 51.45 };
+        let swapped_bristol = Coord { x: 51.45, y: -2.6 };
+        let swapped_sydney = Coord {
+            x: -33.86,
+            y: 151.2,
+        };
+
+        assert!(axes_look_swapped([swapped_sydney], None));
+        assert!(!axes_look_swapped([swapped_bristol], None));
+        assert!(axes_look_swapped([swapped_bristol], Some(bristol)));
+        assert!(!axes_look_swapped([bristol], Some(bristol)));
+    }
+
+    #[test]
+    fn test_bounding_box_intersects_path() {
+        let bbox: BoundingBox = "-1,50,1,52".parse().unwrap();
+
+        let crossing = [Coord { x: -2.0, y: 51.0 }, Coord { x: 2.0, y: 51.0 }];
+        let outside = [Coord { x: -2.0, y: 53.0 }, Coord { x: 2.0, y: 53.0 }];
+        assert!(bbox.intersects_path(&crossing));
+        assert!(!bbox.intersects_path(&outside));
+        assert!("1,50,-1,52".parse::<BoundingBox>().is_err());
+    }
+}
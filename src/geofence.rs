@@ -0,0 +1,294 @@
+//! Keeping only the parts of a route inside a polygon, such as a park boundary.
+
+use crate::geo::Located;
+use crate::Error;
+use geo_types::Coord;
+use serde_json::Value;
+use std::fs;
+
+/// A ring of a polygon, as a closed or unclosed list of vertices.
+type Ring = Vec<Coord<f64>>;
+
+/// An area made up of one or more polygons, each an exterior ring followed by any holes.
+/// Coordinates are treated as planar, which is fine at the scale of a park.
+#[derive(Debug, Clone)]
+pub struct Geofence {
+    polygons: Vec<Vec<Ring>>,
+}
+
+impl Geofence {
+    pub fn new(polygons: Vec<Vec<Ring>>) -> Self {
+        Self { polygons }
+    }
+
+    /// Loads the polygons from a GeoJSON file, which may hold a Polygon or MultiPolygon, or a
+    /// Feature or FeatureCollection of them.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let error = |reason: String| Error::GeofenceError {
+            path: path.to_string(),
+            reason,
+        };
+        let contents = fs::read_to_string(path).map_err(|source| Error::FileError {
+            path: path.to_string(),
+            source,
+        })?;
+        let json: Value = serde_json::from_str(&contents).map_err(|e| error(e.to_string()))?;
+        Self::from_geojson(&json).map_err(|reason| error(reason.to_string()))
+    }
+
+    pub fn from_geojson(json: &Value) -> Result<Self, &'static str> {
+        let mut polygons = Vec::new();
+        collect_polygons(json, &mut polygons)?;
+        if polygons.is_empty() {
+            return Err("no polygons found");
+        }
+        Ok(Self { polygons })
+    }
+
+    pub fn contains(&self, point: Coord<f64>) -> bool {
+        self.polygons.iter().any(|rings| {
+            // Even-odd over all rings, so that holes are excluded.
+            rings.iter().filter(|ring| ray_crosses(ring, point)).count() % 2 == 1
+        })
+    }
+
+    /// Splits a line into the parts that lie inside the area, cutting it where it crosses a
+    /// boundary.
+    pub fn clip<P: Located>(&self, points: &[P]) -> Vec<Vec<P>> {
+        if let [point] = points {
+            return if self.contains(point.coord()) {
+                vec![vec![point.clone()]]
+            } else {
+                Vec::new()
+            };
+        }
+
+        let mut parts = Vec::new();
+        let mut current = Vec::new();
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0].coord(), pair[1].coord());
+            let mut cuts = self.crossings(a, b);
+            cuts.push(1.0);
+
+            let mut start = 0.0;
+            for end in cuts {
+                let inside = self.contains(lerp(a, b, (start + end) / 2.0));
+                if inside {
+                    if current.is_empty() {
+                        current.push(if start == 0.0 {
+                            pair[0].clone()
+                        } else {
+                            P::from(lerp(a, b, start))
+                        });
+                    }
+                    current.push(if end == 1.0 {
+                        pair[1].clone()
+                    } else {
+                        P::from(lerp(a, b, end))
+                    });
+                } else if !current.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+                start = end;
+            }
+        }
+        if !current.is_empty() {
+            parts.push(current);
+        }
+        parts
+    }
+
+    /// Where along a→b it crosses any ring, as sorted fractions of the way from a to b.
+    fn crossings(&self, a: Coord<f64>, b: Coord<f64>) -> Vec<f64> {
+        let mut cuts: Vec<f64> = self
+            .polygons
+            .iter()
+            .flatten()
+            .flat_map(|ring| ring_edges(ring))
+            .filter_map(|(c, d)| intersection(a, b, c, d))
+            .filter(|&t| t > 0.0 && t < 1.0)
+            .collect();
+        cuts.sort_by(f64::total_cmp);
+        cuts.dedup();
+        cuts
+    }
+}
+
+fn collect_polygons(json: &Value, polygons: &mut Vec<Vec<Ring>>) -> Result<(), &'static str> {
+    let coordinates = || json.get("coordinates").ok_or("geometry has no coordinates");
+    match json.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => {
+            let features = json
+                .get("features")
+                .and_then(Value::as_array)
+                .ok_or("FeatureCollection has no features")?;
+            for feature in features {
+                collect_polygons(feature, polygons)?;
+            }
+        }
+        Some("Feature") => {
+            // Features may have other geometry, such as the route itself, so skip those.
+            if let Some(geometry) = json.get("geometry") {
+                let kind = geometry.get("type").and_then(Value::as_str);
+                if matches!(kind, Some("Polygon" | "MultiPolygon")) {
+                    collect_polygons(geometry, polygons)?;
+                }
+            }
+        }
+        Some("Polygon") => polygons.push(parse_polygon(coordinates()?)?),
+        Some("MultiPolygon") => {
+            for polygon in coordinates()?
+                .as_array()
+                .ok_or("coordinates is not an array")?
+            {
+                polygons.push(parse_polygon(polygon)?);
+            }
+        }
+        _ => return Err("expected a GeoJSON Polygon, MultiPolygon, Feature or FeatureCollection"),
+    }
+    Ok(())
+}
+
+fn parse_polygon(json: &Value) -> Result<Vec<Ring>, &'static str> {
+    json.as_array()
+        .ok_or("polygon is not an array of rings")?
+        .iter()
+        .map(|ring| {
+            let positions = ring.as_array().ok_or("ring is not an array of positions")?;
+            let ring = positions
+                .iter()
+                .map(|position| {
+                    let position = position.as_array().filter(|p| p.len() >= 2);
+                    let number = |i: usize| position.and_then(|p| p[i].as_f64());
+                    Some(Coord {
+                        x: number(0)?,
+                        y: number(1)?,
+                    })
+                })
+                .collect::<Option<Ring>>()
+                .ok_or("position is not a [longitude, latitude] pair")?;
+            if ring.len() < 3 {
+                return Err("ring has fewer than three positions");
+            }
+            Ok(ring)
+        })
+        .collect()
+}
+
+fn ring_edges(ring: &[Coord<f64>]) -> impl Iterator<Item = (Coord<f64>, Coord<f64>)> + '_ {
+    // GeoJSON rings repeat the first vertex at the end, but tolerate ones that don't.
+    let closing = (ring.first() != ring.last()).then(|| (ring[ring.len() - 1], ring[0]));
+    ring.windows(2).map(|w| (w[0], w[1])).chain(closing)
+}
+
+/// Whether a ray heading east from the point crosses the ring an odd number of times.
+fn ray_crosses(ring: &[Coord<f64>], point: Coord<f64>) -> bool {
+    ring_edges(ring)
+        .filter(|(c, d)| {
+            (c.y > point.y) != (d.y > point.y)
+                && point.x < c.x + (point.y - c.y) / (d.y - c.y) * (d.x - c.x)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// How far along a→b it meets c→d, if it does.
+fn intersection(a: Coord<f64>, b: Coord<f64>, c: Coord<f64>, d: Coord<f64>) -> Option<f64> {
+    let cross = |p: Coord<f64>, q: Coord<f64>| p.x * q.y - p.y * q.x;
+    let (ab, cd, ac) = (b - a, d - c, c - a);
+    let denominator = cross(ab, cd);
+    if denominator == 0.0 {
+        return None;
+    }
+    let t = cross(ac, cd) / denominator;
+    let u = cross(ac, ab) / denominator;
+    (0.0..=1.0).contains(&u).then_some(t)
+}
+
+fn lerp(a: Coord<f64>, b: Coord<f64>, t: f64) -> Coord<f64> {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn square(min: f64, max: f64) -> Ring {
+        vec![
+            Coord { x: min, y: min },
+            Coord { x: max, y: min },
+            Coord { x: max, y: max },
+            Coord { x: min, y: max },
+            Coord { x: min, y: min },
+        ]
+    }
+
+    #[test]
+    fn test_contains_respects_holes() {
+        let fence = Geofence::new(vec![vec![square(0.0, 10.0), square(4.0, 6.0)]]);
+        assert!(fence.contains(Coord { x: 2.0, y: 2.0 }));
+        assert!(!fence.contains(Coord { x: 5.0, y: 5.0 }));
+        assert!(!fence.contains(Coord { x: 11.0, y: 5.0 }));
+    }
+
+    #[test]
+    fn test_clip_splits_at_crossings() {
+        let fence = Geofence::new(vec![vec![square(0.0, 10.0)]]);
+        // Starts outside, runs through the square, leaves, then comes back in.
+        let line = [
+            Coord { x: -5.0, y: 5.0 },
+            Coord { x: 15.0, y: 5.0 },
+            Coord { x: 15.0, y: 8.0 },
+            Coord { x: 5.0, y: 8.0 },
+        ];
+
+        let parts = fence.clip(&line);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(
+            parts[0],
+            vec![Coord { x: 0.0, y: 5.0 }, Coord { x: 10.0, y: 5.0 }]
+        );
+        assert_eq!(
+            parts[1],
+            vec![Coord { x: 10.0, y: 8.0 }, Coord { x: 5.0, y: 8.0 }]
+        );
+    }
+
+    #[test]
+    fn test_clip_keeps_lines_entirely_inside() {
+        let fence = Geofence::new(vec![vec![square(0.0, 10.0)]]);
+        let line = [
+            Coord { x: 1.0, y: 1.0 },
+            Coord { x: 2.0, y: 2.0 },
+            Coord { x: 3.0, y: 1.0 },
+        ];
+        assert_eq!(fence.clip(&line), vec![line.to_vec()]);
+    }
+
+    #[test]
+    fn test_from_geojson_feature_collection() {
+        let json = json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [20.0, 20.0] }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "MultiPolygon",
+                        "coordinates": [[[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]]]
+                    }
+                }
+            ]
+        });
+        let fence = Geofence::from_geojson(&json).unwrap();
+        assert!(fence.contains(Coord { x: 0.5, y: 0.5 }));
+        assert!(!fence.contains(Coord { x: 20.0, y: 20.0 }));
+
+        assert!(Geofence::from_geojson(&json!({ "type": "LineString" })).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! GeoJSON output, for web maps and GIS tools.
+
+use gpx::{Track, Waypoint};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+/// Writes a FeatureCollection with a MultiLineString feature per track, holding a line per
+/// track segment, and a Point feature per waypoint. Positions include elevation when known.
+pub fn write_geojson(
+    tracks: &[Track],
+    waypoints: &[Waypoint],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let track_features = tracks.iter().map(|track| {
+        let lines: Vec<Vec<Value>> = track
+            .segments
+            .iter()
+            .map(|segment| segment.points.iter().map(position).collect())
+            .collect();
+        json!({
+            "type": "Feature",
+            "properties": {
+                "name": track.name,
+                "description": track.description,
+            },
+            "geometry": { "type": "MultiLineString", "coordinates": lines },
+        })
+    });
+    let waypoint_features = waypoints.iter().map(|waypoint| {
+        json!({
+            "type": "Feature",
+            "properties": {
+                "name": waypoint.name,
+                "description": waypoint.description,
+                "symbol": waypoint.symbol,
+            },
+            "geometry": { "type": "Point", "coordinates": position(waypoint) },
+        })
+    });
+
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": track_features.chain(waypoint_features).collect::<Vec<_>>(),
+    });
+    serde_json::to_writer(&mut writer, &collection)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+fn position(waypoint: &Waypoint) -> Value {
+    let point = waypoint.point();
+    match waypoint.elevation {
+        Some(elevation) => json!([point.x(), point.y(), elevation]),
+        None => json!([point.x(), point.y()]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::TrackSegment;
+
+    #[test]
+    fn test_track_becomes_multilinestring() {
+        let mut high = Waypoint::new(Point::new(-2.61, 51.46));
+        high.elevation = Some(120.0);
+        let track = Track {
+            name: Some("Loop".to_string()),
+            segments: vec![TrackSegment {
+                points: vec![Waypoint::new(Point::new(-2.6, 51.45)), high],
+            }],
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        write_geojson(&[track], &[], &mut output).unwrap();
+        let geojson: Value = serde_json::from_slice(&output).unwrap();
+
+        let feature = &geojson["features"][0];
+        assert_eq!(feature["properties"]["name"], "Loop");
+        assert_eq!(feature["geometry"]["type"], "MultiLineString");
+        assert_eq!(
+            feature["geometry"]["coordinates"],
+            json!([[[-2.6, 51.45], [-2.61, 51.46, 120.0]]])
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Responses from AllTrails' GraphQL API, which wrap the trail under `data` and give lists as
+//! connections of edges and nodes rather than plain arrays.
+
+use crate::Error;
+use serde_json::{Map, Value};
+
+/// Where GraphQL responses put what the REST responses have at the root, by the REST key.
+const ROOT_FIELDS: &[(&str, &str)] = &[
+    ("trails", "trail"),
+    ("trails", "trails"),
+    ("maps", "map"),
+    ("maps", "maps"),
+];
+
+/// Rewrites a GraphQL response into the layout of the REST responses, so that the trail is
+/// found at `/trails/0` like everywhere else. Anything else is returned unchanged.
+pub(crate) fn unwrap(json: Value) -> Result<Value, Error> {
+    let Value::Object(mut root) = json else {
+        return Ok(json);
+    };
+    if !root.contains_key("data") || ["trails", "maps"].iter().any(|k| root.contains_key(*k)) {
+        return Ok(Value::Object(root));
+    }
+
+    let mut data = match root.remove("data") {
+        Some(Value::Object(data)) => data,
+        _ => return Err(query_failure(&root)),
+    };
+    for (rest_key, graphql_key) in ROOT_FIELDS {
+        let trails = match data.remove(*graphql_key) {
+            Some(Value::Null) | None => continue,
+            Some(trails) => flatten_connections(trails),
+        };
+        let trails = match trails {
+            Value::Array(trails) => trails,
+            trail => vec![trail],
+        };
+        return Ok(Value::Object(Map::from_iter([(
+            rest_key.to_string(),
+            Value::Array(trails),
+        )])));
+    }
+    Err(query_failure(&root))
+}
+
+/// The error for a response without a trail, which is usually down to the query failing.
+fn query_failure(root: &Map<String, Value>) -> Error {
+    let messages: Vec<&str> = root
+        .get("errors")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|error| error.get("message")?.as_str())
+        .collect();
+    if messages.is_empty() {
+        Error::NoRouteLines
+    } else {
+        Error::GraphqlError(messages.join("; "))
+    }
+}
+
+/// Replaces every `{"edges": [{"node": ...}]}` or `{"nodes": [...]}` connection with a plain
+/// array of its nodes.
+fn flatten_connections(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(flatten_connections).collect()),
+        Value::Object(mut object) => {
+            let is_connection = object
+                .get("edges")
+                .and_then(Value::as_array)
+                .is_some_and(|edges| edges.iter().all(|edge| edge.get("node").is_some()));
+            if is_connection {
+                let edges = object.remove("edges").unwrap_or_default();
+                let nodes = edges
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|edge| edge.get("node").cloned());
+                return Value::Array(nodes.map(flatten_connections).collect());
+            }
+            if let Some(Value::Array(_)) = object.get("nodes") {
+                return flatten_connections(object.remove("nodes").unwrap_or_default());
+            }
+            Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, flatten_connections(value)))
+                    .collect(),
+            )
+        }
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_unwrap() {
+        let response = json!({
+            "data": {
+                "trail": {
+                    "name": "Connected",
+                    "defaultMap": {
+                        "routes": {
+                            "edges": [{
+                                "node": {
+                                    "lineSegments": {
+                                        "nodes": [{ "polyline": { "pointsData": "abc" } }]
+                                    }
+                                }
+                            }]
+                        }
+                    }
+                }
+            }
+        });
+        let unwrapped = unwrap(response).unwrap();
+        assert_eq!(
+            unwrapped.pointer("/trails/0/defaultMap/routes/0/lineSegments/0/polyline/pointsData"),
+            Some(&json!("abc"))
+        );
+
+        let rest = json!({ "trails": [{ "name": "REST" }] });
+        assert_eq!(unwrap(rest.clone()).unwrap(), rest);
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let response = json!({
+            "data": null,
+            "errors": [{ "message": "Trail not found" }, { "message": "Not authorised" }]
+        });
+        let err = unwrap(response).unwrap_err();
+        assert!(
+            matches!(err, Error::GraphqlError(ref m) if m == "Trail not found; Not authorised")
+        );
+    }
+}
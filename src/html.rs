@@ -0,0 +1,102 @@
+//! A standalone HTML preview of a route, drawn with Leaflet on OpenStreetMap tiles.
+
+use crate::builder::escape;
+use gpx::{Track, Waypoint};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+const LEAFLET_VERSION: &str = "1.9.4";
+
+/// Writes a web page showing each track segment as a line, with markers at the start and end
+/// of the route and at each waypoint.
+pub fn write_html(
+    title: &str,
+    tracks: &[Track],
+    waypoints: &[Waypoint],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let lines: Vec<Vec<[f64; 2]>> = tracks
+        .iter()
+        .flat_map(|track| &track.segments)
+        .map(|segment| segment.points.iter().map(lat_lon).collect())
+        .filter(|line: &Vec<_>| !line.is_empty())
+        .collect();
+
+    // Leaflet shows tooltips as HTML, and waypoint names come from the input, so they're
+    // escaped to be shown as text.
+    let mut markers: Vec<Value> = waypoints
+        .iter()
+        .map(|wp| json!({ "at": lat_lon(wp), "label": escape(wp.name.as_deref().unwrap_or("")) }))
+        .collect();
+    if let (Some(start), Some(end)) = (
+        lines.first().and_then(|line| line.first()),
+        lines.last().and_then(|line| line.last()),
+    ) {
+        markers.push(json!({ "at": start, "label": "Start" }));
+        markers.push(json!({ "at": end, "label": "End" }));
+    }
+
+    let data = json!({ "lines": lines, "markers": markers });
+    write!(
+        writer,
+        include_str!("preview.html"),
+        title = escape(title),
+        leaflet = LEAFLET_VERSION,
+        data = script_safe(&data.to_string()),
+    )?;
+    writer.flush()
+}
+
+fn lat_lon(waypoint: &Waypoint) -> [f64; 2] {
+    let point = waypoint.point();
+    [point.y(), point.x()]
+}
+
+/// Stops text embedded in a <script> element from closing it early.
+fn script_safe(json: &str) -> String {
+    json.replace('<', "\\u003c")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::TrackSegment;
+
+    #[test]
+    fn test_preview_contains_route_and_markers() {
+        let track = Track {
+            segments: vec![TrackSegment {
+                points: vec![
+                    Waypoint::new(Point::new(-2.6, 51.45)),
+                    Waypoint::new(Point::new(-2.61, 51.46)),
+                ],
+            }],
+            ..Default::default()
+        };
+        let mut waypoint = Waypoint::new(Point::new(-2.605, 51.455));
+        waypoint.name = Some("</script>".to_string());
+
+        let mut output = Vec::new();
+        write_html("Loop & back", &[track], &[waypoint], &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+
+        assert!(html.contains("<title>Loop &amp; back</title>"));
+        assert!(html.contains("[[[51.45,-2.6],[51.46,-2.61]]]"));
+        assert!(html.contains(r#""label":"Start""#));
+        assert!(!html.contains("</script>\""));
+    }
+
+    #[test]
+    fn test_labels_are_text() {
+        let mut waypoint = Waypoint::new(Point::new(-2.605, 51.455));
+        waypoint.name = Some("<img src=x onerror=alert(1)> & co".to_string());
+
+        let mut output = Vec::new();
+        write_html("Walk", &[], &[waypoint], &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+
+        assert!(html.contains(r#""label":"&lt;img src=x onerror=alert(1)&gt; &amp; co""#));
+        assert!(!html.contains("<img"));
+    }
+}
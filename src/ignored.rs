@@ -0,0 +1,102 @@
+//! Warnings about what the input has but the output leaves out, so that a route that's missing
+//! something is noticed before it's needed.
+
+use crate::warning::{Ignored, Warning};
+use crate::{bundled, find_in_json, poi};
+use serde_json::Value;
+
+/// The trails other than the first in a response with several, such as search results.
+pub(crate) fn other_trails(json: &Value) -> Option<Warning> {
+    let trails = find_in_json(json, &["/trails", "/maps"]).and_then(Value::as_array)?;
+    let names = trails.iter().skip(1).map(|trail| text(trail, "name"));
+    ignored(Ignored::OtherTrails, names)
+}
+
+/// The routes other than the first on the trail's map.
+pub(crate) fn other_routes(json: &Value) -> Option<Warning> {
+    let routes = find_in_json(json, &["/trails/0/defaultMap/routes", "/maps/0/routes"])
+        .and_then(Value::as_array)?;
+    let names = routes.iter().skip(1).map(|route| {
+        text(route, "name").or_else(|| {
+            let id = route.get("id")?;
+            Some(format!(
+                "route {}",
+                id.as_str().map_or(id.to_string(), str::to_string)
+            ))
+        })
+    });
+    ignored(Ignored::OtherRoutes, names)
+}
+
+/// The trails an offline map pack bundles with the first trail.
+pub(crate) fn bundled_trails(json: &Value) -> Option<Warning> {
+    let names = bundled::peek(json)
+        .into_iter()
+        .map(|trail| text(trail, "name"));
+    ignored(Ignored::BundledTrails, names)
+}
+
+/// The waypoints without a usable location.
+pub(crate) fn unlocated_waypoints(json: &Value) -> Option<Warning> {
+    let names = poi::unlocated_waypoints(json).into_iter();
+    ignored(Ignored::UnlocatedWaypoints, names)
+}
+
+fn text(json: &Value, key: &str) -> Option<String> {
+    json.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+/// A warning that `names` were ignored, or `None` if there are none. Each is `None` if the
+/// thing ignored has no name.
+fn ignored(what: Ignored, names: impl ExactSizeIterator<Item = Option<String>>) -> Option<Warning> {
+    let count = names.len();
+    (count > 0).then(|| Warning::Ignored {
+        what,
+        count,
+        names: names.flatten().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_warnings() {
+        let json = json!({
+            "trails": [
+                {
+                    "name": "Main",
+                    "defaultMap": {
+                        "routes": [{}, { "name": "Shortcut" }, { "id": 7 }, {}, {}],
+                        "waypoints": [{ "name": "Nowhere" }]
+                    },
+                    "bundledTrails": [{ "name": "Bundled" }]
+                },
+                {}
+            ]
+        });
+        let ignored = |what, count, names: &[&str]| {
+            Some(Warning::Ignored {
+                what,
+                count,
+                names: names.iter().map(|name| name.to_string()).collect(),
+            })
+        };
+        assert_eq!(other_trails(&json), ignored(Ignored::OtherTrails, 1, &[]));
+        assert_eq!(
+            other_routes(&json),
+            ignored(Ignored::OtherRoutes, 4, &["Shortcut", "route 7"])
+        );
+        assert_eq!(
+            bundled_trails(&json),
+            ignored(Ignored::BundledTrails, 1, &["Bundled"])
+        );
+        assert_eq!(
+            unlocated_waypoints(&json),
+            ignored(Ignored::UnlocatedWaypoints, 1, &["Nowhere"])
+        );
+        assert_eq!(other_trails(&json!({ "trails": [{}] })), None);
+    }
+}
@@ -0,0 +1,704 @@
+//! Reading routes from inputs other than AllTrails JSON, and telling the kinds apart.
+
+use crate::decode::decode_polyline;
+use crate::filter::Filter;
+use crate::format::InputFormat;
+use crate::garmin::{read_track_point_readings, SensorReadings};
+use crate::geo::Located;
+use crate::lookup::JsonPointer;
+use crate::stats::TrailStats;
+use crate::xml::{self, Token};
+use crate::{geo, json, Error, Warning, POLYLINE_PRECISION};
+use geo_types::Coord;
+use gpx::Waypoint;
+use serde_json::Value;
+
+/// A route as read from any kind of input, before the conversion options are applied.
+pub(crate) struct Route {
+    pub name: String,
+    pub lines: Vec<Vec<RoutePoint>>,
+    /// Points of interest, such as parking and viewpoints.
+    pub waypoints: Vec<Waypoint>,
+    pub swapped_latlon: bool,
+    pub description: Option<String>,
+    pub trail_stats: Option<TrailStats>,
+    pub link: Option<String>,
+    pub trail_id: Option<u64>,
+    /// Where in the input document the geometry was read from, for JSON inputs.
+    pub sources: Vec<JsonPointer>,
+    /// Problems with the input that didn't stop it being read.
+    pub warnings: Vec<Warning>,
+}
+
+impl Route {
+    pub(crate) fn new(name: String, lines: Vec<Line>, swap_latlon: bool) -> Self {
+        let lines = lines
+            .into_iter()
+            .map(|line| {
+                line.into_iter()
+                    .map(|(mut coord, elevation)| {
+                        if swap_latlon {
+                            geo::swap_axes(std::slice::from_mut(&mut coord));
+                        }
+                        RoutePoint {
+                            elevation,
+                            ..RoutePoint::from(coord)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            name,
+            lines,
+            waypoints: Vec::new(),
+            swapped_latlon: swap_latlon,
+            description: None,
+            trail_stats: None,
+            link: None,
+            trail_id: None,
+            sources: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// A point of a route along with what the input recorded there, which stays with it however
+/// the route is changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct RoutePoint {
+    pub coord: Coord<f64>,
+    pub elevation: Option<f64>,
+    pub readings: SensorReadings,
+}
+
+impl From<Coord<f64>> for RoutePoint {
+    fn from(coord: Coord<f64>) -> Self {
+        Self {
+            coord,
+            elevation: None,
+            readings: SensorReadings::default(),
+        }
+    }
+}
+
+impl Located for RoutePoint {
+    fn coord(&self) -> Coord<f64> {
+        self.coord
+    }
+}
+
+/// What an input turned out to be.
+pub(crate) enum Input {
+    Gpx,
+    Kml,
+    Csv,
+    GeoJson(Value),
+    AllTrails(Value),
+}
+
+/// Works out what the input is, unless told.
+pub(crate) fn identify(
+    input: &[u8],
+    format: Option<InputFormat>,
+    filter: Option<&Filter>,
+) -> Result<Input, Error> {
+    Ok(match format {
+        None => return detect(input, filter),
+        Some(InputFormat::Gpx) => Input::Gpx,
+        Some(InputFormat::Kml) => Input::Kml,
+        Some(InputFormat::Csv) => Input::Csv,
+        Some(InputFormat::GeoJson) => Input::GeoJson(parse_json(input, filter)?),
+        Some(InputFormat::AllTrails) => Input::AllTrails(parse_json(input, filter)?),
+    })
+}
+
+/// Parses JSON input, reshaping it with `filter` if there is one.
+pub(crate) fn parse_json(input: &[u8], filter: Option<&Filter>) -> Result<Value, Error> {
+    let json = json::parse(input)?;
+    match filter {
+        Some(filter) => filter.apply(json),
+        None => Ok(json),
+    }
+}
+
+/// Tells GPX, KML, GeoJSON and AllTrails JSON apart by looking at the content rather than
+/// trusting file names, since the input is often stdin. JSON is reshaped with `filter` before
+/// it's looked at.
+pub(crate) fn detect(input: &[u8], filter: Option<&Filter>) -> Result<Input, Error> {
+    let start = input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input);
+    if start.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<') {
+        // Both are XML, so go by the root element.
+        let root = xml::tokens(&String::from_utf8_lossy(start)).find_map(|token| match token {
+            Token::Start(name) => Some(name == "kml"),
+            _ => None,
+        });
+        return Ok(if root == Some(true) {
+            Input::Kml
+        } else {
+            Input::Gpx
+        });
+    }
+
+    let json = parse_json(input, filter)?;
+    let geojson_type = json.get("type").and_then(Value::as_str);
+    Ok(match geojson_type {
+        Some(
+            "FeatureCollection" | "Feature" | "GeometryCollection" | "LineString"
+            | "MultiLineString",
+        ) => Input::GeoJson(json),
+        _ => Input::AllTrails(json),
+    })
+}
+
+/// Reads the tracks and routes of a GPX file as one route, with a line per track segment or
+/// GPX route.
+pub(crate) fn read_gpx(input: &[u8], swap_latlon: bool) -> Result<Route, Error> {
+    let gpx = gpx::read(input)?;
+
+    let tracks = gpx.tracks.iter();
+    let name = gpx
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.name.clone())
+        .or_else(|| tracks.clone().find_map(|track| track.name.clone()))
+        .or_else(|| gpx.routes.iter().find_map(|route| route.name.clone()))
+        .unwrap_or_else(|| "Route".to_string());
+    let description = gpx
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.description.clone())
+        .or_else(|| tracks.clone().find_map(|track| track.description.clone()));
+
+    let lines: Vec<_> = tracks
+        .flat_map(|track| &track.segments)
+        .map(|segment| &segment.points)
+        .chain(gpx.routes.iter().map(|route| &route.points))
+        .filter(|points| !points.is_empty())
+        .map(|points| {
+            points
+                .iter()
+                .map(|point| (point.point().0, point.elevation))
+                .collect()
+        })
+        .collect();
+    if lines.is_empty() {
+        return Err(Error::NoRouteLines);
+    }
+
+    let mut route = Route::new(name, lines, swap_latlon);
+    route.description = description;
+    route.waypoints = gpx.waypoints.clone();
+
+    // The gpx crate skips extensions, so sensor readings are picked out separately. Track
+    // points come first in `lines`, in the same order as in the document.
+    let readings = read_track_point_readings(&String::from_utf8_lossy(input));
+    let points = route.lines.iter_mut().flatten();
+    for (point, readings) in points.zip(readings) {
+        point.readings = readings;
+    }
+    Ok(route)
+}
+
+/// Reads the LineStrings and gx:Tracks of a KML document, such as a Google My Maps export, as
+/// one route named after the document or its first placemark.
+pub(crate) fn read_kml(input: &[u8], swap_latlon: bool) -> Result<Route, Error> {
+    let input = String::from_utf8_lossy(input);
+    let mut stack: Vec<&str> = Vec::new();
+    let mut text = String::new();
+    let mut lines = Vec::new();
+    let mut track: Option<Line> = None;
+    let (mut document_name, mut placemark_name, mut description) = (None, None, None);
+
+    for token in xml::tokens(&input) {
+        match token {
+            Token::Start(name) => {
+                if name == "Track" {
+                    track = Some(Vec::new());
+                }
+                stack.push(name);
+                text.clear();
+            }
+            Token::Text(content) => text.push_str(&content),
+            Token::End(name) => {
+                if stack.last() != Some(&name) {
+                    return Err(Error::InvalidKml(format!("unexpected </{name}>")));
+                }
+                stack.pop();
+                let parent = stack.last().copied();
+                let value = text.trim();
+                match (name, parent) {
+                    ("coordinates", Some("LineString")) => {
+                        let line = value
+                            .split_whitespace()
+                            .map(|tuple| parse_position(tuple, ','))
+                            .collect::<Result<Line, _>>()?;
+                        if !line.is_empty() {
+                            lines.push(line);
+                        }
+                    }
+                    ("coord", Some("Track")) => {
+                        if let Some(track) = &mut track {
+                            track.push(parse_position(value, ' ')?);
+                        }
+                    }
+                    ("Track", _) => lines.extend(track.take().filter(|t| !t.is_empty())),
+                    ("name", Some("Document" | "Folder")) if document_name.is_none() => {
+                        document_name = Some(value.to_string());
+                    }
+                    ("name", Some("Placemark")) if placemark_name.is_none() => {
+                        placemark_name = Some(value.to_string());
+                    }
+                    ("description", Some("Document" | "Placemark")) if description.is_none() => {
+                        description = Some(value.to_string());
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+        }
+    }
+
+    if lines.is_empty() {
+        return Err(Error::NoRouteLines);
+    }
+    let name = document_name
+        .or(placemark_name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Route".to_string());
+    let mut route = Route::new(name, lines, swap_latlon);
+    route.description = description.filter(|description| !description.is_empty());
+    Ok(route)
+}
+
+/// Parses a "longitude,latitude[,altitude]" tuple, or the same separated by spaces as in
+/// gx:coord.
+fn parse_position(tuple: &str, separator: char) -> Result<(Coord<f64>, Option<f64>), Error> {
+    let invalid = || Error::InvalidCoordinate(tuple.to_string());
+    let mut numbers = tuple
+        .split(separator)
+        .filter(|n| !n.is_empty())
+        .map(|n| n.trim().parse::<f64>().map_err(|_| invalid()));
+    let longitude = numbers.next().ok_or_else(invalid)??;
+    let latitude = numbers.next().ok_or_else(invalid)??;
+    let altitude = numbers.next().transpose()?;
+    if latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+        return Err(invalid());
+    }
+    Ok((
+        Coord {
+            x: longitude,
+            y: latitude,
+        },
+        altitude,
+    ))
+}
+
+/// Reads a CSV file of coordinates. With a header row, the latitude, longitude and elevation
+/// columns are found by name, and a "segment" column starts a new line whenever it changes, so
+/// files written by `--format csv` read back as they were. Without one, the columns are
+/// latitude, longitude and optionally elevation, in that order.
+pub(crate) fn read_csv(input: &[u8], swap_latlon: bool) -> Result<Route, Error> {
+    let input = String::from_utf8_lossy(input);
+    let mut rows = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, split_csv_row(line)))
+        .filter(|(_, fields)| fields.iter().any(|field| !field.is_empty()))
+        .peekable();
+
+    let mut columns = CsvColumns {
+        latitude: 0,
+        longitude: 1,
+        elevation: Some(2),
+        segment: None,
+        name: None,
+    };
+    if let Some((_, first)) = rows.peek() {
+        if first
+            .first()
+            .is_some_and(|field| field.parse::<f64>().is_err())
+        {
+            columns = CsvColumns::from_header(first)?;
+            rows.next();
+        }
+    }
+
+    let mut name = None;
+    let mut lines: Vec<Line> = Vec::new();
+    let mut current_segment = None;
+    for (line_number, fields) in rows {
+        let field = |index: Option<usize>| index.and_then(|i| fields.get(i)).map(String::as_str);
+        let number = |index: usize| -> Result<Option<f64>, Error> {
+            match field(Some(index)).filter(|value| !value.is_empty()) {
+                None => Ok(None),
+                Some(value) => value.parse().map(Some).map_err(|_| Error::InvalidCsv {
+                    line: line_number,
+                    reason: format!("{value:?} is not a number"),
+                }),
+            }
+        };
+        let (Some(latitude), Some(longitude)) =
+            (number(columns.latitude)?, number(columns.longitude)?)
+        else {
+            return Err(Error::InvalidCsv {
+                line: line_number,
+                reason: "missing latitude or longitude".to_string(),
+            });
+        };
+        if latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+            return Err(Error::InvalidCoordinate(format!("{latitude},{longitude}")));
+        }
+        let elevation = columns.elevation.map(number).transpose()?.flatten();
+
+        if name.is_none() {
+            name = field(columns.name)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string);
+        }
+        let segment = field(columns.segment);
+        if lines.is_empty() || segment != current_segment.as_deref() {
+            lines.push(Vec::new());
+            current_segment = segment.map(str::to_string);
+        }
+        let coord = Coord {
+            x: longitude,
+            y: latitude,
+        };
+        lines
+            .last_mut()
+            .expect("a line was just pushed")
+            .push((coord, elevation));
+    }
+
+    if lines.is_empty() {
+        return Err(Error::NoRouteLines);
+    }
+    Ok(Route::new(
+        name.unwrap_or_else(|| "Route".to_string()),
+        lines,
+        swap_latlon,
+    ))
+}
+
+/// Which column holds what, counting from 0.
+struct CsvColumns {
+    latitude: usize,
+    longitude: usize,
+    elevation: Option<usize>,
+    segment: Option<usize>,
+    name: Option<usize>,
+}
+
+impl CsvColumns {
+    fn from_header(header: &[String]) -> Result<Self, Error> {
+        let find = |names: &[&str]| {
+            header
+                .iter()
+                .position(|column| names.contains(&column.trim().to_ascii_lowercase().as_str()))
+        };
+        let missing = |column: &str| Error::InvalidCsv {
+            line: 1,
+            reason: format!("no {column} column in the header"),
+        };
+        Ok(Self {
+            latitude: find(&["lat", "latitude", "y"]).ok_or_else(|| missing("latitude"))?,
+            longitude: find(&["lon", "lng", "long", "longitude", "x"])
+                .ok_or_else(|| missing("longitude"))?,
+            elevation: find(&["ele", "elevation", "alt", "altitude"]),
+            segment: find(&["segment"]),
+            name: find(&["name", "track"]),
+        })
+    }
+}
+
+/// Splits a CSV row into fields, handling quoted fields with doubled quotes inside them.
+fn split_csv_row(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("there is always a field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Reads a bare encoded polyline as a route. Without a document to say where the route should
+/// be, swapped axes are only detected when latitudes are out of range.
+pub(crate) fn read_polyline(encoded: &str, swap_latlon: bool) -> Result<Route, Error> {
+    let coords = decode_polyline(encoded.trim(), POLYLINE_PRECISION)
+        .map_err(Error::InvalidPolylineArgument)?;
+    let swap_latlon = swap_latlon || geo::axes_look_swapped(coords.iter().copied(), None);
+    let route = Route::new(
+        "Route".to_string(),
+        vec![coords.into_iter().map(|coord| (coord, None)).collect()],
+        swap_latlon,
+    );
+    if let Some(RoutePoint { coord: c, .. }) = route.lines[0]
+        .iter()
+        .find(|point| point.coord.y.abs() > 90.0 || point.coord.x.abs() > 180.0)
+    {
+        return Err(Error::InvalidCoordinate(format!("{},{}", c.y, c.x)));
+    }
+    Ok(route)
+}
+
+/// Reads the LineString and MultiLineString geometry of a GeoJSON document as one route, named
+/// after the first feature with a name.
+pub(crate) fn read_geojson(json: &Value, swap_latlon: bool) -> Result<Route, Error> {
+    let mut lines = Vec::new();
+    collect_lines(json, &JsonPointer::new(""), &mut lines)?;
+    if lines.is_empty() {
+        return Err(Error::NoRouteLines);
+    }
+
+    let property = |key: &str| {
+        let features = json.get("features").and_then(Value::as_array);
+        std::iter::once(json)
+            .chain(features.into_iter().flatten())
+            .find_map(|feature| feature.get("properties")?.get(key)?.as_str())
+            .map(str::to_string)
+    };
+    let name = property("name")
+        .or_else(|| property("title"))
+        .unwrap_or_else(|| "Route".to_string());
+
+    let mut route = Route::new(name, lines, swap_latlon);
+    route.description = property("description");
+    Ok(route)
+}
+
+/// Reads route geometry that an AllTrails response embeds as GeoJSON at `pointer`, either as
+/// an object or as a string holding one.
+pub(crate) fn read_embedded_geojson(
+    json: &Value,
+    pointer: &JsonPointer,
+) -> Result<Vec<Line>, Error> {
+    let parsed;
+    let geojson = match json.as_str() {
+        Some(text) => {
+            parsed = serde_json::from_str::<Value>(text)?;
+            &parsed
+        }
+        None => json,
+    };
+    let mut lines = Vec::new();
+    collect_lines(geojson, pointer, &mut lines)?;
+    Ok(lines)
+}
+
+pub(crate) type Line = Vec<(Coord<f64>, Option<f64>)>;
+
+fn collect_lines(json: &Value, pointer: &JsonPointer, lines: &mut Vec<Line>) -> Result<(), Error> {
+    let nested = |key: &str| json.get(key).map(|value| (value, pointer.join(key)));
+    match json.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => {
+            if let Some((features, pointer)) = nested("features") {
+                for (i, feature) in features.as_array().into_iter().flatten().enumerate() {
+                    collect_lines(feature, &pointer.join(i), lines)?;
+                }
+            }
+        }
+        Some("Feature") => {
+            if let Some((geometry, pointer)) = nested("geometry") {
+                collect_lines(geometry, &pointer, lines)?;
+            }
+        }
+        Some("GeometryCollection") => {
+            if let Some((geometries, pointer)) = nested("geometries") {
+                for (i, geometry) in geometries.as_array().into_iter().flatten().enumerate() {
+                    collect_lines(geometry, &pointer.join(i), lines)?;
+                }
+            }
+        }
+        Some("LineString") => {
+            if let Some((positions, pointer)) = nested("coordinates") {
+                lines.push(read_positions(positions, &pointer)?);
+            }
+        }
+        Some("MultiLineString") => {
+            if let Some((line_strings, pointer)) = nested("coordinates") {
+                for (i, positions) in line_strings.as_array().into_iter().flatten().enumerate() {
+                    lines.push(read_positions(positions, &pointer.join(i))?);
+                }
+            }
+        }
+        // Points, polygons and so on aren't part of a route.
+        _ => {}
+    }
+    Ok(())
+}
+
+fn read_positions(json: &Value, pointer: &JsonPointer) -> Result<Line, Error> {
+    let positions = json.as_array().map(Vec::as_slice).unwrap_or_default();
+    positions
+        .iter()
+        .enumerate()
+        .map(|(index, position)| {
+            let number = |i: usize| position.get(i).and_then(Value::as_f64);
+            let (Some(longitude), Some(latitude)) = (number(0), number(1)) else {
+                return Err(Error::InvalidPosition {
+                    pointer: pointer.join(index),
+                });
+            };
+            if latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+                return Err(Error::CoordinateOutOfRange {
+                    pointer: pointer.clone(),
+                    index,
+                    latitude,
+                    longitude,
+                });
+            }
+            Ok((
+                Coord {
+                    x: longitude,
+                    y: latitude,
+                },
+                number(2),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_detect() {
+        assert!(matches!(
+            detect(b"\xEF\xBB\xBF  <?xml version=\"1.0\"?><gpx/>", None),
+            Ok(Input::Gpx)
+        ));
+        assert!(matches!(
+            detect(br#"{"type": "FeatureCollection", "features": []}"#, None),
+            Ok(Input::GeoJson(_))
+        ));
+        assert!(matches!(
+            detect(br#"{"trails": []}"#, None),
+            Ok(Input::AllTrails(_))
+        ));
+
+        let filter = ".data".parse().unwrap();
+        assert!(matches!(
+            detect(br#"{"data": {"type": "LineString"}}"#, Some(&filter)),
+            Ok(Input::GeoJson(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_kml() {
+        let kml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">
+  <Document>
+    <name>Coast &amp; Hills</name>
+    <Placemark>
+      <name>Day 1</name>
+      <LineString>
+        <coordinates>
+          -2.6,51.45,20 -2.61,51.46,25
+        </coordinates>
+      </LineString>
+    </Placemark>
+    <Placemark>
+      <gx:Track>
+        <when>2024-05-01T10:00:00Z</when>
+        <gx:coord>-2.62 51.47 30</gx:coord>
+        <gx:coord>-2.63 51.48 35</gx:coord>
+      </gx:Track>
+    </Placemark>
+    <Placemark><name>Car park</name><Point><coordinates>-2.6,51.45</coordinates></Point></Placemark>
+  </Document>
+</kml>"#;
+
+        assert!(matches!(detect(kml.as_bytes(), None), Ok(Input::Kml)));
+        let route = read_kml(kml.as_bytes(), false).unwrap();
+        assert_eq!(route.name, "Coast & Hills");
+        assert_eq!(route.lines.len(), 2);
+        assert_eq!(route.lines[1][0].coord, Coord { x: -2.62, y: 51.47 });
+        assert_eq!(route.lines[0][1].elevation, Some(25.0));
+
+        let error = read_kml(
+            b"<kml><LineString><coordinates>1,x</coordinates></LineString></kml>",
+            false,
+        );
+        assert_eq!(
+            error.err().unwrap().to_string(),
+            "Invalid coordinate \"1,x\" in the input"
+        );
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let headerless = b"51.45,-2.6,20\n51.46,-2.61\n\n";
+        let route = read_csv(headerless, false).unwrap();
+        assert_eq!(route.lines.len(), 1);
+        assert_eq!(route.lines[0][1].coord, Coord { x: -2.61, y: 51.46 });
+        assert_eq!(route.lines[0][0].elevation, Some(20.0));
+        assert_eq!(route.lines[0][1].elevation, None);
+
+        // As written by --format csv.
+        let written = b"track,segment,latitude,longitude,elevation\n\
+            \"Up, over\",1,51.45,-2.6,\n\
+            \"Up, over\",1,51.46,-2.61,120.5\n\
+            \"Up, over\",2,51.47,-2.62,\n";
+        let route = read_csv(written, false).unwrap();
+        assert_eq!(route.name, "Up, over");
+        assert_eq!(route.lines.len(), 2);
+        assert_eq!(route.lines[0][1].elevation, Some(120.5));
+
+        let error = read_csv(b"lat,lon\n51.45,west\n", false).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Failed to read CSV input at line 2: \"west\" is not a number"
+        );
+    }
+
+    #[test]
+    fn test_read_geojson() {
+        let json = json!({
+            "type": "FeatureCollection",
+            "features": [
+                { "type": "Feature", "properties": {}, "geometry": { "type": "Point", "coordinates": [0, 0] } },
+                {
+                    "type": "Feature",
+                    "properties": { "name": "Ridge walk" },
+                    "geometry": {
+                        "type": "MultiLineString",
+                        "coordinates": [[[-2.6, 51.45, 20.0], [-2.61, 51.46]], [[-2.62, 51.47]]]
+                    }
+                }
+            ]
+        });
+
+        let route = read_geojson(&json, false).unwrap();
+        assert_eq!(route.name, "Ridge walk");
+        assert_eq!(route.lines.len(), 2);
+        assert_eq!(route.lines[0][1].coord, Coord { x: -2.61, y: 51.46 });
+        assert_eq!(route.lines[0][0].elevation, Some(20.0));
+    }
+
+    #[test]
+    fn test_read_geojson_reports_bad_positions() {
+        let json = json!({
+            "type": "LineString",
+            "coordinates": [[-2.6, 51.45], ["east", 51.46]]
+        });
+        let error = read_geojson(&json, false).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Position at /coordinates/1 is not a [longitude, latitude] pair"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Parsing JSON input, with simd-json when the `simd` feature is on and the CPU can run it,
+//! since offline bundles can be tens of megabytes and parsing them dominates conversion.
+
+use serde_json::Value;
+
+/// Parses a JSON document. Errors always come from serde_json, so that they have the same
+/// line and column whichever parser is used.
+pub(crate) fn parse(input: &[u8]) -> Result<Value, serde_json::Error> {
+    #[cfg(feature = "simd")]
+    if simd_available() {
+        // simd-json parses in place, so it needs a copy it can scribble over.
+        if let Ok(json) = simd_json::serde::from_slice(&mut input.to_vec()) {
+            return Ok(json);
+        }
+    }
+    serde_json::from_slice(input)
+}
+
+/// Whether this CPU has the instructions simd-json needs to be any faster than serde_json.
+#[cfg(feature = "simd")]
+fn simd_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2") || is_x86_feature_detected!("sse4.2")
+    }
+    // NEON is part of the baseline for aarch64.
+    #[cfg(target_arch = "aarch64")]
+    {
+        true
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let json = parse(br#"{"trails": [{"name": "Abbots Leigh"}]}"#).unwrap();
+        assert_eq!(json["trails"][0]["name"], "Abbots Leigh");
+
+        let error = parse(b"{\n  \"trails\": [}").unwrap_err();
+        assert_eq!(error.line(), 2);
+    }
+}
@@ -0,0 +1,248 @@
+//! A subset of JSONPath, for pointing at the route in responses where it isn't always in the
+//! same place, such as one polyline per array element. JSON pointers are accepted too.
+//!
+//! Supported are the root `$`, children by name (`.name` or `['name']`), by index (`[0]`,
+//! or `[-1]` from the end), every child (`.*` or `[*]`), and descendants at any depth (`..`).
+
+use crate::extractor::Extractor;
+use crate::lookup::JsonPointer;
+use crate::Polyline;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Selector {
+    /// An object key, or an array index when it's a number, as in JSON pointers.
+    Name(String),
+    Index(i64),
+    Wildcard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    /// Whether the selector applies at any depth below, rather than only to children.
+    descendants: bool,
+    selector: Selector,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    source: String,
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    /// Everything in `json` the path matches, with where it was found, in document order.
+    pub fn query<'json>(&self, json: &'json Value) -> Vec<(JsonPointer, &'json Value)> {
+        let mut matches = vec![(JsonPointer::new(""), json)];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for (pointer, value) in matches {
+                let mut scope = vec![(pointer.clone(), value)];
+                if step.descendants {
+                    descendants(&pointer, value, &mut scope);
+                }
+                for (pointer, value) in scope {
+                    select(&step.selector, &pointer, value, &mut next);
+                }
+            }
+            matches = next;
+        }
+        matches
+    }
+}
+
+/// Adds every value below `value`, depth first.
+fn descendants<'json>(
+    pointer: &JsonPointer,
+    value: &'json Value,
+    out: &mut Vec<(JsonPointer, &'json Value)>,
+) {
+    let mut children = Vec::new();
+    select(&Selector::Wildcard, pointer, value, &mut children);
+    for (pointer, child) in children {
+        out.push((pointer.clone(), child));
+        descendants(&pointer, child, out);
+    }
+}
+
+fn select<'json>(
+    selector: &Selector,
+    pointer: &JsonPointer,
+    value: &'json Value,
+    out: &mut Vec<(JsonPointer, &'json Value)>,
+) {
+    let escape = |key: &str| key.replace('~', "~0").replace('/', "~1");
+    match (selector, value) {
+        (Selector::Wildcard, Value::Object(fields)) => out.extend(
+            fields
+                .iter()
+                .map(|(key, child)| (pointer.join(escape(key)), child)),
+        ),
+        (Selector::Wildcard, Value::Array(items)) => out.extend(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, child)| (pointer.join(i), child)),
+        ),
+        (Selector::Name(name), Value::Object(fields)) => {
+            if let Some(child) = fields.get(name) {
+                out.push((pointer.join(escape(name)), child));
+            }
+        }
+        (Selector::Name(name), Value::Array(items)) => {
+            if let Some(child) = name.parse().ok().and_then(|i: usize| items.get(i)) {
+                out.push((pointer.join(name), child));
+            }
+        }
+        (Selector::Index(index), Value::Array(items)) => {
+            let i = if *index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)
+            } else {
+                Some(*index as usize)
+            };
+            if let Some((i, child)) = i.and_then(|i| Some((i, items.get(i)?))) {
+                out.push((pointer.join(i), child));
+            }
+        }
+        _ => {}
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    /// Parses a JSONPath starting with `$`, or a JSON pointer starting with `/`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = match s.chars().next() {
+            Some('$') => parse_path(&s[1..]),
+            Some('/') => Ok(s[1..]
+                .split('/')
+                .map(|token| Step {
+                    descendants: false,
+                    selector: Selector::Name(token.replace("~1", "/").replace("~0", "~")),
+                })
+                .collect()),
+            None => Ok(Vec::new()),
+            _ => Err("expected a JSONPath starting with $ or a JSON pointer starting with /"),
+        };
+        steps
+            .map(|steps| Self {
+                source: s.to_string(),
+                steps,
+            })
+            .map_err(|reason| format!("invalid path {s:?}: {reason}"))
+    }
+}
+
+fn parse_path(mut rest: &str) -> Result<Vec<Step>, &'static str> {
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        let descendants = rest.starts_with("..");
+        if descendants {
+            rest = &rest[2..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+        } else if !rest.starts_with('[') {
+            return Err("expected . or [");
+        }
+
+        let (selector, after) = if let Some(inside) = rest.strip_prefix('[') {
+            let (inside, after) = inside.split_once(']').ok_or("unclosed [")?;
+            let quoted = ['\'', '"']
+                .into_iter()
+                .find_map(|quote| inside.strip_prefix(quote)?.strip_suffix(quote));
+            let selector = match (quoted, inside) {
+                (Some(name), _) => Selector::Name(name.to_string()),
+                (None, "*") => Selector::Wildcard,
+                (None, index) => Selector::Index(index.parse().map_err(|_| "expected an index")?),
+            };
+            (selector, after)
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let selector = match &rest[..end] {
+                "" => return Err("expected a name after ."),
+                "*" => Selector::Wildcard,
+                name => Selector::Name(name.to_string()),
+            };
+            (selector, &rest[end..])
+        };
+        rest = after;
+        steps.push(Step {
+            descendants,
+            selector,
+        });
+    }
+    Ok(steps)
+}
+
+impl Extractor for JsonPath {
+    /// Every string the path matches, as a line segment each.
+    fn extract<'json>(&self, json: &'json Value) -> Option<Vec<Polyline<'json>>> {
+        let polylines: Vec<_> = self
+            .query(json)
+            .into_iter()
+            .filter_map(|(pointer, value)| Some(Polyline::new(value.as_str()?, pointer)))
+            .collect();
+        (!polylines.is_empty()).then_some(polylines)
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pointers(path: &str, json: &Value) -> Vec<String> {
+        let path: JsonPath = path.parse().unwrap();
+        path.query(json)
+            .into_iter()
+            .map(|(pointer, _)| pointer.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let json = json!({
+            "routes": [
+                { "lineSegments": [{ "polyline": { "pointsData": "a" } }] },
+                { "lineSegments": [{ "polyline": { "pointsData": "b" } }, { "polyline": { "pointsData": "c" } }] }
+            ]
+        });
+        assert_eq!(
+            pointers("$..pointsData", &json),
+            [
+                "/routes/0/lineSegments/0/polyline/pointsData",
+                "/routes/1/lineSegments/0/polyline/pointsData",
+                "/routes/1/lineSegments/1/polyline/pointsData",
+            ]
+        );
+        assert_eq!(
+            pointers("$.routes[*].lineSegments[0]", &json),
+            ["/routes/0/lineSegments/0", "/routes/1/lineSegments/0"]
+        );
+        assert_eq!(
+            pointers("$['routes'][-1].lineSegments[1].polyline", &json),
+            ["/routes/1/lineSegments/1/polyline"]
+        );
+        assert_eq!(
+            pointers("/routes/1/lineSegments/0", &json),
+            ["/routes/1/lineSegments/0"]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("routes".parse::<JsonPath>().is_err());
+        assert!("$.routes[".parse::<JsonPath>().is_err());
+        assert!("$.routes[x]".parse::<JsonPath>().is_err());
+        assert!("$.".parse::<JsonPath>().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! KML output, for Google Earth and apps that prefer it to GPX.
+
+use crate::builder::escape;
+use gpx::{Track, Waypoint};
+use std::io::{self, Write};
+
+/// Writes a KML document with a Placemark per track, holding a LineString per track segment,
+/// and a Placemark per waypoint.
+pub fn write_kml(
+    title: &str,
+    tracks: &[Track],
+    waypoints: &[Waypoint],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(writer, "  <Document>")?;
+    writeln!(writer, "    <name>{}</name>", escape(title))?;
+    writeln!(
+        writer,
+        r#"    <Style id="route"><LineStyle><color>ff2a3ed6</color><width>4</width></LineStyle></Style>"#
+    )?;
+
+    for track in tracks {
+        writeln!(writer, "    <Placemark>")?;
+        write_text(&mut writer, "name", track.name.as_deref())?;
+        write_text(&mut writer, "description", track.description.as_deref())?;
+        writeln!(writer, "      <styleUrl>#route</styleUrl>")?;
+        writeln!(writer, "      <MultiGeometry>")?;
+        for segment in &track.segments {
+            let coordinates: Vec<String> = segment.points.iter().map(coordinates).collect();
+            writeln!(
+                writer,
+                "        <LineString><tessellate>1</tessellate><coordinates>{}</coordinates></LineString>",
+                coordinates.join(" ")
+            )?;
+        }
+        writeln!(writer, "      </MultiGeometry>")?;
+        writeln!(writer, "    </Placemark>")?;
+    }
+
+    for waypoint in waypoints {
+        writeln!(writer, "    <Placemark>")?;
+        write_text(&mut writer, "name", waypoint.name.as_deref())?;
+        write_text(&mut writer, "description", waypoint.description.as_deref())?;
+        writeln!(
+            writer,
+            "      <Point><coordinates>{}</coordinates></Point>",
+            coordinates(waypoint)
+        )?;
+        writeln!(writer, "    </Placemark>")?;
+    }
+
+    writeln!(writer, "  </Document>")?;
+    writeln!(writer, "</kml>")?;
+    writer.flush()
+}
+
+fn write_text(writer: &mut impl Write, tag: &str, text: Option<&str>) -> io::Result<()> {
+    match text {
+        Some(text) => writeln!(writer, "      <{tag}>{}</{tag}>", escape(text)),
+        None => Ok(()),
+    }
+}
+
+/// KML puts longitude first, then latitude and optionally altitude.
+fn coordinates(waypoint: &Waypoint) -> String {
+    let point = waypoint.point();
+    match waypoint.elevation {
+        Some(elevation) => format!("{},{},{elevation}", point.x(), point.y()),
+        None => format!("{},{}", point.x(), point.y()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::TrackSegment;
+
+    #[test]
+    fn test_track_becomes_placemark() {
+        let track = Track {
+            name: Some("Loop & back".to_string()),
+            segments: vec![TrackSegment {
+                points: vec![
+                    Waypoint::new(Point::new(-2.6, 51.45)),
+                    Waypoint::new(Point::new(-2.61, 51.46)),
+                ],
+            }],
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        write_kml("Loop & back", &[track], &[], &mut output).unwrap();
+        let kml = String::from_utf8(output).unwrap();
+
+        assert!(kml.contains("<name>Loop &amp; back</name>"));
+        assert!(kml.contains("<coordinates>-2.6,51.45 -2.61,51.46</coordinates>"));
+    }
+}
@@ -1,33 +1,201 @@
 use derive_more::Deref;
-use gpx::{Gpx, GpxVersion, Track, TrackSegment, Waypoint};
+use geo_types::{Coord, LineString};
+use gpx::{Metadata, Time, Track, TrackSegment, Waypoint};
+use serde::Deserialize;
 use serde_json::Value;
-use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::io::{Read, Write};
 use thiserror::Error;
 
+pub mod archive;
+mod builder;
+mod bundled;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod color;
+mod csv;
+pub mod cues;
+pub mod decode;
+#[cfg(feature = "cli")]
+pub mod diagnostic;
+pub mod diff;
+pub mod document;
+pub mod extractor;
+pub mod filter;
+pub mod fingerprint;
+pub mod format;
+pub mod garmin;
+pub mod geo;
+pub mod geofence;
+mod geojson;
+mod graphql;
+mod html;
+mod ignored;
+mod input;
+mod json;
+pub mod jsonpath;
+mod kml;
+pub mod log;
+pub mod lookup;
+pub mod metadata;
+#[cfg(feature = "mvt")]
+pub mod mvt;
+#[cfg(feature = "osrm")]
+pub mod osrm;
+pub mod poi;
+pub mod preset;
+pub mod profile;
+pub mod provenance;
+pub mod report;
+pub mod sanity;
+pub mod scan;
+pub mod schema;
+pub mod segments;
+pub mod shape;
+pub mod similarity;
+pub mod simplify;
+pub mod sink;
+pub mod source;
+pub mod stats;
+pub mod summary;
+mod svg;
+mod synced_trails;
+pub mod text;
+pub mod timing;
+mod trail_detail;
+pub mod units;
+pub mod warning;
+mod xml;
+
+use archive::{
+    ascii_file_name, input_stem, output_file_name, route_file_stem, BatchOutput, UniqueNames,
+};
+pub use builder::{GpxBuilder, GpxStreamWriter};
+#[cfg(feature = "cli")]
+pub use cli::{Args, Command};
+use color::{display_extensions, TrackColor, DISPLAY_NAMESPACES};
+pub use document::{AllTrailsDocument, DocumentKind};
+use extractor::Extractors;
+use filter::Filter;
+use fingerprint::Duplicates;
+use format::{numbered_path, Format, InputFormat, Layout};
+use garmin::{SensorReadings, TRACK_POINT_EXTENSION_NAMESPACE, TRACK_POINT_EXTENSION_PREFIX};
+use geofence::Geofence;
+use input::{Input, Route, RoutePoint};
+use lookup::{lookup, JsonPointer, LookupFailure};
+use poi::SymbolMap;
+use provenance::{Provenance, PROVENANCE_NAMESPACE, PROVENANCE_PREFIX};
+use simplify::SimplifyOptions;
+pub use sink::OutputSink;
+pub use source::InputSource;
+use stats::{format_minutes, track_stats, TrackStats, TrailStats, DEFAULT_ELEVATION_THRESHOLD};
+use std::fmt;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use timing::Pace;
+use units::Units;
+pub use warning::Warning;
+
 const POLYLINE_PRECISION: u32 = 5;
 const GPX_CREATOR: &str = "alltrailsgpx";
+/// The name of routes whose input gives neither a name nor a trail ID.
+const UNNAMED_ROUTE: &str = "Unnamed AllTrails route";
+
+/// Routes with fewer points than this are refused by default, since a track needs at least two
+/// to go anywhere.
+pub const DEFAULT_MIN_POINTS: usize = 2;
+
+/// Inputs larger than this many bytes are refused by default, rather than read into memory.
+pub const DEFAULT_MAX_INPUT_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Below this many bytes of encoded polylines, decoding them takes less time than starting
+/// threads to share it out.
+const PARALLEL_DECODE_MIN_BYTES: usize = 256 * 1024;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("Polyline data not found in JSON")]
-    PolylineNotFound,
+    #[error("Polyline data not found in JSON ({0})")]
+    PolylineNotFound(LookupFailure),
 
-    #[error("Polyline data is not a string")]
-    PolylineNotString,
+    #[error("Polyline data at {pointer} is not a string")]
+    PolylineNotString { pointer: JsonPointer },
 
-    #[error("Route name not found in JSON")]
-    RouteNameNotFound,
+    #[error("Route name not found in JSON ({0})")]
+    RouteNameNotFound(LookupFailure),
 
-    #[error("Route name is not a string")]
-    RouteNameNotString,
+    #[error("Route name at {pointer} is not a string")]
+    RouteNameNotString { pointer: JsonPointer },
 
     #[error("Failed to decode polyline: {0}")]
     PolylineDecodeError(#[from] polyline::errors::PolylineError),
 
+    #[error("Failed to decode polyline at {pointer}: {source}")]
+    InvalidPolyline {
+        pointer: JsonPointer,
+        #[source]
+        source: decode::DecodeError,
+    },
+
+    #[error(
+        "Point {index} of the polyline at {pointer} is out of range: latitude {latitude}, \
+         longitude {longitude}"
+    )]
+    CoordinateOutOfRange {
+        pointer: JsonPointer,
+        index: usize,
+        latitude: f64,
+        longitude: f64,
+    },
+
+    #[error("Failed to read input: {0}")]
+    InputReadError(#[source] std::io::Error),
+
+    #[error("Failed to write output: {0}")]
+    OutputWriteError(#[source] std::io::Error),
+
     #[error("Failed to parse JSON input: {0}")]
     JsonParseError(#[from] serde_json::Error),
 
+    #[error("Failed to read GPX input: {0}")]
+    GpxReadError(#[from] gpx::errors::GpxError),
+
+    #[error("Position at {pointer} is not a [longitude, latitude] pair")]
+    InvalidPosition { pointer: JsonPointer },
+
+    #[error("Failed to decode --polyline: {0}")]
+    InvalidPolylineArgument(#[source] decode::DecodeError),
+
+    #[error("Failed to read KML input: {0}")]
+    InvalidKml(String),
+
+    #[error("Invalid coordinate {0:?} in the input")]
+    InvalidCoordinate(String),
+
+    #[error("Failed to read CSV input at line {line}: {reason}")]
+    InvalidCsv { line: usize, reason: String },
+
+    #[error("The input has no tracks or lines to convert")]
+    NoRouteLines,
+
+    #[error("The GraphQL query failed: {0}")]
+    GraphqlError(String),
+
+    #[error("There is no trail {index}, as the input only has {count}")]
+    NoSuchTrail { index: usize, count: usize },
+
+    #[error("The trail has no route with ID {id}")]
+    NoSuchRoute { id: u64 },
+
+    #[error("The route has no points (pass --allow-empty to write it anyway)")]
+    EmptyRoute,
+
+    #[error(
+        "The route has too few points to be useful ({points}, where at least {min} are needed)"
+    )]
+    TooFewPoints { points: usize, min: usize },
+
+    #[error("The route looks corrupted: {0} (pass --lenient to convert it anyway)")]
+    ImplausibleRoute(sanity::Problem),
+
     #[error("Failed to open file: {path}")]
     FileError {
         path: String,
@@ -35,28 +203,455 @@ pub enum Error {
         source: std::io::Error,
     },
 
-    #[error("Error writing GPX data: {0}")]
-    GpxWriteError(#[from] gpx::errors::GpxError),
+    #[error("Error writing zip archive: {0}")]
+    ArchiveError(#[from] zip::result::ZipError),
+
+    #[error("Converting multiple inputs requires --output to name a directory or .zip file")]
+    BatchOutputRequired,
+
+    #[error("{failed} of {total} inputs failed to convert")]
+    ValidationFailed { failed: usize, total: usize },
+
+    #[error("Failed to convert {path}: {source}")]
+    BatchInputError {
+        path: String,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("Failed to load polygon from {path}: {reason}")]
+    GeofenceError { path: String, reason: String },
+
+    #[error("Line {line} of {path}: {reason}")]
+    InvalidConfig {
+        path: String,
+        line: usize,
+        reason: String,
+    },
+
+    #[error("Line {line} of {path} is not of the form `kind = symbol`")]
+    InvalidSymbolMap { path: String, line: usize },
+
+    #[error("No part of the route is within the polygon")]
+    NothingWithin,
+
+    #[error("Writing more than one format requires --output to name a file")]
+    MultipleFormatsNeedOutput,
+
+    #[error("The route has to be split into several files, so --output must name a file")]
+    SplitNeedsOutput,
+
+    #[error("--metadata-out writes next to the output, so --output must name a file")]
+    MetadataNeedsOutput,
+
+    #[error("The route is too short to trim or fuzz that much from each end")]
+    TrimmedAway,
+
+    #[error("--profile can only be used when converting a single route")]
+    ProfileNeedsSingleRoute,
+
+    #[error("--cues can only be used when converting a single route")]
+    CuesNeedSingleRoute,
+
+    #[error("The input has no points of interest to write")]
+    NoWaypoints,
+
+    #[error("--split-routes can only be used when converting a single input")]
+    SplitRoutesNeedsSingleInput,
+
+    #[error(
+        "--split-routes writes a file per route, so --output must name a directory or .zip file"
+    )]
+    SplitRoutesNeedsOutput,
+
+    #[error("--include-bundled can only be used when converting a single input")]
+    BundledNeedsSingleInput,
+
+    #[error("--include-bundled writes each trail as a track of one GPX file, so only GPX")]
+    BundledNeedsGpx,
+
+    #[error("--stream can only write a single AllTrails route as GPX")]
+    StreamNeedsSingleGpx,
+
+    #[error("--stream can't apply {0}, which hides part of the route")]
+    StreamCantHide(&'static str),
+
+    #[error("Failed to decode vector tile: {0}")]
+    MvtDecodeError(&'static str),
+
+    #[error("No LineString features in the vector tile matched")]
+    NoTileLines,
+
+    #[error("Failed to match the route to paths: {0}")]
+    MapMatchError(String),
+
+    #[error("The input is larger than {limit} bytes (pass --max-input-size to allow more)")]
+    InputTooLarge { limit: u64 },
+
+    #[error("Failed to apply --filter: {0}")]
+    FilterFailed(String),
+
+    #[error("The GPX written doesn't match the GPX 1.1 schema, which is a bug: {0}")]
+    InvalidGpx(String),
 }
 
-#[derive(clap::Parser, Debug)]
-#[command(author, version, about)]
-pub struct Args {
-    /// The input JSON file containing the polyline data. Defaults to stdin.
-    #[arg(short, long)]
-    pub input: Option<String>,
+/// Knobs controlling how a route is converted. The default converts the route as-is.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub format: Format,
+    /// Use this as the route name instead of the one from the input.
+    pub name: Option<String>,
+    /// Which of the trails in an AllTrails response to convert, counting from zero.
+    pub trail_index: usize,
+    /// Which of the trail's routes to convert, by its ID, rather than the first.
+    pub route_id: Option<u64>,
+    /// What kind of file the input is, or `None` to work it out from the content.
+    pub input_format: Option<InputFormat>,
+    /// Fail with [`Error::InputTooLarge`] on inputs larger than this many bytes.
+    pub max_input_size: Option<u64>,
+    pub simplify: Option<SimplifyOptions>,
+    /// Always swap latitude and longitude, rather than only when they look swapped.
+    pub swap_latlon: bool,
+    /// Convert routes with no points instead of failing with [`Error::EmptyRoute`].
+    pub allow_empty: bool,
+    /// Fail with [`Error::TooFewPoints`] on routes with some points, but fewer than this.
+    pub min_points: usize,
+    /// Convert routes that look corrupted instead of failing with
+    /// [`Error::ImplausibleRoute`], noting the problem in [`Conversion::warnings`].
+    pub lenient: bool,
+    /// Fail on AllTrails routes with no name, rather than making one up.
+    pub strict: bool,
+    /// What to call AllTrails routes with no name, rather than after the trail's ID.
+    pub fallback_name: Option<String>,
+    /// Transliterate the names of files written by [`run_batch`] to ASCII.
+    pub ascii_file_names: bool,
+    /// Transliterate route names to ASCII.
+    pub ascii_names: bool,
+    /// Join the route's line segments into one.
+    pub merge_segments: bool,
+    /// Follow the route back to its start, turning a one-way route into an out and back.
+    pub round_trip: bool,
+    /// When merging, segments further apart than this many meters stay separate.
+    pub max_bridge: Option<f64>,
+    /// Split segments wherever consecutive points are further apart than this many meters.
+    pub split_gap: Option<f64>,
+    /// Keep only the parts of the route inside this area.
+    pub within: Option<Geofence>,
+    /// Which GPX symbols to give waypoints that don't have one.
+    pub symbols: SymbolMap,
+    /// Remove this many meters from each end of the route.
+    pub trim_ends: Option<f64>,
+    /// Move each end to a random point up to this many meters away.
+    pub fuzz_ends: Option<f64>,
+    /// Round coordinates to this many decimal places.
+    pub coord_decimals: Option<u32>,
+    /// Fill in the route type from the route's shape when the input doesn't give one.
+    pub detect_route_type: bool,
+    /// Split the output into files of at most this many points.
+    pub max_points_per_file: Option<usize>,
+    /// The `<number>` to give the first track when several are written, counting up from it.
+    pub start_number: u32,
+    /// Avoid anything in the output that varies between runs, such as timestamps.
+    pub deterministic: bool,
+    /// When converting several inputs with [`run_batch`], write a [`metadata`] file next to
+    /// each route.
+    pub metadata_sidecars: bool,
+    /// When converting several inputs with [`run_batch`], convert every one even if it's the
+    /// same trail as another.
+    pub keep_duplicates: bool,
+    /// When converting several inputs with [`run_batch`], stop at the first that fails rather
+    /// than converting the rest.
+    pub fail_fast: bool,
+    /// Also convert the trails an offline map pack bundles with the chosen one, with
+    /// [`convert_trails`].
+    pub include_bundled: bool,
+    /// Which root to read when a response has both `trails` and `maps`. Without one, `trails`
+    /// is read and the conversion warns about it.
+    pub prefer_format: Option<Layout>,
+    /// Record where the route came from in the GPX, as a [`Provenance`] extension.
+    pub provenance: bool,
+    /// Color the track in the display extensions of apps such as OsmAnd and Locus Map.
+    pub color: Option<TrackColor>,
+    /// Give each point a made-up time, as though the route were walked at a steady pace.
+    pub synthetic_times: bool,
+    /// When the made-up times start, rather than now.
+    pub start_time: Option<OffsetDateTime>,
+    /// How the pace of the made-up times varies along the route.
+    pub pace: Pace,
+    /// Name whoever is publishing the converted route as the GPX document's author.
+    pub author: Option<String>,
+    /// Link the GPX document to this URL, such as the publisher's website.
+    pub publisher_link: Option<String>,
+    /// Write the estimate of how long the route takes to walk into the GPX metadata.
+    pub write_estimate: bool,
+    /// Write only the points of interest, without the route, failing with
+    /// [`Error::NoWaypoints`] if there aren't any. Inputs needn't then have a route at all.
+    pub waypoints_only: bool,
+    /// Check GPX output against the GPX 1.1 schema before writing it, failing with
+    /// [`Error::InvalidGpx`] rather than writing anything that doesn't match.
+    pub validate_gpx: bool,
+    /// Units for the scale bar of SVG output.
+    pub units: Units,
+    /// Snap the route onto OpenStreetMap paths using this OSRM server.
+    #[cfg(feature = "osrm")]
+    pub match_osrm: Option<osrm::Endpoint>,
+    pub elevation_threshold: f64,
+    /// Extractors to find the route with in AllTrails JSON, before trying the usual places.
+    pub extractors: Extractors,
+    /// Reshape JSON input with this before reading anything from it.
+    pub filter: Option<Filter>,
+}
 
-    /// The GPX file to create. Defaults to stdout.
-    #[arg(short, long)]
-    pub output: Option<String>,
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            format: Format::default(),
+            name: None,
+            trail_index: 0,
+            route_id: None,
+            input_format: None,
+            max_input_size: Some(DEFAULT_MAX_INPUT_SIZE),
+            simplify: None,
+            swap_latlon: false,
+            allow_empty: false,
+            min_points: DEFAULT_MIN_POINTS,
+            lenient: false,
+            strict: false,
+            fallback_name: None,
+            ascii_file_names: false,
+            ascii_names: false,
+            merge_segments: false,
+            round_trip: false,
+            max_bridge: None,
+            split_gap: None,
+            within: None,
+            symbols: SymbolMap::default(),
+            trim_ends: None,
+            fuzz_ends: None,
+            coord_decimals: None,
+            detect_route_type: false,
+            max_points_per_file: None,
+            start_number: 1,
+            deterministic: false,
+            metadata_sidecars: false,
+            keep_duplicates: false,
+            fail_fast: false,
+            include_bundled: false,
+            prefer_format: None,
+            provenance: false,
+            color: None,
+            synthetic_times: false,
+            start_time: None,
+            pace: Pace::default(),
+            author: None,
+            publisher_link: None,
+            write_estimate: false,
+            waypoints_only: false,
+            validate_gpx: false,
+            units: Units::default(),
+            #[cfg(feature = "osrm")]
+            match_osrm: None,
+            elevation_threshold: DEFAULT_ELEVATION_THRESHOLD,
+            extractors: Extractors::default(),
+            filter: None,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, Deref)]
-pub struct Polyline<'a>(&'a str);
+/// What a successful conversion produced.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub name: String,
+    pub stats: TrackStats,
+    pub trail_stats: Option<TrailStats>,
+    /// Whether latitude and longitude were swapped back, either on request or because they
+    /// looked swapped.
+    pub swapped_latlon: bool,
+    /// The track as it was written.
+    pub track: Track,
+    /// The trail's page on AllTrails, if the document says where that is.
+    pub link: Option<String>,
+    /// Sensor readings from a recorded input, for each point of each of the track's segments,
+    /// or nothing if there weren't any.
+    pub sensors: Vec<Vec<SensorReadings>>,
+    /// Points of interest, such as parking and viewpoints.
+    pub waypoints: Vec<Waypoint>,
+    /// The trail's ID on AllTrails, if the document says.
+    pub trail_id: Option<u64>,
+    /// How long reading and converting the route took.
+    pub elapsed: Duration,
+    /// Problems that didn't stop the conversion, but that whoever asked for it should know
+    /// about.
+    pub warnings: Vec<Warning>,
+    /// Where in the input document the geometry was read from, for JSON inputs.
+    pub sources: Vec<JsonPointer>,
+    /// Where the route came from, if it's to be written into the GPX.
+    pub provenance: Option<Provenance>,
+}
+
+impl Conversion {
+    /// Splits the track into parts of at most `max_points` points, numbering them from
+    /// `start_number`. If there's no limit or the track is within it, the only part is the
+    /// whole conversion.
+    pub fn split(&self, max_points: Option<usize>, start_number: u32) -> Vec<Conversion> {
+        let Some(max_points) = max_points else {
+            return vec![self.clone()];
+        };
+        // Each point is split along with its sensor readings, if there are any.
+        let segments = self
+            .track
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                let readings = self.sensors.get(i).map_or(&[][..], Vec::as_slice);
+                segment
+                    .points
+                    .iter()
+                    .enumerate()
+                    .map(|(j, point)| (point.clone(), readings.get(j).copied()))
+                    .collect()
+            })
+            .collect();
+        let parts = segments::split_by_points(segments, max_points);
+        if parts.len() <= 1 {
+            return vec![self.clone()];
+        }
+
+        let name = self.track.name.as_deref().unwrap_or(&self.name);
+        let count = parts.len();
+        parts
+            .into_iter()
+            .enumerate()
+            .map(|(i, part)| {
+                let mut conversion = self.clone();
+                conversion.track.name = Some(format!("{name} ({}/{count})", i + 1));
+                conversion.track.number = Some(start_number.saturating_add(i as u32));
+                if !self.sensors.is_empty() {
+                    conversion.sensors = part
+                        .iter()
+                        .map(|points| {
+                            points
+                                .iter()
+                                .map(|(_, readings)| readings.unwrap_or_default())
+                                .collect()
+                        })
+                        .collect();
+                }
+                conversion.track.segments = part
+                    .into_iter()
+                    .map(|points| TrackSegment {
+                        points: points.into_iter().map(|(point, _)| point).collect(),
+                    })
+                    .collect();
+                conversion
+            })
+            .collect()
+    }
+}
+
+/// An encoded polyline, along with where in the document it was found.
+#[derive(Debug, Clone, Deref)]
+pub struct Polyline<'a> {
+    #[deref]
+    data: &'a str,
+    pointer: JsonPointer,
+}
+
+impl<'a> Polyline<'a> {
+    /// A polyline found at `pointer`, such as by an [`extractor::Extractor`].
+    pub fn new(data: &'a str, pointer: JsonPointer) -> Self {
+        Self { data, pointer }
+    }
+
+    pub fn pointer(&self) -> &JsonPointer {
+        &self.pointer
+    }
+
+    /// Decodes the polyline into lon/lat coordinates.
+    pub fn decode(&self) -> Result<LineString<f64>, Error> {
+        decode::decode_polyline(self.data, POLYLINE_PRECISION)
+            .map(LineString::new)
+            .map_err(|source| Error::InvalidPolyline {
+                pointer: self.pointer.clone(),
+                source,
+            })
+    }
+}
+
+/// Decodes a polyline one lon/lat coordinate at a time, so that points can be processed, or
+/// processing stopped, without decoding the whole line first. Iteration stops after the first
+/// error.
+pub fn decode_points<'a>(
+    polyline: &'a Polyline<'_>,
+    precision: u32,
+) -> impl Iterator<Item = Result<Coord<f64>, Error>> + 'a {
+    decode::Points::new(polyline.data, precision).map(|point| {
+        point.map_err(|source| Error::InvalidPolyline {
+            pointer: polyline.pointer.clone(),
+            source,
+        })
+    })
+}
+
+/// An owned [`Polyline`], for keeping it beyond the lifetime of the document or sending it to
+/// another thread.
+#[derive(Debug, Clone, PartialEq, Eq, Deref)]
+pub struct PolylineBuf {
+    #[deref]
+    data: String,
+    pointer: JsonPointer,
+}
+
+impl PolylineBuf {
+    pub fn as_polyline(&self) -> Polyline<'_> {
+        Polyline {
+            data: &self.data,
+            pointer: self.pointer.clone(),
+        }
+    }
+}
+
+impl From<Polyline<'_>> for PolylineBuf {
+    fn from(polyline: Polyline<'_>) -> Self {
+        Self {
+            data: polyline.data.to_string(),
+            pointer: polyline.pointer,
+        }
+    }
+}
+
+impl<'a> From<&'a PolylineBuf> for Polyline<'a> {
+    fn from(polyline: &'a PolylineBuf) -> Self {
+        polyline.as_polyline()
+    }
+}
 
 #[derive(Debug, Clone, Copy, Deref)]
 pub struct RouteName<'a>(&'a str);
 
+/// An owned [`RouteName`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deref)]
+pub struct RouteNameBuf(String);
+
+impl RouteNameBuf {
+    pub fn as_route_name(&self) -> RouteName<'_> {
+        RouteName(&self.0)
+    }
+}
+
+impl From<RouteName<'_>> for RouteNameBuf {
+    fn from(name: RouteName<'_>) -> Self {
+        Self(name.0.to_string())
+    }
+}
+
+impl<'a> From<&'a RouteNameBuf> for RouteName<'a> {
+    fn from(name: &'a RouteNameBuf) -> Self {
+        name.as_route_name()
+    }
+}
+
 pub fn find_in_json<'json>(json: &'json Value, paths: &[&str]) -> Option<&'json Value> {
     paths.iter().find_map(|path| json.pointer(path))
 }
@@ -64,159 +659,1517 @@ pub fn find_in_json<'json>(json: &'json Value, paths: &[&str]) -> Option<&'json
 // - detail=offline: has "trails" array at root (e.g., /trails/0/defaultMap/routes/0/...)
 // - detail=deep: has "maps" array at root (e.g., /maps/0/routes/0/...)
 pub fn extract_polyline(json: &Value) -> Result<Polyline<'_>, Error> {
-    let polyline_str = find_in_json(
+    let (path, polyline) = lookup(
         json,
         &[
             "/trails/0/defaultMap/routes/0/lineSegments/0/polyline/pointsData",
             "/maps/0/routes/0/lineSegments/0/polyline/pointsData",
         ],
     )
-    .ok_or(Error::PolylineNotFound)?
-    .as_str()
-    .ok_or(Error::PolylineNotString)?;
+    .map_err(Error::PolylineNotFound)?;
+
+    let pointer = JsonPointer::new(path);
+    match polyline.as_str() {
+        Some(data) => Ok(Polyline { data, pointer }),
+        None => Err(Error::PolylineNotString { pointer }),
+    }
+}
+
+/// Extracts the polyline of every line segment in the route, in order.
+pub fn extract_polylines(json: &Value) -> Result<Vec<Polyline<'_>>, Error> {
+    Ok(extract_line_segments(json)?.into_iter().flatten().collect())
+}
+
+/// Extracts the polylines of each line segment. Most segments have a single polyline, but some
+/// responses split a segment's geometry across several, which are given in order.
+pub fn extract_line_segments(json: &Value) -> Result<Vec<Vec<Polyline<'_>>>, Error> {
+    let (path, segments) = lookup(
+        json,
+        &[
+            "/trails/0/defaultMap/routes/0/lineSegments",
+            "/maps/0/routes/0/lineSegments",
+        ],
+    )
+    .map_err(Error::PolylineNotFound)?;
 
-    Ok(Polyline(polyline_str))
+    let segments_pointer = JsonPointer::new(path);
+    let not_found =
+        |pointer: JsonPointer| Error::PolylineNotFound(LookupFailure::new(json, &[pointer]));
+
+    let segments = segments
+        .as_array()
+        .filter(|segments| !segments.is_empty())
+        .ok_or_else(|| not_found(segments_pointer.join(0)))?;
+
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            let segment_pointer = segments_pointer.join(i);
+            let pieces = ["polylines", "polyline"].into_iter().find_map(|key| {
+                let pieces = segment.get(key)?.as_array()?;
+                Some((segment_pointer.join(key), pieces))
+            });
+            let Some((pieces_pointer, pieces)) = pieces else {
+                return polyline_in(json, segment, &segment_pointer, SEGMENT_POLYLINE_FIELDS)
+                    .map(|polyline| vec![polyline]);
+            };
+            pieces
+                .iter()
+                .enumerate()
+                .map(|(j, piece)| {
+                    polyline_in(json, piece, &pieces_pointer.join(j), PIECE_POLYLINE_FIELDS)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Joins the pieces of a line segment into one line. Pieces usually start where the last one
+/// ended, in which case the shared point is only kept once.
+fn join_pieces(pieces: impl Iterator<Item = LineString<f64>>) -> LineString<f64> {
+    let mut coords: Vec<Coord<f64>> = Vec::new();
+    for piece in pieces {
+        let skip = usize::from(
+            coords
+                .last()
+                .is_some_and(|last| piece.0.first() == Some(last)),
+        );
+        coords.extend(piece.0.into_iter().skip(skip));
+    }
+    LineString::new(coords)
+}
+
+/// Where a line segment's encoded polyline may be, relative to the segment, in the order
+/// they're tried. AllTrails uses the first, but the others turn up in some responses.
+const SEGMENT_POLYLINE_FIELDS: &[&str] = &[
+    "/polyline/pointsData",
+    "/polyline/encoded",
+    "/polyline/points",
+    "/pointsData",
+    "/geometry/polyline",
+];
+
+/// The same for each piece of a line segment that's split across several polylines.
+const PIECE_POLYLINE_FIELDS: &[&str] = &["/pointsData", "/encoded", "/points"];
+
+/// The polyline in `value`, which is at `pointer` in `document`, at the first of `fields`
+/// that it has.
+fn polyline_in<'a>(
+    document: &Value,
+    value: &'a Value,
+    pointer: &JsonPointer,
+    fields: &[&str],
+) -> Result<Polyline<'a>, Error> {
+    let candidates: Vec<JsonPointer> = fields
+        .iter()
+        .map(|field| JsonPointer::new(format!("{pointer}{field}")))
+        .collect();
+    let found = fields
+        .iter()
+        .zip(&candidates)
+        .find_map(|(field, candidate)| Some((value.pointer(field)?, candidate)));
+    match found {
+        Some((data, pointer)) => match data.as_str() {
+            Some(data) => Ok(Polyline {
+                data,
+                pointer: pointer.clone(),
+            }),
+            None => Err(Error::PolylineNotString {
+                pointer: pointer.clone(),
+            }),
+        },
+        None => Err(Error::PolylineNotFound(LookupFailure::new(
+            document,
+            &candidates,
+        ))),
+    }
 }
 
 pub fn extract_route_name(json: &Value) -> Result<RouteName<'_>, Error> {
-    let name_str = find_in_json(json, &["/trails/0/name", "/maps/0/name"])
-        .ok_or(Error::RouteNameNotFound)?
-        .as_str()
-        .ok_or(Error::RouteNameNotString)?;
+    let (path, name) =
+        lookup(json, &["/trails/0/name", "/maps/0/name"]).map_err(Error::RouteNameNotFound)?;
 
+    let name_str = name.as_str().ok_or_else(|| Error::RouteNameNotString {
+        pointer: JsonPointer::new(path),
+    })?;
     Ok(RouteName(name_str))
 }
 
-pub fn create_gpx(line_string: geo_types::LineString<f64>, name: RouteName<'_>) -> Track {
-    let waypoints: Vec<Waypoint> = line_string
+/// Extracts where AllTrails says the trail is, which is handy for sanity checking the route.
+pub fn extract_location(json: &Value) -> Option<Coord<f64>> {
+    let number = |paths: &[&str]| find_in_json(json, paths).and_then(Value::as_f64);
+    Some(Coord {
+        x: number(&["/trails/0/location/longitude", "/trails/0/_geoloc/lng"])?,
+        y: number(&["/trails/0/location/latitude", "/trails/0/_geoloc/lat"])?,
+    })
+}
+
+/// Extracts the statistics AllTrails reports for the trail, if the document has them. These are
+/// informational, so a missing or malformed block is not an error.
+pub fn extract_trail_stats(json: &Value) -> Option<TrailStats> {
+    let geo_stats = find_in_json(json, &["/trails/0/trailGeoStats"])?;
+    let mut stats = TrailStats::deserialize(geo_stats).ok()?;
+    stats.route_type = find_in_json(json, &["/trails/0/routeType/name"])
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    stats.difficulty = extract_difficulty(json);
+    Some(stats)
+}
+
+/// Extracts how hard AllTrails rates the trail. It's rated 1, 3 or 5, sometimes as a string.
+pub fn extract_difficulty(json: &Value) -> Option<String> {
+    let rating = find_in_json(json, &["/trails/0/difficultyRating"])?;
+    let rating = rating.as_u64().or_else(|| rating.as_str()?.parse().ok())?;
+    let difficulty = match rating {
+        0..=2 => "Easy",
+        3..=4 => "Moderate",
+        _ => "Hard",
+    };
+    Some(difficulty.to_string())
+}
+
+/// Extracts the trail's overview paragraph, cleaned up and shortened for use as a description.
+pub fn extract_overview(json: &Value) -> Option<String> {
+    find_in_json(json, &["/trails/0/overview", "/trails/0/description"])
+        .and_then(Value::as_str)
+        .and_then(|overview| text::sanitize_description(overview, text::MAX_DESCRIPTION_CHARS))
+}
+
+/// Extracts the address of the trail's page on AllTrails.
+pub fn extract_trail_link(json: &Value) -> Option<String> {
+    find_in_json(json, &["/trails/0/slug"])
+        .and_then(Value::as_str)
+        .map(|slug| format!("https://www.alltrails.com/{}", slug.trim_start_matches('/')))
+}
+
+/// Extracts the trail's ID on AllTrails.
+pub fn extract_trail_id(json: &Value) -> Option<u64> {
+    find_in_json(json, &["/trails/0/id", "/maps/0/trailId"]).and_then(Value::as_u64)
+}
+
+pub fn create_gpx(line_string: LineString<f64>, name: RouteName<'_>) -> Track {
+    create_track(vec![line_string], name)
+}
+
+/// Creates a track with one track segment per line string.
+pub fn create_track(line_strings: Vec<LineString<f64>>, name: RouteName<'_>) -> Track {
+    let segments = line_strings
         .into_iter()
-        .map(|coord| Waypoint::new(coord.into()))
+        .map(|line_string| TrackSegment {
+            points: line_string
+                .into_iter()
+                .map(|coord| Waypoint::new(coord.into()))
+                .collect(),
+        })
         .collect();
 
-    let segment = TrackSegment { points: waypoints };
-
     Track {
         name: Some(name.to_string()),
-        segments: vec![segment],
+        segments,
         ..Default::default()
     }
 }
 
-pub fn write_gpx(track: Track, writer: impl Write) -> Result<(), Error> {
-    let gpx = Gpx {
-        version: GpxVersion::Gpx11,
-        creator: Some(GPX_CREATOR.to_string()),
-        tracks: vec![track],
+/// Describes the trail in GPX metadata using the statistics AllTrails reports for it.
+pub fn create_metadata(name: RouteName<'_>, trail_stats: &TrailStats) -> Metadata {
+    Metadata {
+        name: Some(name.to_string()),
+        description: Some(trail_stats.to_string()),
+        keywords: trail_stats.route_type.clone(),
         ..Default::default()
+    }
+}
+
+/// Writes a GPX file containing just the given track. See [`GpxBuilder`] for more control.
+pub fn write_gpx(track: Track, writer: impl Write) -> Result<(), Error> {
+    GpxBuilder::new().track(track).write(writer)
+}
+
+/// Converts a route to GPX with the default [`Options`].
+pub fn run(reader: impl Read, writer: impl Write) -> Result<(), Error> {
+    run_with_options(reader, writer, &Options::default()).map(|_| ())
+}
+
+/// Converts a route and writes it in `options.format`.
+pub fn run_with_options(
+    reader: impl Read,
+    writer: impl Write,
+    options: &Options,
+) -> Result<Conversion, Error> {
+    let conversion = convert(reader, options)?;
+    write_conversion(&conversion, options.format, options, writer)?;
+    Ok(conversion)
+}
+
+/// Converts a route without writing it anywhere, so that it can then be written in as many
+/// formats as needed with [`write_conversion`]. Besides AllTrails JSON, the input may be GPX,
+/// KML or GeoJSON, which is told apart by its content.
+pub fn convert(reader: impl Read, options: &Options) -> Result<Conversion, Error> {
+    let started = Instant::now();
+    let input = read_input(reader, options.max_input_size)?;
+
+    let route = match input::identify(&input, options.input_format, options.filter.as_ref())? {
+        Input::Gpx => input::read_gpx(&input, options.swap_latlon)?,
+        Input::Kml => input::read_kml(&input, options.swap_latlon)?,
+        Input::Csv => input::read_csv(&input, options.swap_latlon)?,
+        Input::GeoJson(json) => input::read_geojson(&json, options.swap_latlon)?,
+        Input::AllTrails(json) => read_document(json, options)?,
     };
+    convert_route(route, options, started)
+}
 
-    Ok(gpx::write(&gpx, writer)?)
+/// Reads the route that `options` choose from an AllTrails document in any of its layouts.
+fn read_document(json: Value, options: &Options) -> Result<Route, Error> {
+    let mut json = normalize_layout(json)?;
+    let warning = prefer_layout(&mut json, options.prefer_format);
+    select_trail(&mut json, options.trail_index)?;
+    if let Some(route_id) = options.route_id {
+        select_route(&mut json, route_id)?;
+    }
+    let mut route = read_alltrails(&json, options)?;
+    route.warnings.extend(warning);
+    if !options.waypoints_only {
+        route.warnings.extend(ignored::other_routes(&json));
+    }
+    Ok(route)
 }
 
-pub fn get_input_reader(input: &Option<String>) -> Result<Box<dyn BufRead>, Error> {
-    match input.as_deref() {
-        None | Some("-") => Ok(Box::new(std::io::stdin().lock())),
-        Some(file_name) => {
-            let file = File::open(file_name).map_err(|source| Error::FileError {
-                path: file_name.to_string(),
-                source,
-            })?;
-            Ok(Box::new(BufReader::new(file)))
+/// Converts each of the routes on the trail's map separately, rather than only the first or
+/// the one [`Options::route_id`] picks. Each track is numbered, e.g. "Ashton Court (2/3)".
+/// Inputs other than AllTrails JSON, and maps with a single route, give a single conversion.
+/// Routes are converted at the same time across the available cores, but returned in order.
+pub fn convert_routes(reader: impl Read, options: &Options) -> Result<Vec<Conversion>, Error> {
+    let started = Instant::now();
+    let input = read_input(reader, options.max_input_size)?;
+    let json = match input::identify(&input, options.input_format, options.filter.as_ref())? {
+        Input::AllTrails(json) => json,
+        _ => return Ok(vec![convert(input.as_slice(), options)?]),
+    };
+    let mut json = normalize_layout(json)?;
+    let warning = prefer_layout(&mut json, options.prefer_format);
+    select_trail(&mut json, options.trail_index)?;
+
+    // Everything is read from the first route, so each route is given a copy of the rest of
+    // the document of its own to be read from, which also lets them be read in parallel.
+    let routes = routes_mut(&mut json)
+        .map(std::mem::take)
+        .unwrap_or_default();
+    let documents: Vec<Value> = if routes.is_empty() {
+        vec![json]
+    } else {
+        routes
+            .into_iter()
+            .map(|route| {
+                let mut document = json.clone();
+                if let Some(routes) = routes_mut(&mut document) {
+                    routes.push(route);
+                }
+                document
+            })
+            .collect()
+    };
+    let count = documents.len();
+    let conversions = parallel_map(&documents, |document| {
+        let mut route = read_alltrails(document, options)?;
+        route.warnings.extend(warning.clone());
+        convert_route(route, options, started)
+    });
+
+    let mut numbered = Vec::with_capacity(count);
+    for (i, conversion) in conversions.into_iter().enumerate() {
+        let mut conversion = conversion?;
+        if count > 1 {
+            let name = conversion.track.name.as_deref().unwrap_or(&conversion.name);
+            conversion.track.name = Some(format!("{name} ({}/{count})", i + 1));
+            conversion.track.number = Some(options.start_number.saturating_add(i as u32));
         }
+        numbered.push(conversion);
     }
+    Ok(numbered)
 }
 
-pub fn get_output_writer(output: &Option<String>) -> Result<BufWriter<Box<dyn Write>>, Error> {
-    let inner_writer: Box<dyn Write> = match output.as_deref() {
-        None | Some("-") => Box::new(std::io::stdout().lock()),
-        Some(file_name) => {
-            let file = File::create(file_name).map_err(|source| Error::FileError {
-                path: file_name.to_string(),
-                source,
-            })?;
-            Box::new(file)
-        }
+/// Converts the chosen trail along with, if [`Options::include_bundled`] is set, each trail
+/// an offline map pack bundles with it under `bundledTrails` or `nearbyTrails`. Bundled trails
+/// that can't be converted, such as those without a route, are skipped with a warning on the
+/// chosen trail's conversion, which always comes first. Bundled trails are converted at the
+/// same time across the available cores, but returned in order.
+pub fn convert_trails(reader: impl Read, options: &Options) -> Result<Vec<Conversion>, Error> {
+    let started = Instant::now();
+    let input = read_input(reader, options.max_input_size)?;
+    let json = match input::identify(&input, options.input_format, options.filter.as_ref())? {
+        Input::AllTrails(json) if options.include_bundled => json,
+        _ => return Ok(vec![convert(input.as_slice(), options)?]),
     };
+    let mut json = normalize_layout(json)?;
+    let warning = prefer_layout(&mut json, options.prefer_format);
+    select_trail(&mut json, options.trail_index)?;
+    if let Some(route_id) = options.route_id {
+        select_route(&mut json, route_id)?;
+    }
+    let bundled = bundled::take(&mut json);
+    let mut route = read_alltrails(&json, options)?;
+    route.warnings.extend(warning);
+    if !options.waypoints_only {
+        route.warnings.extend(ignored::other_routes(&json));
+    }
+    let mut conversions = vec![convert_route(route, options, started)?];
 
-    Ok(BufWriter::new(inner_writer))
+    // Naming and picking a route only make sense for the chosen trail.
+    let bundled_options = Options {
+        name: None,
+        fallback_name: None,
+        route_id: None,
+        ..options.clone()
+    };
+    let converted = parallel_map(&bundled, |json| {
+        read_alltrails(json, &bundled_options)
+            .and_then(|route| convert_route(route, &bundled_options, started))
+    });
+    for (json, conversion) in bundled.iter().zip(converted) {
+        match conversion {
+            Ok(conversion) => conversions.push(conversion),
+            Err(e) => {
+                let name = extract_route_name(json)
+                    .map_or_else(|_| fallback_name(extract_trail_id(json)), |n| n.to_string());
+                let reason = e.to_string();
+                conversions[0]
+                    .warnings
+                    .push(Warning::BundledSkipped { name, reason });
+            }
+        }
+    }
+    Ok(conversions)
 }
 
-pub fn run(reader: impl Read, writer: impl Write) -> Result<(), Error> {
-    let json: Value = serde_json::from_reader(reader)?;
+/// Reads the whole input, failing with [`Error::InputTooLarge`] rather than reading more than
+/// `max_size` bytes of it.
+pub fn read_input(reader: impl Read, max_size: Option<u64>) -> Result<Vec<u8>, Error> {
+    let mut input = Vec::new();
+    reader
+        .take(max_size.map_or(u64::MAX, |max| max.saturating_add(1)))
+        .read_to_end(&mut input)
+        .map_err(Error::InputReadError)?;
+    match max_size {
+        Some(limit) if input.len() as u64 > limit => Err(Error::InputTooLarge { limit }),
+        _ => Ok(input),
+    }
+}
 
-    let polyline = extract_polyline(&json)?;
-    let route_name = extract_route_name(&json)?;
+/// Rewrites the layouts of AllTrails' other APIs into the offline one, which is what
+/// everything is read from.
+fn normalize_layout(json: Value) -> Result<Value, Error> {
+    let json = trail_detail::unwrap(graphql::unwrap(json)?);
+    Ok(synced_trails::unwrap(json))
+}
 
-    let line_string = polyline::decode_polyline(&polyline, POLYLINE_PRECISION)?;
+/// Drops one of the roots of a document that has both `trails` and `maps`, which are
+/// otherwise read from whichever is looked up first. Unless a layout was asked for, the offline
+/// one wins and a warning saying so is returned.
+fn prefer_layout(json: &mut Value, prefer: Option<Layout>) -> Option<Warning> {
+    let fields = json.as_object_mut()?;
+    if !(fields.contains_key("trails") && fields.contains_key("maps")) {
+        return None;
+    }
+    let read = prefer.unwrap_or(Layout::Offline);
+    fields.remove(match read {
+        Layout::Offline => "maps",
+        Layout::Deep => "trails",
+    });
+    prefer.is_none().then_some(Warning::BothLayouts { read })
+}
 
-    let track = create_gpx(line_string, route_name);
+/// Moves the chosen trail to the front of the document, which is where everything is read
+/// from.
+fn select_trail(json: &mut Value, index: usize) -> Result<(), Error> {
+    if index == 0 {
+        return Ok(());
+    }
+    // The same two layouts as everywhere else: detail=offline and detail=deep.
+    let key = if json.get("trails").is_some() {
+        "trails"
+    } else {
+        "maps"
+    };
+    let Some(trails) = json.get_mut(key).and_then(Value::as_array_mut) else {
+        return Err(Error::NoSuchTrail { index, count: 1 });
+    };
+    if index >= trails.len() {
+        return Err(Error::NoSuchTrail {
+            index,
+            count: trails.len(),
+        });
+    }
+    trails.swap(0, index);
+    Ok(())
+}
 
-    write_gpx(track, writer)?;
+/// The routes of the first trail's map.
+fn routes_mut(json: &mut Value) -> Option<&mut Vec<Value>> {
+    let path = ["/trails/0/defaultMap/routes", "/maps/0/routes"]
+        .into_iter()
+        .find(|path| json.pointer(path).is_some())?;
+    json.pointer_mut(path)?.as_array_mut()
+}
 
+/// Moves the route with the given ID to the front of the trail's routes.
+fn select_route(json: &mut Value, id: u64) -> Result<(), Error> {
+    let routes = routes_mut(json).ok_or(Error::NoSuchRoute { id })?;
+    // IDs are usually numbers, but some responses give them as strings.
+    let index = routes
+        .iter()
+        .position(|route| {
+            find_in_json(route, &["/id", "/routeId"]).is_some_and(|route_id| {
+                route_id.as_u64() == Some(id) || route_id.as_str() == Some(&id.to_string())
+            })
+        })
+        .ok_or(Error::NoSuchRoute { id })?;
+    routes.swap(0, index);
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use geo_types::Coord;
-    use gpx::Gpx;
-    use polyline::encode_coordinates;
-    use serde_json::{json, Value};
-    use std::io::BufReader;
+/// A trail in an AllTrails response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrailListing {
+    /// Where the trail is in the response, as used by [`Options::trail_index`].
+    pub index: usize,
+    pub id: Option<u64>,
+    pub name: Option<String>,
+}
 
-    struct TestCase<'tc> {
-        name: &'tc str,
-        route_name: &'tc str,
-        coords: Vec<Coord>,
-        json_builder: Box<dyn Fn(&str) -> Value>,
+impl fmt::Display for TrailListing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            self.index,
+            self.name.as_deref().unwrap_or("(unnamed)")
+        )?;
+        if let Some(id) = self.id {
+            write!(f, " (ID {id})")?;
+        }
+        Ok(())
     }
+}
 
-    fn run_conversion_test(case: TestCase<'_>) {
-        let polyline_str = encode_coordinates(case.coords.clone(), POLYLINE_PRECISION)
-            .expect("Failed to encode polyline");
-        let json_value = (case.json_builder)(&polyline_str);
-        let json_input = json_value.to_string();
-        let parsed_gpx = run_and_parse_gpx(&json_input);
+/// Lists the trails in an AllTrails response, which may have more than one when it's a set of
+/// search results or a list.
+pub fn list_trails(mut reader: impl Read) -> Result<Vec<TrailListing>, Error> {
+    let mut input = Vec::new();
+    reader
+        .read_to_end(&mut input)
+        .map_err(Error::InputReadError)?;
+    let json = normalize_layout(json::parse(&input)?)?;
+    trail_listings(&json).ok_or(Error::NoRouteLines)
+}
 
-        assert_gpx_basics(&parsed_gpx, case.route_name, case.coords.len());
+/// Lists the trails in an AllTrails document that's already in the offline or deep layout.
+fn trail_listings(json: &Value) -> Option<Vec<TrailListing>> {
+    let trails = find_in_json(json, &["/trails", "/maps"]).and_then(Value::as_array)?;
+    Some(
+        trails
+            .iter()
+            .enumerate()
+            .map(|(index, trail)| TrailListing {
+                index,
+                id: find_in_json(trail, &["/id", "/trailId"]).and_then(Value::as_u64),
+                name: trail
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            })
+            .collect(),
+    )
+}
 
-        let points = &parsed_gpx.tracks[0].segments[0].points;
-        for (i, coord) in case.coords.iter().enumerate() {
-            let point = points[i].point();
-            const TOLERANCE: f64 = 1e-6;
-            assert!(
-                (point.y() - coord.y).abs() < TOLERANCE && (point.x() - coord.x).abs() < TOLERANCE,
-                "Test case '{}' failed: point {} mismatch.\n Expected: ({:?})\n  Got: ({:?})",
-                case.name,
-                i,
-                coord,
-                point
-            );
+/// Converts a bare encoded polyline, as found in AllTrails' `pointsData` and many other APIs.
+pub fn convert_polyline(encoded: &str, options: &Options) -> Result<Conversion, Error> {
+    let started = Instant::now();
+    convert_route(
+        input::read_polyline(encoded, options.swap_latlon)?,
+        options,
+        started,
+    )
+}
+
+/// Where newer responses put route geometry that they give as GeoJSON rather than polylines.
+const ROUTE_GEOJSON_PATHS: &[&str] = &[
+    "/trails/0/defaultMap/routesGeojson",
+    "/maps/0/routesGeojson",
+    "/trails/0/defaultMap/routes/0/geojson",
+    "/maps/0/routes/0/geojson",
+    "/trails/0/routesGeojson",
+    "/trails/0/geojson",
+];
+
+fn read_alltrails(json: &Value, options: &Options) -> Result<Route, Error> {
+    let segments = match options.extractors.extract(json) {
+        Some(polylines) => Ok(polylines
+            .into_iter()
+            .map(|polyline| vec![polyline])
+            .collect()),
+        None => extract_line_segments(json),
+    };
+    let mut route = match segments {
+        Ok(segments) => read_line_segments(json, &segments, options)?,
+        Err(Error::PolylineNotFound(failure)) => match lookup(json, ROUTE_GEOJSON_PATHS) {
+            Ok((path, geojson)) => read_route_geojson(json, geojson, path, options)?,
+            Err(_) if options.waypoints_only => Route::new(String::new(), Vec::new(), false),
+            // The polylines are what's usually there, so they're what gets reported missing.
+            Err(_) => return Err(Error::PolylineNotFound(failure)),
+        },
+        Err(e) => return Err(e),
+    };
+
+    route.trail_id = extract_trail_id(json);
+    route.name = match extract_route_name(json) {
+        Ok(name) => name.to_string(),
+        Err(e) if options.strict && options.name.is_none() => return Err(e),
+        Err(_) => {
+            let name = options
+                .fallback_name
+                .clone()
+                .unwrap_or_else(|| fallback_name(route.trail_id));
+            if options.name.is_none() {
+                let warning = Warning::Unnamed { name: name.clone() };
+                route.warnings.push(warning);
+            }
+            name
         }
+    };
+    route.waypoints = poi::extract_waypoints(json);
+    route.warnings.extend(ignored::unlocated_waypoints(json));
+    route.warnings.extend(ignored::other_trails(json));
+    route.warnings.extend(ignored::bundled_trails(json));
+    route.description = extract_overview(json);
+    route.trail_stats = extract_trail_stats(json);
+    route.link = extract_trail_link(json);
+    Ok(route)
+}
+
+/// A name for a route whose input doesn't give one.
+fn fallback_name(trail_id: Option<u64>) -> String {
+    match trail_id {
+        Some(id) => format!("AllTrails trail {id}"),
+        None => UNNAMED_ROUTE.to_string(),
     }
+}
 
-    #[test]
-    fn test_offline_format_conversion() {
-        let case = TestCase {
-            name: "offline_format",
-            route_name: "My Test Trail",
-            coords: vec![
-                Coord { x: -120.2, y: 38.5 },
-                Coord {
-                    x: -120.95,
-                    y: 40.7,
-                },
-                Coord {
-                    x: -121.2116,
-                    y: 40.9416,
-                },
-            ],
-            json_builder: Box::new(|polyline| {
-                json!({
-                    "trails": [
-                        {
-                            "name": "My Test Trail",
+/// Reads the route geometry from embedded GeoJSON, keeping any elevations it has.
+fn read_route_geojson(
+    json: &Value,
+    geojson: &Value,
+    path: &str,
+    options: &Options,
+) -> Result<Route, Error> {
+    let lines = input::read_embedded_geojson(geojson, &JsonPointer::new(path))?;
+    if lines.is_empty() {
+        return Err(Error::NoRouteLines);
+    }
+    let swapped_latlon = options.swap_latlon
+        || geo::axes_look_swapped(
+            lines.iter().flatten().map(|&(c, _)| c),
+            extract_location(json),
+        );
+    Ok(Route {
+        sources: vec![JsonPointer::new(path)],
+        ..Route::new(String::new(), lines, swapped_latlon)
+    })
+}
+
+fn read_line_segments(
+    json: &Value,
+    segments: &[Vec<Polyline<'_>>],
+    options: &Options,
+) -> Result<Route, Error> {
+    // Decoded one piece at a time, so that problems can be pinned on the right piece.
+    let polylines: Vec<&Polyline> = segments.iter().flatten().collect();
+    let mut line_strings = decode_polylines(&polylines)?;
+
+    let swapped_latlon = options.swap_latlon
+        || geo::axes_look_swapped(
+            line_strings.iter().flatten().copied(),
+            extract_location(json),
+        );
+    if swapped_latlon {
+        for line_string in &mut line_strings {
+            geo::swap_axes(&mut line_string.0);
+        }
+    }
+
+    for (polyline, line_string) in polylines.iter().zip(&line_strings) {
+        if let Some((index, c)) = line_string
+            .0
+            .iter()
+            .enumerate()
+            .find(|(_, c)| c.y.abs() > 90.0 || c.x.abs() > 180.0)
+        {
+            return Err(Error::CoordinateOutOfRange {
+                pointer: polyline.pointer().clone(),
+                index,
+                latitude: c.y,
+                longitude: c.x,
+            });
+        }
+    }
+
+    let mut pieces = line_strings.into_iter();
+    let lines = segments
+        .iter()
+        .map(|segment| {
+            let line = join_pieces(pieces.by_ref().take(segment.len()));
+            line.into_iter().map(RoutePoint::from).collect()
+        })
+        .collect();
+
+    Ok(Route {
+        lines,
+        swapped_latlon,
+        sources: polylines
+            .iter()
+            .map(|polyline| polyline.pointer().clone())
+            .collect(),
+        ..Route::new(String::new(), Vec::new(), false)
+    })
+}
+
+/// Decodes polylines in order, sharing them out across the available cores when there are
+/// enough of them, as in large offline bundles, for it to be worth it. Fails with the first
+/// polyline that doesn't decode.
+fn decode_polylines(polylines: &[&Polyline<'_>]) -> Result<Vec<LineString<f64>>, Error> {
+    let bytes: usize = polylines.iter().map(|polyline| polyline.len()).sum();
+    if bytes < PARALLEL_DECODE_MIN_BYTES {
+        return polylines.iter().map(|polyline| polyline.decode()).collect();
+    }
+    parallel_map(polylines, |polyline| polyline.decode())
+        .into_iter()
+        .collect()
+}
+
+/// Maps each item with `f`, sharing them out in order across the available cores, and
+/// returns the results in the same order as the items.
+fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(items.len());
+    if threads < 2 {
+        return items.iter().map(f).collect();
+    }
+
+    let chunk_size = items.len().div_ceil(threads);
+    let f = &f;
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+/// Applies the conversion options to a route read from any kind of input.
+fn convert_route(route: Route, options: &Options, started: Instant) -> Result<Conversion, Error> {
+    let mut name = options.name.clone().unwrap_or(route.name);
+    if options.ascii_names {
+        name = text::to_ascii(&name);
+    }
+    let mut lines = route.lines;
+    let mut waypoints = route.waypoints;
+
+    if !options.allow_empty && !options.waypoints_only && lines.iter().all(Vec::is_empty) {
+        return Err(Error::EmptyRoute);
+    }
+    let points = lines.iter().map(Vec::len).sum();
+    if points > 0 && points < options.min_points {
+        return Err(Error::TooFewPoints {
+            points,
+            min: options.min_points,
+        });
+    }
+
+    let mut warnings = route.warnings;
+    for problem in sanity::check(&lines) {
+        if !options.lenient {
+            return Err(Error::ImplausibleRoute(problem));
+        }
+        warnings.push(Warning::Implausible(problem));
+    }
+
+    if options.merge_segments {
+        lines = segments::merge(lines, options.max_bridge);
+    }
+
+    #[cfg(feature = "osrm")]
+    if let Some(endpoint) = &options.match_osrm {
+        lines = osrm::match_lines(endpoint, lines)?;
+    }
+
+    if let Some(geofence) = &options.within {
+        lines = lines.iter().flat_map(|line| geofence.clip(line)).collect();
+        if lines.is_empty() && !options.waypoints_only {
+            return Err(Error::NothingWithin);
+        }
+        waypoints.retain(|waypoint| geofence.contains(waypoint.point().0));
+    }
+
+    if let Some(max_gap) = options.split_gap {
+        lines = segments::split_at_gaps(lines, max_gap);
+    }
+
+    // Waypoints near the ends would give away what trimming or fuzzing is hiding.
+    let hidden_radius = options
+        .trim_ends
+        .into_iter()
+        .chain(options.fuzz_ends)
+        .reduce(f64::max);
+    if let Some(radius) = hidden_radius {
+        let ends: Vec<Coord<f64>> = [
+            lines.first().and_then(|l| l.first()),
+            lines.last().and_then(|l| l.last()),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|point| point.coord)
+        .collect();
+        waypoints.retain(|waypoint| {
+            ends.iter()
+                .all(|&end| geo::haversine_distance(end, waypoint.point().0) > radius)
+        });
+    }
+
+    if let Some(meters) = options.trim_ends {
+        lines = segments::trim_ends(lines, meters);
+        if lines.is_empty() {
+            return Err(Error::TrimmedAway);
+        }
+    }
+
+    if let Some(radius) = options.fuzz_ends {
+        lines = segments::fuzz_ends(lines, radius);
+        if lines.is_empty() {
+            return Err(Error::TrimmedAway);
+        }
+    }
+
+    if options.waypoints_only && waypoints.is_empty() {
+        return Err(Error::NoWaypoints);
+    }
+
+    if let Some(simplify) = &options.simplify {
+        for line in &mut lines {
+            *line = simplify::simplify(line, simplify);
+        }
+    }
+
+    if options.round_trip {
+        lines = segments::round_trip(lines);
+        if options.merge_segments {
+            lines = segments::merge(lines, options.max_bridge);
+        }
+    }
+
+    let line_strings = lines
+        .iter()
+        .map(|line| line.iter().map(|point| point.coord).collect())
+        .collect();
+    let mut track = create_track(line_strings, RouteName(&name));
+    track.description = route.description;
+    let track_points = track.segments.iter_mut().flat_map(|s| &mut s.points);
+    for (point, route_point) in track_points.zip(lines.iter().flatten()) {
+        point.elevation = route_point.elevation;
+    }
+    let sensors = if lines
+        .iter()
+        .flatten()
+        .any(|point| !point.readings.is_empty())
+    {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|point| point.readings).collect())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let round = options.coord_decimals.map(coordinate_rounder);
+    if let Some(round) = &round {
+        round_coordinates(&mut track, round);
+    }
+    for waypoint in &mut waypoints {
+        options.symbols.apply(waypoint);
+        if let Some(round) = &round {
+            *waypoint = moved_waypoint(waypoint, round(waypoint.point().0));
+        }
+    }
+
+    if options.synthetic_times {
+        let start = options.start_time.unwrap_or_else(|| {
+            if options.deterministic {
+                OffsetDateTime::UNIX_EPOCH
+            } else {
+                OffsetDateTime::now_utc()
+            }
+        });
+        timing::add_times(&mut track, start, options.pace);
+    }
+
+    let stats = track_stats(&track, options.elevation_threshold);
+    let mut trail_stats = route.trail_stats;
+    if let Some(shape) = stats.shape.filter(|_| options.detect_route_type) {
+        trail_stats
+            .get_or_insert_with(TrailStats::default)
+            .route_type
+            .get_or_insert_with(|| shape.to_string());
+    }
+    let provenance = options
+        .provenance
+        .then(|| Provenance::now(route.trail_id, route.link.clone(), options.deterministic));
+
+    Ok(Conversion {
+        stats,
+        name,
+        trail_stats,
+        swapped_latlon: route.swapped_latlon,
+        track,
+        link: route.link,
+        sensors,
+        waypoints,
+        trail_id: route.trail_id,
+        elapsed: started.elapsed(),
+        warnings,
+        sources: route.sources,
+        provenance,
+    })
+}
+
+/// Rounds coordinates to the given number of decimal places.
+fn coordinate_rounder(decimals: u32) -> impl Fn(Coord<f64>) -> Coord<f64> {
+    let factor = 10_f64.powi(i32::try_from(decimals).unwrap_or(i32::MAX));
+    // Adding zero turns -0 into 0, which would otherwise be written as "-0".
+    let round = move |value: f64| (value * factor).round() / factor + 0.0;
+    move |coord| Coord {
+        x: round(coord.x),
+        y: round(coord.y),
+    }
+}
+
+/// The time of the track's first point, if it has one.
+pub(crate) fn first_time(track: &Track) -> Option<Time> {
+    track
+        .segments
+        .iter()
+        .flat_map(|segment| &segment.points)
+        .next()
+        .and_then(|point| point.time)
+}
+
+/// Moves each point to its rounded position, dropping points that then land on the one
+/// before.
+fn round_coordinates(track: &mut Track, round: impl Fn(Coord<f64>) -> Coord<f64>) {
+    for segment in &mut track.segments {
+        let mut rounded: Vec<Waypoint> = Vec::with_capacity(segment.points.len());
+        for point in &segment.points {
+            let coord = round(point.point().0);
+            if rounded.last().is_some_and(|last| last.point().0 == coord) {
+                continue;
+            }
+            // The point can't be moved in place, but elevation is all that's been set so far.
+            let mut moved = Waypoint::new(coord.into());
+            moved.elevation = point.elevation;
+            rounded.push(moved);
+        }
+        segment.points = rounded;
+    }
+}
+
+/// A copy of the waypoint at another position, since a waypoint's position can't be changed.
+fn moved_waypoint(waypoint: &Waypoint, position: Coord<f64>) -> Waypoint {
+    let mut moved = Waypoint::new(position.into());
+    moved.elevation = waypoint.elevation;
+    moved.time = waypoint.time;
+    moved.name = waypoint.name.clone();
+    moved.comment = waypoint.comment.clone();
+    moved.description = waypoint.description.clone();
+    moved.source = waypoint.source.clone();
+    moved.links = waypoint.links.clone();
+    moved.symbol = waypoint.symbol.clone();
+    moved.type_ = waypoint.type_.clone();
+    moved
+}
+
+/// Writes a converted route in the given format, with the output settings of `options`, such
+/// as [`Options::author`] and [`Options::waypoints_only`].
+pub fn write_conversion(
+    conversion: &Conversion,
+    format: Format,
+    options: &Options,
+    writer: impl Write,
+) -> Result<(), Error> {
+    let tracks: &[Track] = if options.waypoints_only {
+        &[]
+    } else {
+        std::slice::from_ref(&conversion.track)
+    };
+    match format {
+        Format::Gpx => {
+            let mut builder = GpxBuilder::new();
+            if let Some(trail_stats) = &conversion.trail_stats {
+                let name = RouteName(&conversion.name);
+                builder = builder.metadata(create_metadata(name, trail_stats));
+            }
+            if let Some(time) = first_time(&conversion.track) {
+                builder = builder.time(time);
+            }
+            if options.write_estimate {
+                let estimate = format!(
+                    "Naismith's rule: {}",
+                    format_minutes(conversion.stats.estimated_minutes)
+                );
+                let description = match &conversion.trail_stats {
+                    Some(trail_stats) => format!("{trail_stats}, {estimate}"),
+                    None => estimate,
+                };
+                builder = builder.description(description);
+            }
+            let builder = add_conversions(builder, std::slice::from_ref(conversion), options);
+            return write_validated_gpx(&builder, options.validate_gpx, writer);
+        }
+        Format::GeoJson => geojson::write_geojson(tracks, &conversion.waypoints, writer),
+        Format::Csv if options.waypoints_only => {
+            csv::write_waypoints(&conversion.waypoints, writer)
+        }
+        Format::Csv => csv::write_csv(tracks, writer),
+        Format::Kml => kml::write_kml(&conversion.name, tracks, &conversion.waypoints, writer),
+        Format::Html => html::write_html(&conversion.name, tracks, &conversion.waypoints, writer),
+        Format::Svg => svg::write_svg(tracks, &conversion.waypoints, options.units, writer),
+    }
+    .map_err(Error::OutputWriteError)
+}
+
+/// Writes several converted routes as one GPX document, with a track for each, with the
+/// output settings of `options`.
+pub fn write_merged_gpx(
+    conversions: &[Conversion],
+    options: &Options,
+    writer: impl Write,
+) -> Result<(), Error> {
+    write_validated_gpx(
+        &add_conversions(GpxBuilder::new(), conversions, options),
+        options.validate_gpx,
+        writer,
+    )
+}
+
+/// Writes a GPX document, first checking it against the GPX 1.1 schema if `validate` is set.
+fn write_validated_gpx(
+    builder: &GpxBuilder,
+    validate: bool,
+    mut writer: impl Write,
+) -> Result<(), Error> {
+    if !validate {
+        return builder.write(writer);
+    }
+    let mut gpx = Vec::new();
+    builder.write(&mut gpx)?;
+    schema::validate_gpx(&String::from_utf8_lossy(&gpx)).map_err(Error::InvalidGpx)?;
+    writer
+        .write_all(&gpx)
+        .and_then(|()| writer.flush())
+        .map_err(Error::OutputWriteError)
+}
+
+/// Adds the tracks, waypoints and sensor readings of conversions to a GPX document.
+fn add_conversions(
+    mut builder: GpxBuilder,
+    conversions: &[Conversion],
+    options: &Options,
+) -> GpxBuilder {
+    if conversions.iter().any(|c| !c.sensors.is_empty()) {
+        builder = builder.namespace(
+            TRACK_POINT_EXTENSION_PREFIX,
+            TRACK_POINT_EXTENSION_NAMESPACE,
+        );
+    }
+    if conversions.iter().any(|c| c.provenance.is_some()) {
+        builder = builder.namespace(PROVENANCE_PREFIX, PROVENANCE_NAMESPACE);
+    }
+    if options.color.is_some() {
+        for (prefix, uri) in DISPLAY_NAMESPACES {
+            builder = builder.namespace(prefix, uri);
+        }
+    }
+    if let Some(author) = &options.author {
+        builder = builder.author(author);
+    }
+    if let Some(link) = &options.publisher_link {
+        builder = builder.link(link);
+    }
+    for conversion in conversions {
+        for waypoint in &conversion.waypoints {
+            builder = builder.waypoint(waypoint.clone());
+        }
+        if options.waypoints_only {
+            continue;
+        }
+        let point_extensions = conversion
+            .sensors
+            .iter()
+            .flatten()
+            .map(|readings| {
+                if readings.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![readings.to_xml()]
+                }
+            })
+            .collect();
+        let mut extensions: Vec<String> = conversion
+            .provenance
+            .iter()
+            .map(Provenance::to_xml)
+            .collect();
+        // Auto colors are picked from the route, so routes written together differ.
+        let color = options
+            .color
+            .map(|color| color.for_route(conversion.stats.fingerprint));
+        extensions.extend(color.map(display_extensions).into_iter().flatten());
+        builder = builder.track_with_point_extensions(
+            conversion.track.clone(),
+            extensions,
+            point_extensions,
+        );
+    }
+    if builder.track_count() > 1 {
+        builder = builder.number_tracks(options.start_number);
+    }
+    builder
+}
+
+/// Like [`run_with_options`], but reads and writes without blocking, for embedding in async
+/// applications. The conversion itself happens in memory once the input has been read.
+#[cfg(feature = "tokio")]
+pub async fn run_async(
+    mut reader: impl tokio::io::AsyncRead + Unpin,
+    mut writer: impl tokio::io::AsyncWrite + Unpin,
+    options: &Options,
+) -> Result<Conversion, Error> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Reading one byte past the limit is enough for the conversion to notice it's exceeded.
+    let mut input = Vec::new();
+    (&mut reader)
+        .take(
+            options
+                .max_input_size
+                .map_or(u64::MAX, |max| max.saturating_add(1)),
+        )
+        .read_to_end(&mut input)
+        .await
+        .map_err(Error::InputReadError)?;
+
+    let mut output = Vec::new();
+    let conversion = run_with_options(input.as_slice(), &mut output, options)?;
+
+    writer
+        .write_all(&output)
+        .await
+        .map_err(Error::OutputWriteError)?;
+    writer.flush().await.map_err(Error::OutputWriteError)?;
+
+    Ok(conversion)
+}
+
+/// Converts the LineString features of a vector tile into one track each, returning how many
+/// tracks were written. Features can be narrowed down by layer name and by an area they must
+/// pass through.
+#[cfg(feature = "mvt")]
+pub fn run_mvt(
+    mut reader: impl Read,
+    writer: impl Write,
+    tile: mvt::TileId,
+    layer: Option<&str>,
+    bbox: Option<geo::BoundingBox>,
+) -> Result<usize, Error> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(Error::InputReadError)?;
+
+    let builder = mvt::decode_lines(&data, tile)?
+        .into_iter()
+        .filter(|line| layer.map_or(true, |layer| line.layer == layer))
+        .filter(|line| bbox.map_or(true, |bbox| bbox.intersects_path(&line.coords)))
+        .enumerate()
+        .map(|(i, line)| {
+            let name = line
+                .name
+                .unwrap_or_else(|| format!("{} {}", line.layer, i + 1));
+            create_gpx(LineString::new(line.coords), RouteName(&name))
+        })
+        .fold(GpxBuilder::new(), GpxBuilder::track);
+
+    let count = builder.track_count();
+    if count == 0 {
+        return Err(Error::NoTileLines);
+    }
+
+    builder.number_tracks(1).write(writer)?;
+    Ok(count)
+}
+
+/// Converts an AllTrails route to GPX, writing each point as soon as it's decoded rather than
+/// building the whole track first, which keeps memory use flat for very long recordings.
+/// Returns how many points were written.
+///
+/// Only the route's name and points are written. Of the options, only those choosing the
+/// route, naming it, swapping its axes and rounding its coordinates apply, along with the
+/// checks [`convert`] makes before converting anything. Options that hide part of the route,
+/// such as [`Options::trim_ends`], fail with [`Error::StreamCantHide`] rather than being
+/// ignored.
+pub fn run_streaming(
+    reader: impl Read,
+    writer: impl Write,
+    options: &Options,
+) -> Result<usize, Error> {
+    let hiding = [
+        ("--trim-ends", options.trim_ends.is_some()),
+        ("--fuzz-ends", options.fuzz_ends.is_some()),
+        ("--within", options.within.is_some()),
+    ];
+    if let Some(&(option, _)) = hiding.iter().find(|(_, set)| *set) {
+        return Err(Error::StreamCantHide(option));
+    }
+
+    let input = read_input(reader, options.max_input_size)?;
+    let mut json = normalize_layout(input::parse_json(&input, options.filter.as_ref())?)?;
+    prefer_layout(&mut json, options.prefer_format);
+    select_trail(&mut json, options.trail_index)?;
+    if let Some(route_id) = options.route_id {
+        select_route(&mut json, route_id)?;
+    }
+    let segments = extract_line_segments(&json)?;
+    let mut name = match (&options.name, extract_route_name(&json)) {
+        (Some(name), _) => name.clone(),
+        (None, Ok(name)) => name.to_string(),
+        (None, Err(e)) if options.strict => return Err(e),
+        (None, Err(_)) => options
+            .fallback_name
+            .clone()
+            .unwrap_or_else(|| fallback_name(extract_trail_id(&json))),
+    };
+    if options.ascii_names {
+        name = text::to_ascii(&name);
+    }
+
+    // The route is decoded more than once rather than kept: first to see whether its axes
+    // are swapped, then to check it as convert_route would, and only then to write it.
+    let swapped = options.swap_latlon
+        || geo::axes_look_swapped(
+            segments
+                .iter()
+                .flatten()
+                .flat_map(|polyline| decode_points(polyline, POLYLINE_PRECISION))
+                .map_while(Result::ok),
+            extract_location(&json),
+        );
+
+    let mut checker = sanity::Checker::default();
+    let mut points = 0;
+    stream_points(&segments, swapped, |coord| {
+        match coord {
+            None => checker.start_line(),
+            Some(coord) => {
+                checker.push(coord);
+                points += 1;
+            }
+        }
+        Ok(())
+    })?;
+    if points == 0 && !options.allow_empty {
+        return Err(Error::EmptyRoute);
+    }
+    if points > 0 && points < options.min_points {
+        return Err(Error::TooFewPoints {
+            points,
+            min: options.min_points,
+        });
+    }
+    if let Some(problem) = checker.finish().into_iter().next() {
+        if !options.lenient {
+            return Err(Error::ImplausibleRoute(problem));
+        }
+    }
+
+    let round = options.coord_decimals.map(coordinate_rounder);
+    let mut gpx = GpxStreamWriter::new(writer)?;
+    gpx.track(&name)?;
+    stream_points(&segments, swapped, |coord| match coord {
+        None => gpx.segment(),
+        Some(coord) => {
+            let coord = round.as_ref().map_or(coord, |round| round(coord));
+            gpx.point(&Waypoint::new(coord.into()))
+        }
+    })?;
+    gpx.finish()?;
+    Ok(points)
+}
+
+/// Decodes each line segment a point at a time, passing `visit` `None` at the start of each
+/// segment and then each of its points. Pieces are joined as by join_pieces.
+fn stream_points(
+    segments: &[Vec<Polyline<'_>>],
+    swapped: bool,
+    mut visit: impl FnMut(Option<Coord<f64>>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    for pieces in segments {
+        visit(None)?;
+        let mut last = None;
+        for polyline in pieces {
+            for (index, coord) in decode_points(polyline, POLYLINE_PRECISION).enumerate() {
+                let mut coord = coord?;
+                if swapped {
+                    std::mem::swap(&mut coord.x, &mut coord.y);
+                }
+                if coord.y.abs() > 90.0 || coord.x.abs() > 180.0 {
+                    return Err(Error::CoordinateOutOfRange {
+                        pointer: polyline.pointer().clone(),
+                        index,
+                        latitude: coord.y,
+                        longitude: coord.x,
+                    });
+                }
+                // As in join_pieces, a piece starting where the last ended shares that point.
+                let shared = index == 0 && last == Some(coord);
+                last = Some(coord);
+                if !shared {
+                    visit(Some(coord))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What [`run_batch`] converted, each along with where it was written, and which inputs it
+/// couldn't convert, each along with why.
+#[derive(Debug)]
+pub struct BatchRun {
+    pub converted: Vec<(String, Conversion)>,
+    pub failed: Vec<(String, Error)>,
+}
+
+/// Converts each input into its own file per format, written into a directory or bundled into
+/// a zip archive depending on `output`. An input that's a tar archive, gzipped or not, stands
+/// for each of the JSON, GeoJSON, GPX and KML files in it. Inputs that are the same trail as
+/// an earlier one, going by trail ID or [`fingerprint`](fingerprint::fingerprint), are skipped
+/// with a warning on the earlier one, unless [`Options::keep_duplicates`] is set.
+///
+/// An input that fails to convert doesn't stop the others, but is returned in
+/// [`BatchRun::failed`], unless [`Options::fail_fast`] is set, in which case it's returned as
+/// [`Error::BatchInputError`]. Failing to write the output always stops the run.
+pub fn run_batch(
+    inputs: &[InputSource],
+    output: &str,
+    formats: &[Format],
+    options: &Options,
+) -> Result<BatchRun, Error> {
+    let mut batch = BatchOutput::create(output, options.deterministic)?;
+    let mut names = UniqueNames::default();
+    let mut duplicates = Duplicates::default();
+    let mut conversions: Vec<(String, Conversion)> = Vec::with_capacity(inputs.len());
+    let mut failed = Vec::new();
+
+    let batch_error = |path: &str| {
+        let path = path.to_string();
+        move |source| Error::BatchInputError {
+            path,
+            source: Box::new(source),
+        }
+    };
+
+    for input in inputs {
+        // Archives are converted an entry at a time as they're read, rather than read whole.
+        input.expand(options.max_input_size, |source| {
+            let source = match source {
+                Ok(source) => source,
+                Err(e) if options.fail_fast => return Err(batch_error(&input.to_string())(e)),
+                Err(e) => {
+                    failed.push((input.to_string(), e));
+                    return Ok(());
+                }
+            };
+            let name = source.to_string();
+            // Unnamed routes are better called after their file than their trail ID.
+            let options = Options {
+                fallback_name: input_stem(&name).map(str::to_string),
+                ..options.clone()
+            };
+            let conversion = match source.open().and_then(|reader| convert(reader, &options)) {
+                Ok(conversion) => conversion,
+                Err(e) if options.fail_fast => return Err(batch_error(&name)(e)),
+                Err(e) => {
+                    failed.push((name, e));
+                    return Ok(());
+                }
+            };
+            if !options.keep_duplicates {
+                let fingerprint =
+                    (conversion.stats.points > 0).then_some(conversion.stats.fingerprint);
+                if let Some(i) =
+                    duplicates.check(conversions.len(), conversion.trail_id, fingerprint)
+                {
+                    let warning = Warning::Duplicate { input: name };
+                    conversions[i].1.warnings.push(warning);
+                    return Ok(());
+                }
+            }
+            let file_name = |extension| {
+                let file_name = output_file_name(&name, extension);
+                if options.ascii_file_names {
+                    ascii_file_name(&file_name)
+                } else {
+                    file_name
+                }
+            };
+
+            let parts = conversion.split(options.max_points_per_file, options.start_number);
+            let mut written_to = Vec::with_capacity(formats.len() * parts.len());
+            for &format in formats {
+                for (i, part) in parts.iter().enumerate() {
+                    let mut file_name = file_name(format.extension());
+                    if parts.len() > 1 {
+                        file_name = numbered_path(&file_name, i + 1);
+                    }
+                    let mut sink = batch.sink(&names.claim(file_name));
+                    write_conversion(part, format, &options, sink.writer()?)?;
+                    written_to.push(sink.to_string());
+                }
+            }
+            let written_to = written_to.join(", ");
+            if options.metadata_sidecars {
+                let mut sink = batch.sink(&names.claim(file_name("metadata.json")));
+                metadata::write_metadata(&written_to, &conversion, sink.writer()?)
+                    .map_err(Error::OutputWriteError)?;
+            }
+            conversions.push((written_to, conversion));
+            Ok(())
+        })?;
+    }
+
+    batch.finish()?;
+    Ok(BatchRun {
+        converted: conversions,
+        failed,
+    })
+}
+
+/// Converts each route on the trail's map into its own file per format, for apps that only
+/// import the first track of a file. The files are written into a directory or bundled into
+/// a zip archive depending on `output`, and named from `template` with
+/// [`route_file_stem`]. Each conversion is returned along with where it was written.
+pub fn run_split_routes(
+    input: &InputSource,
+    output: &str,
+    template: &str,
+    formats: &[Format],
+    options: &Options,
+) -> Result<Vec<(String, Conversion)>, Error> {
+    let routes = input
+        .open()
+        .and_then(|reader| convert_routes(reader, options))?;
+    let mut batch = BatchOutput::create(output, options.deterministic)?;
+    let mut names = UniqueNames::default();
+    let mut conversions = Vec::with_capacity(routes.len());
+
+    let count = routes.len();
+    for (i, conversion) in routes.into_iter().enumerate() {
+        let mut stem = route_file_stem(template, &conversion.name, i + 1, count);
+        if options.ascii_file_names {
+            stem = ascii_file_name(&stem);
+        }
+        let mut written_to = Vec::with_capacity(formats.len());
+        for &format in formats {
+            let file_name = format!("{stem}.{}", format.extension());
+            let mut sink = batch.sink(&names.claim(file_name));
+            write_conversion(&conversion, format, options, sink.writer()?)?;
+            written_to.push(sink.to_string());
+        }
+        conversions.push((written_to.join(", "), conversion));
+    }
+
+    batch.finish()?;
+    Ok(conversions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color::Rgb;
+    use geo_types::Coord;
+    use gpx::Gpx;
+    use polyline::encode_coordinates;
+    use preset::Preset;
+    use serde_json::{json, Value};
+    use std::io::BufReader;
+
+    struct TestCase<'tc> {
+        name: &'tc str,
+        route_name: &'tc str,
+        coords: Vec<Coord>,
+        json_builder: Box<dyn Fn(&str) -> Value>,
+    }
+
+    fn run_conversion_test(case: TestCase<'_>) {
+        let polyline_str = encode_coordinates(case.coords.clone(), POLYLINE_PRECISION)
+            .expect("Failed to encode polyline");
+        let json_value = (case.json_builder)(&polyline_str);
+        let json_input = json_value.to_string();
+        let parsed_gpx = run_and_parse_gpx(&json_input);
+
+        assert_gpx_basics(&parsed_gpx, case.route_name, case.coords.len());
+
+        let points = &parsed_gpx.tracks[0].segments[0].points;
+        for (i, coord) in case.coords.iter().enumerate() {
+            let point = points[i].point();
+            const TOLERANCE: f64 = 1e-6;
+            assert!(
+                (point.y() - coord.y).abs() < TOLERANCE && (point.x() - coord.x).abs() < TOLERANCE,
+                "Test case '{}' failed: point {} mismatch.\n Expected: ({:?})\n  Got: ({:?})",
+                case.name,
+                i,
+                coord,
+                point
+            );
+        }
+    }
+
+    #[test]
+    fn test_offline_format_conversion() {
+        let case = TestCase {
+            name: "offline_format",
+            route_name: "My Test Trail",
+            coords: vec![
+                Coord { x: -120.2, y: 38.5 },
+                Coord {
+                    x: -120.95,
+                    y: 40.7,
+                },
+                Coord {
+                    x: -121.2116,
+                    y: 40.9416,
+                },
+            ],
+            json_builder: Box::new(|polyline| {
+                json!({
+                    "trails": [
+                        {
+                            "name": "My Test Trail",
                             "defaultMap": {
                                 "routes": [
                                     {
@@ -243,7 +2196,13 @@ mod tests {
         let case = TestCase {
             name: "deep_format",
             route_name: "My Other Trail",
-            coords: vec![Coord { x: -121.0, y: 38.8 }],
+            coords: vec![
+                Coord { x: -121.0, y: 38.8 },
+                Coord {
+                    x: -121.01,
+                    y: 38.81,
+                },
+            ],
             json_builder: Box::new(|polyline| {
                 json!({
                     "maps": [
@@ -268,6 +2227,1280 @@ mod tests {
         run_conversion_test(case);
     }
 
+    #[test]
+    fn test_trail_stats_in_metadata() {
+        let json = json!({
+            "trails": [
+                {
+                    "name": "Stats Trail",
+                    "routeType": { "name": "Loop" },
+                    "trailGeoStats": {
+                        "length": 12345.6,
+                        "elevationGain": 412.2,
+                        "durationMinutes": 200
+                    },
+                    "defaultMap": {
+                        "routes": [
+                            {
+                                "lineSegments": [
+                                    { "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }
+                                ]
+                            }
+                        ]
+                    }
+                }
+            ]
+        });
+
+        let trail_stats = extract_trail_stats(&json).expect("trail stats should be extracted");
+        assert_eq!(trail_stats.length, Some(12345.6));
+        assert_eq!(trail_stats.route_type.as_deref(), Some("Loop"));
+
+        let gpx = run_and_parse_gpx(&json.to_string());
+        let metadata = gpx.metadata.expect("metadata should be written");
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some(
+                "Length: 12.35 km, Elevation gain: 412 m, Route type: Loop, Estimated time: 3h20m"
+            )
+        );
+    }
+
+    #[test]
+    fn test_gpx_input_keeps_elevation() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk>
+    <name>Hill</name>
+    <trkseg>
+      <trkpt lat="51.45" lon="-2.6"><ele>20</ele></trkpt>
+      <trkpt lat="51.46" lon="-2.61"><ele>85.5</ele></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+
+        let gpx = run_and_parse_gpx(input);
+        assert_gpx_basics(&gpx, "Hill", 2);
+        let points = &gpx.tracks[0].segments[0].points;
+        assert_eq!(points[0].elevation, Some(20.0));
+        assert_eq!(points[1].elevation, Some(85.5));
+    }
+
+    #[test]
+    fn test_split_conversion() {
+        let coords: Vec<Coord> = (0..5)
+            .map(|i| Coord {
+                x: -2.6,
+                y: 51.45 + f64::from(i) * 0.001,
+            })
+            .collect();
+        let polyline = encode_coordinates(coords, POLYLINE_PRECISION).unwrap();
+        let options = Options {
+            name: Some("Long".to_string()),
+            ..Default::default()
+        };
+        let conversion = convert_polyline(&polyline, &options).unwrap();
+
+        let parts = conversion.split(Some(3), 1);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].track.name.as_deref(), Some("Long (1/2)"));
+        assert_eq!(parts[1].track.number, Some(2));
+        let last_of_first = parts[0].track.segments[0].points[2].point();
+        assert_eq!(parts[1].track.segments[0].points[0].point(), last_of_first);
+        assert_eq!(conversion.split(None, 1).len(), 1);
+        assert_eq!(conversion.split(Some(5), 1).len(), 1);
+    }
+
+    #[test]
+    fn test_empty_route() {
+        assert!(matches!(
+            convert_polyline("", &Options::default()),
+            Err(Error::EmptyRoute)
+        ));
+
+        let options = Options {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let conversion = convert_polyline("", &options).unwrap();
+        assert_eq!(conversion.stats.points, 0);
+    }
+
+    #[test]
+    fn test_max_input_size() {
+        let options = Options {
+            max_input_size: Some(10),
+            ..Default::default()
+        };
+        let input = json!({"trails": [{"defaultMap": {"routes": [{"lineSegments": []}]}}]});
+        assert!(matches!(
+            convert(input.to_string().as_bytes(), &options),
+            Err(Error::InputTooLarge { limit: 10 })
+        ));
+        assert_eq!(read_input(&b"0123456789"[..], Some(10)).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_min_points() {
+        // A single point, as left by a truncated polyline.
+        let polyline = "_p~iF~ps|U";
+        assert!(matches!(
+            convert_polyline(polyline, &Options::default()),
+            Err(Error::TooFewPoints { points: 1, min: 2 })
+        ));
+
+        let options = Options {
+            min_points: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert_polyline(polyline, &options).unwrap().stats.points,
+            1
+        );
+
+        let options = Options {
+            min_points: 3,
+            ..Default::default()
+        };
+        assert!(matches!(
+            convert_polyline("_p~iF~ps|U_ulLnnqC", &options),
+            Err(Error::TooFewPoints { points: 2, min: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_fallback_name() {
+        let input = json!({
+            "trails": [{
+                "id": 10_236_086,
+                "defaultMap": {
+                    "routes": [{
+                        "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }]
+                    }]
+                }
+            }]
+        })
+        .to_string();
+        let conversion = convert(input.as_bytes(), &Options::default()).unwrap();
+        assert_eq!(conversion.name, "AllTrails trail 10236086");
+        assert_eq!(conversion.warnings.len(), 1);
+
+        let options = Options {
+            fallback_name: input_stem("saved/abbots-leigh.json").map(str::to_string),
+            ..Default::default()
+        };
+        let conversion = convert(input.as_bytes(), &options).unwrap();
+        assert_eq!(conversion.name, "abbots-leigh");
+
+        let options = Options {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            convert(input.as_bytes(), &options),
+            Err(Error::RouteNameNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_ascii_names() {
+        let options = Options {
+            name: Some("Sentier du Lac Léman".to_string()),
+            ascii_names: true,
+            ..Default::default()
+        };
+        let conversion = convert_polyline("_p~iF~ps|U_ulLnnqC", &options).unwrap();
+        assert_eq!(conversion.name, "Sentier du Lac Leman");
+    }
+
+    #[test]
+    fn test_synthetic_times() {
+        let mut options = Options {
+            deterministic: true,
+            ..Default::default()
+        };
+        Preset::Apple.apply(&mut options);
+        let coords = vec![
+            Coord { x: -2.6, y: 51.45 },
+            Coord { x: -2.61, y: 51.46 },
+            Coord { x: -2.62, y: 51.46 },
+        ];
+        let polyline = encode_coordinates(coords, POLYLINE_PRECISION).unwrap();
+        let conversion = convert_polyline(&polyline, &options).unwrap();
+        let points = &conversion.track.segments[0].points;
+        assert!(points.iter().all(|point| point.time.is_some()));
+        assert_eq!(
+            first_time(&conversion.track),
+            Some(Time::from(OffsetDateTime::UNIX_EPOCH))
+        );
+
+        let start = OffsetDateTime::from_unix_timestamp(1_714_550_400).unwrap();
+        options.start_time = Some(start);
+        let conversion = convert_polyline(&polyline, &options).unwrap();
+        assert_eq!(first_time(&conversion.track), Some(Time::from(start)));
+    }
+
+    #[test]
+    fn test_implausible_route() {
+        // A hike near Bristol whose last point is in New York.
+        let coords = vec![
+            Coord { x: -2.6, y: 51.45 },
+            Coord { x: -2.61, y: 51.46 },
+            Coord { x: -74.0, y: 40.7 },
+        ];
+        let polyline = encode_coordinates(coords, POLYLINE_PRECISION).unwrap();
+        assert!(matches!(
+            convert_polyline(&polyline, &Options::default()),
+            Err(Error::ImplausibleRoute(_))
+        ));
+
+        let options = Options {
+            lenient: true,
+            ..Default::default()
+        };
+        let conversion = convert_polyline(&polyline, &options).unwrap();
+        assert_eq!(conversion.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_detect_route_type() {
+        let square = vec![
+            Coord { x: 0.0, y: 0.0 },
+            Coord { x: 0.01, y: 0.0 },
+            Coord { x: 0.01, y: 0.01 },
+            Coord { x: 0.0, y: 0.0 },
+        ];
+        let polyline = encode_coordinates(square, POLYLINE_PRECISION).unwrap();
+        let options = Options {
+            detect_route_type: true,
+            ..Default::default()
+        };
+        let conversion = convert_polyline(&polyline, &options).unwrap();
+        assert_eq!(conversion.stats.shape, Some(shape::RouteShape::Loop));
+        let route_type = conversion.trail_stats.and_then(|stats| stats.route_type);
+        assert_eq!(route_type.as_deref(), Some("Loop"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let coords: Vec<Coord> = (0..3)
+            .map(|i| Coord {
+                x: -2.6 + f64::from(i) * 0.01,
+                y: 51.45,
+            })
+            .collect();
+        let polyline = encode_coordinates(coords, POLYLINE_PRECISION).unwrap();
+        let options = Options {
+            round_trip: true,
+            merge_segments: true,
+            ..Default::default()
+        };
+        let conversion = convert_polyline(&polyline, &options).unwrap();
+        assert_eq!(conversion.track.segments.len(), 1);
+        assert_eq!(conversion.stats.points, 5);
+        assert_eq!(conversion.stats.shape, Some(shape::RouteShape::OutAndBack));
+    }
+
+    #[test]
+    fn test_gpx_input_keeps_heart_rate() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="watch" xmlns="http://www.topografix.com/GPX/1/1"
+  xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <trk>
+    <name>Run</name>
+    <trkseg>
+      <trkpt lat="51.45" lon="-2.6">
+        <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>128</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>
+      </trkpt>
+      <trkpt lat="51.46" lon="-2.61"/>
+    </trkseg>
+  </trk>
+</gpx>"#;
+
+        let mut output = Vec::new();
+        run_with_options(input.as_bytes(), &mut output, &Options::default()).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(TRACK_POINT_EXTENSION_NAMESPACE));
+        assert_eq!(output.matches("<gpxtpx:hr>128</gpxtpx:hr>").count(), 1);
+        assert_gpx_basics(&gpx::read(output.as_bytes()).unwrap(), "Run", 2);
+    }
+
+    #[test]
+    fn test_gpx_input_readings_stay_with_their_points() {
+        // A loop back to the start, where the readings differ from the first time round.
+        let point = |lat, elevation, hr| {
+            format!(
+                r#"<trkpt lat="{lat}" lon="-2.6"><ele>{elevation}</ele><extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>{hr}</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions></trkpt>"#
+            )
+        };
+        let input = format!(
+            r#"<gpx version="1.1" creator="watch" xmlns="http://www.topografix.com/GPX/1/1"
+  xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <trk><name>Loop</name><trkseg>{}{}{}</trkseg></trk>
+</gpx>"#,
+            point("51.45", 10.0, 120),
+            point("51.46", 20.0, 130),
+            point("51.45", 12.0, 150),
+        );
+        let options = Options {
+            coord_decimals: Some(3),
+            ..Default::default()
+        };
+
+        let conversion = convert(input.as_bytes(), &options).unwrap();
+        let points = &conversion.track.segments[0].points;
+        assert_eq!(points[0].elevation, Some(10.0));
+        assert_eq!(points[2].elevation, Some(12.0));
+        let parts = conversion.split(Some(2), 1);
+        assert_eq!(parts[1].sensors[0][1].heart_rate, Some(150));
+
+        let mut output = Vec::new();
+        write_conversion(&conversion, Format::Gpx, &options, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let first = output.find("<gpxtpx:hr>120</gpxtpx:hr>").unwrap();
+        let last = output.find("<gpxtpx:hr>150</gpxtpx:hr>").unwrap();
+        assert!(first < last);
+    }
+
+    #[test]
+    fn test_convert_polyline() {
+        let options = Options {
+            name: Some("Bare".to_string()),
+            ..Default::default()
+        };
+        let conversion = convert_polyline(" _p~iF~ps|U_ulLnnqC ", &options).unwrap();
+        assert_eq!(conversion.name, "Bare");
+        assert_eq!(
+            conversion.track.segments[0].points[1].point().0,
+            Coord {
+                x: -120.95,
+                y: 40.7
+            }
+        );
+
+        assert!(matches!(
+            convert_polyline("_p~iF~ps|U_", &options),
+            Err(Error::InvalidPolylineArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_coord_decimals() {
+        let options = Options {
+            input_format: Some(InputFormat::Csv),
+            coord_decimals: Some(2),
+            ..Default::default()
+        };
+        let input = "51.4512,-2.6049\n51.4498,-2.6039\n51.4601,-2.6149\n";
+        let conversion = convert(input.as_bytes(), &options).unwrap();
+
+        let points = &conversion.track.segments[0].points;
+        assert_eq!(
+            points.len(),
+            2,
+            "the first two points round to the same place"
+        );
+        assert_eq!(points[0].point().0, Coord { x: -2.6, y: 51.45 });
+        assert_eq!(points[1].point().0, Coord { x: -2.61, y: 51.46 });
+    }
+
+    #[test]
+    fn test_geojson_input() {
+        let input = json!({
+            "type": "Feature",
+            "properties": { "name": "Ridge" },
+            "geometry": { "type": "LineString", "coordinates": [[-2.6, 51.45], [-2.61, 51.46]] }
+        });
+
+        let gpx = run_and_parse_gpx(&input.to_string());
+        assert_gpx_basics(&gpx, "Ridge", 2);
+    }
+
+    #[test]
+    fn test_waypoint_symbols() {
+        let json = json!({
+            "trails": [
+                {
+                    "name": "Waypoint Trail",
+                    "defaultMap": {
+                        "routes": [
+                            {
+                                "lineSegments": [
+                                    { "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }
+                                ]
+                            }
+                        ],
+                        "waypoints": [
+                            {
+                                "name": "Main car park",
+                                "type": "parking",
+                                "location": { "latitude": 38.5, "longitude": -120.2 }
+                            }
+                        ]
+                    }
+                }
+            ]
+        });
+
+        let gpx = run_and_parse_gpx(&json.to_string());
+        assert_eq!(gpx.waypoints.len(), 1);
+        assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Main car park"));
+        assert_eq!(gpx.waypoints[0].symbol.as_deref(), Some("Parking Area"));
+    }
+
+    #[test]
+    fn test_waypoints_only() {
+        let json = json!({
+            "maps": [{
+                "name": "Car parks",
+                "waypoints": [
+                    { "name": "Main car park", "location": { "latitude": 51.45, "longitude": -2.6 } }
+                ]
+            }]
+        });
+        let options = Options {
+            waypoints_only: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        run_with_options(json.to_string().as_bytes(), &mut output, &options).unwrap();
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_eq!(gpx.waypoints.len(), 1);
+        assert!(gpx.tracks.is_empty());
+
+        let json = json!({ "maps": [{ "name": "Nothing", "waypoints": [] }] });
+        assert!(matches!(
+            convert(json.to_string().as_bytes(), &options),
+            Err(Error::NoWaypoints)
+        ));
+    }
+
+    #[test]
+    fn test_prefer_format() {
+        let routes =
+            json!([{ "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }] }]);
+        let input = json!({
+            "trails": [{ "name": "Offline", "defaultMap": { "routes": routes } }],
+            "maps": [{ "name": "Deep", "routes": routes }]
+        })
+        .to_string();
+
+        let conversion = convert(input.as_bytes(), &Options::default()).unwrap();
+        assert_eq!(conversion.name, "Offline");
+        assert_eq!(
+            conversion.warnings,
+            [Warning::BothLayouts {
+                read: Layout::Offline
+            }]
+        );
+
+        let options = Options {
+            prefer_format: Some(Layout::Deep),
+            ..Default::default()
+        };
+        let conversion = convert(input.as_bytes(), &options).unwrap();
+        assert_eq!(conversion.name, "Deep");
+        assert!(conversion.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_trail_index() {
+        let trail = |name: &str| {
+            json!({
+                "name": name,
+                "defaultMap": {
+                    "routes": [
+                        { "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }] }
+                    ]
+                }
+            })
+        };
+        let input = json!({ "trails": [trail("First"), trail("Second")] }).to_string();
+
+        let options = Options {
+            trail_index: 1,
+            ..Default::default()
+        };
+        let conversion = run_with_options(input.as_bytes(), std::io::sink(), &options).unwrap();
+        assert_eq!(conversion.name, "Second");
+
+        let options = Options {
+            trail_index: 2,
+            ..Default::default()
+        };
+        assert!(matches!(
+            run_with_options(input.as_bytes(), std::io::sink(), &options),
+            Err(Error::NoSuchTrail { index: 2, count: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_provenance() {
+        let json = json!({
+            "trails": [{
+                "id": 10_236_086,
+                "name": "Traced",
+                "slug": "trail/england/bristol/abbots",
+                "defaultMap": {
+                    "routes": [{
+                        "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }]
+                    }]
+                }
+            }]
+        });
+        let options = Options {
+            provenance: true,
+            deterministic: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        run_with_options(json.to_string().as_bytes(), &mut output, &options).unwrap();
+
+        let gpx = String::from_utf8(output).unwrap();
+        assert!(gpx.contains(&format!("xmlns:alltrailsgpx=\"{PROVENANCE_NAMESPACE}\"")));
+        assert!(gpx.contains("<alltrailsgpx:trailId>10236086</alltrailsgpx:trailId>"));
+        assert!(gpx.contains(
+            "<alltrailsgpx:link>https://www.alltrails.com/trail/england/bristol/abbots</alltrailsgpx:link>"
+        ));
+        assert!(!gpx.contains("<alltrailsgpx:converted>"));
+        assert_gpx_basics(&gpx::read(gpx.as_bytes()).unwrap(), "Traced", 2);
+    }
+
+    #[test]
+    fn test_track_color() {
+        let options = Options {
+            color: Some(TrackColor::Fixed(Rgb(0xe6, 0x19, 0x4b))),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let conversion = convert_polyline("_p~iF~ps|U_ulLnnqC", &options).unwrap();
+        write_conversion(&conversion, Format::Gpx, &options, &mut output).unwrap();
+
+        let gpx = String::from_utf8(output).unwrap();
+        assert!(gpx.contains("xmlns:osmand=\"https://osmand.net\""));
+        assert!(gpx.contains("<osmand:color>#e6194b</osmand:color>"));
+        assert!(gpx.contains("<gpxx:DisplayColor>Red</gpxx:DisplayColor>"));
+        gpx::read(gpx.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_write_estimate() {
+        let options = Options {
+            write_estimate: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let conversion = convert_polyline("_p~iF~ps|U_ulLnnqC", &options).unwrap();
+        write_conversion(&conversion, Format::Gpx, &options, &mut output).unwrap();
+
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        let description = gpx.metadata.unwrap().description.unwrap();
+        let expected = format_minutes(conversion.stats.estimated_minutes);
+        assert_eq!(description, format!("Naismith's rule: {expected}"));
+    }
+
+    #[test]
+    fn test_validate_gpx() {
+        let options = Options {
+            write_estimate: true,
+            validate_gpx: true,
+            author: Some("Bristol Ramblers".to_string()),
+            publisher_link: Some("https://example.org/walks".to_string()),
+            ..Default::default()
+        };
+        let conversion = convert_polyline("_p~iF~ps|U_ulLnnqC", &options).unwrap();
+        let mut output = Vec::new();
+        write_conversion(&conversion, Format::Gpx, &options, &mut output).unwrap();
+        assert!(gpx::read(output.as_slice()).is_ok());
+
+        let mut merged = Vec::new();
+        write_merged_gpx(
+            &[conversion.clone(), conversion.clone()],
+            &options,
+            &mut merged,
+        )
+        .unwrap();
+        assert!(schema::validate_gpx(&String::from_utf8(merged).unwrap()).is_ok());
+
+        // Nothing's written when the document doesn't match.
+        let mut invalid = conversion;
+        invalid
+            .waypoints
+            .push(Waypoint::new(geo_types::Point::new(-2.6, 91.0)));
+        let mut output = Vec::new();
+        assert!(matches!(
+            write_conversion(&invalid, Format::Gpx, &options, &mut output),
+            Err(Error::InvalidGpx(_))
+        ));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_author_and_link() {
+        let options = Options {
+            author: Some("Bristol Ramblers".to_string()),
+            publisher_link: Some("https://example.org/walks".to_string()),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let conversion = convert_polyline("_p~iF~ps|U_ulLnnqC", &options).unwrap();
+        write_conversion(&conversion, Format::Gpx, &options, &mut output).unwrap();
+
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        let metadata = gpx.metadata.unwrap();
+        let author = metadata.author.unwrap();
+        assert_eq!(author.name.as_deref(), Some("Bristol Ramblers"));
+        assert_eq!(metadata.links[0].href, "https://example.org/walks");
+    }
+
+    #[test]
+    fn test_route_id() {
+        let route = |id: Value, polyline: &str| json!({ "id": id, "lineSegments": [{ "polyline": { "pointsData": polyline } }] });
+        let coords: Vec<Coord> = (0..3)
+            .map(|i| Coord {
+                x: -2.6,
+                y: 51.45 + f64::from(i) * 0.001,
+            })
+            .collect();
+        let three_points = encode_coordinates(coords, POLYLINE_PRECISION).unwrap();
+        let input = json!({
+            "maps": [{
+                "name": "Two Routes",
+                "routes": [
+                    route(json!(41), "_p~iF~ps|U_ulLnnqC"),
+                    route(json!("42"), &three_points),
+                ]
+            }]
+        })
+        .to_string();
+
+        let options = Options {
+            route_id: Some(42),
+            ..Default::default()
+        };
+        let conversion = run_with_options(input.as_bytes(), std::io::sink(), &options).unwrap();
+        assert_eq!(conversion.stats.points, 3);
+
+        let options = Options {
+            route_id: Some(43),
+            ..Default::default()
+        };
+        assert!(matches!(
+            run_with_options(input.as_bytes(), std::io::sink(), &options),
+            Err(Error::NoSuchRoute { id: 43 })
+        ));
+
+        let conversions = convert_routes(input.as_bytes(), &Options::default()).unwrap();
+        let summary: Vec<_> = conversions
+            .iter()
+            .map(|c| (c.track.name.as_deref().unwrap(), c.stats.points))
+            .collect();
+        assert_eq!(summary, [("Two Routes (1/2)", 2), ("Two Routes (2/2)", 3)]);
+    }
+
+    #[test]
+    fn test_convert_trails() {
+        let map = json!({ "routes": [{ "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }] }] });
+        let input = json!({
+            "trails": [
+                {
+                    "id": 1,
+                    "name": "Main",
+                    "defaultMap": map,
+                    "bundledTrails": [
+                        { "id": 2, "name": "Bundled", "defaultMap": map },
+                        { "id": 3, "name": "No Route" }
+                    ]
+                }
+            ]
+        })
+        .to_string();
+        let options = Options {
+            name: Some("Renamed".to_string()),
+            include_bundled: true,
+            ..Default::default()
+        };
+        let conversions = convert_trails(input.as_bytes(), &options).unwrap();
+        let names: Vec<_> = conversions.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Renamed", "Bundled"]);
+        assert!(matches!(
+            &conversions[0].warnings[0],
+            Warning::BundledSkipped { name, .. } if name == "No Route"
+        ));
+
+        let conversions = convert_trails(input.as_bytes(), &Options::default()).unwrap();
+        assert_eq!(conversions.len(), 1);
+    }
+
+    #[test]
+    fn test_list_trails() {
+        let input = json!({
+            "trails": [
+                { "id": 10236086, "name": "Abbots Leigh Circular" },
+                { "name": "Ashton Court" }
+            ]
+        })
+        .to_string();
+        let trails = list_trails(input.as_bytes()).unwrap();
+        assert_eq!(trails.len(), 2);
+        assert_eq!(
+            trails[0].to_string(),
+            "0: Abbots Leigh Circular (ID 10236086)"
+        );
+        assert_eq!(trails[1].to_string(), "1: Ashton Court");
+    }
+
+    #[test]
+    fn test_merge() {
+        let options = Options {
+            start_number: 5,
+            ..Default::default()
+        };
+        let conversion = convert_polyline("_p~iF~ps|U_ulLnnqC", &options).unwrap();
+        let conversions = [conversion.clone(), conversion];
+        let mut output = Vec::new();
+        write_merged_gpx(&conversions, &options, &mut output).unwrap();
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_eq!(gpx.tracks.len(), 2);
+        assert_eq!(gpx.tracks[0].number, Some(5));
+        assert_eq!(gpx.tracks[1].number, Some(6));
+
+        let mut output = Vec::new();
+        write_merged_gpx(&conversions[..1], &options, &mut output).unwrap();
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_eq!(gpx.tracks[0].number, None);
+    }
+
+    #[test]
+    fn test_overview_in_track_description() {
+        let json = json!({
+            "trails": [
+                {
+                    "name": "Overview Trail",
+                    "overview": "<p>A short   loop past the lake.</p>",
+                    "defaultMap": {
+                        "routes": [
+                            {
+                                "lineSegments": [
+                                    { "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }
+                                ]
+                            }
+                        ]
+                    }
+                }
+            ]
+        });
+
+        let gpx = run_and_parse_gpx(&json.to_string());
+        assert_eq!(
+            gpx.tracks[0].description.as_deref(),
+            Some("A short loop past the lake.")
+        );
+    }
+
+    #[test]
+    fn test_multiple_line_segments() {
+        let first = vec![Coord { x: -2.6, y: 51.4 }, Coord { x: -2.61, y: 51.41 }];
+        let second = vec![Coord { x: -2.61, y: 51.41 }, Coord { x: -2.62, y: 51.4 }];
+        let segment = |coords: &[Coord]| {
+            json!({
+                "polyline": {
+                    "pointsData": encode_coordinates(coords.to_vec(), POLYLINE_PRECISION).unwrap()
+                }
+            })
+        };
+        let json = json!({
+            "maps": [
+                {
+                    "name": "Two Parts",
+                    "routes": [{ "lineSegments": [segment(&first), segment(&second)] }]
+                }
+            ]
+        });
+
+        let gpx = run_and_parse_gpx(&json.to_string());
+        let segments = &gpx.tracks[0].segments;
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].points.len(), 2);
+
+        let options = Options {
+            merge_segments: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        run_with_options(json.to_string().as_bytes(), &mut output, &options).unwrap();
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_eq!(gpx.tracks[0].segments.len(), 1);
+        assert_eq!(gpx.tracks[0].segments[0].points.len(), 3);
+    }
+
+    #[test]
+    fn test_registered_extractor() {
+        let input = json!({ "walk": { "title": "Private", "line": "_p~iF~ps|U_ulLnnqC" } });
+        assert!(convert(input.to_string().as_bytes(), &Options::default()).is_err());
+
+        let mut options = Options::default();
+        options.extractors.register_pointers(["/walk/line"]);
+        let conversion = convert(input.to_string().as_bytes(), &options).unwrap();
+        assert_eq!(conversion.stats.points, 2);
+        assert_eq!(conversion.sources[0].as_str(), "/walk/line");
+    }
+
+    #[test]
+    fn test_decode_points() {
+        let polyline = Polyline {
+            data: "_p~iF~ps|U_ulLnnqC_mqNvxq`@",
+            pointer: JsonPointer::new("/polyline"),
+        };
+        let north_of_40: Vec<_> = decode_points(&polyline, POLYLINE_PRECISION)
+            .map(Result::unwrap)
+            .filter(|coord| coord.y > 40.0)
+            .collect();
+        assert_eq!(north_of_40.len(), 2);
+
+        let truncated = Polyline {
+            data: "_p~iF~ps|U_ulL",
+            pointer: JsonPointer::new("/polyline"),
+        };
+        let mut points = decode_points(&truncated, POLYLINE_PRECISION);
+        assert!(points.next().unwrap().is_ok());
+        assert!(matches!(
+            points.next(),
+            Some(Err(Error::InvalidPolyline { pointer, .. })) if pointer.as_str() == "/polyline"
+        ));
+        assert!(points.next().is_none());
+    }
+
+    #[test]
+    fn test_run_streaming() {
+        let first = vec![Coord { x: -2.6, y: 51.4 }, Coord { x: -2.61, y: 51.41 }];
+        let second = vec![Coord { x: -2.61, y: 51.41 }, Coord { x: -2.62, y: 51.4 }];
+        let encode =
+            |coords: &[Coord]| encode_coordinates(coords.to_vec(), POLYLINE_PRECISION).unwrap();
+        let input = json!({
+            "trails": [{
+                "name": "Streamed",
+                "defaultMap": {
+                    "routes": [{
+                        "lineSegments": [
+                            { "polylines": [{ "pointsData": encode(&first) }, { "pointsData": encode(&second) }] },
+                            { "polyline": { "pointsData": encode(&second) } }
+                        ]
+                    }]
+                }
+            }]
+        })
+        .to_string();
+
+        let mut output = Vec::new();
+        let points = run_streaming(input.as_bytes(), &mut output, &Options::default()).unwrap();
+        let streamed = gpx::read(output.as_slice()).unwrap();
+        let converted = convert(input.as_bytes(), &Options::default()).unwrap();
+
+        assert_eq!(points, converted.stats.points);
+        assert_eq!(streamed.tracks[0].name.as_deref(), Some("Streamed"));
+        let coords = |segments: &[TrackSegment]| -> Vec<Vec<Coord>> {
+            segments
+                .iter()
+                .map(|segment| segment.points.iter().map(|p| p.point().0).collect())
+                .collect()
+        };
+        assert_eq!(
+            coords(&streamed.tracks[0].segments),
+            coords(&converted.track.segments)
+        );
+
+        // Nothing's written for routes convert would refuse, or that would give away the ends.
+        let options = Options {
+            min_points: 10,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        assert!(matches!(
+            run_streaming(input.as_bytes(), &mut output, &options),
+            Err(Error::TooFewPoints { points: 5, min: 10 })
+        ));
+        let options = Options {
+            trim_ends: Some(200.0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            run_streaming(input.as_bytes(), &mut output, &options),
+            Err(Error::StreamCantHide("--trim-ends"))
+        ));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_parallel_decode_keeps_order() {
+        // Enough polylines to be decoded in parallel, each a single distinct point.
+        let coords: Vec<Coord> = (0..30_000)
+            .map(|i| Coord {
+                x: -2.6,
+                y: 51.0 + f64::from(i) * 1e-5,
+            })
+            .collect();
+        let encoded: Vec<String> = coords
+            .iter()
+            .map(|&coord| encode_coordinates([coord], POLYLINE_PRECISION).unwrap())
+            .collect();
+        let polylines: Vec<Polyline> = encoded
+            .iter()
+            .enumerate()
+            .map(|(i, data)| Polyline {
+                data,
+                pointer: JsonPointer::new("/polylines").join(i),
+            })
+            .collect();
+        let polylines: Vec<&Polyline> = polylines.iter().collect();
+        assert!(encoded.iter().map(String::len).sum::<usize>() >= PARALLEL_DECODE_MIN_BYTES);
+
+        let decoded = decode_polylines(&polylines).unwrap();
+        for (line_string, coord) in decoded.iter().zip(&coords) {
+            assert!((line_string.0[0].y - coord.y).abs() < 1e-6);
+        }
+
+        let bad = Polyline {
+            data: "_p~iF ps|U",
+            pointer: JsonPointer::new("/bad"),
+        };
+        let mut with_bad = polylines.clone();
+        with_bad[12_345] = &bad;
+        assert!(matches!(
+            decode_polylines(&with_bad),
+            Err(Error::InvalidPolyline { pointer, .. }) if pointer.as_str() == "/bad"
+        ));
+    }
+
+    #[test]
+    fn test_parallel_routes_keep_order() {
+        // More routes and bundled trails than there are likely to be cores, each with one
+        // more point than the last.
+        let map = |points: usize| {
+            let coords: Vec<Coord> = (0..points)
+                .map(|i| Coord {
+                    x: -2.6 + i as f64 * 0.001,
+                    y: 51.45,
+                })
+                .collect();
+            let polyline = encode_coordinates(coords, POLYLINE_PRECISION).unwrap();
+            json!({ "lineSegments": [{ "polyline": { "pointsData": polyline } }] })
+        };
+        let routes: Vec<Value> = (2..50).map(map).collect();
+        let bundled: Vec<Value> = (2..50)
+            .map(|points| {
+                json!({
+                    "id": points,
+                    "name": format!("Bundled {points}"),
+                    "defaultMap": { "routes": [map(points)] }
+                })
+            })
+            .collect();
+        let input = json!({
+            "trails": [{
+                "id": 1,
+                "name": "Many Routes",
+                "defaultMap": { "routes": routes },
+                "bundledTrails": bundled
+            }]
+        })
+        .to_string();
+
+        let conversions = convert_routes(input.as_bytes(), &Options::default()).unwrap();
+        let points: Vec<usize> = conversions.iter().map(|c| c.stats.points).collect();
+        assert_eq!(points, (2..50).collect::<Vec<_>>());
+        assert_eq!(
+            conversions[47].track.name.as_deref(),
+            Some("Many Routes (48/48)")
+        );
+
+        let options = Options {
+            include_bundled: true,
+            ..Default::default()
+        };
+        let conversions = convert_trails(input.as_bytes(), &options).unwrap();
+        let names: Vec<&str> = conversions[1..].iter().map(|c| c.name.as_str()).collect();
+        let expected: Vec<String> = (2..50).map(|points| format!("Bundled {points}")).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn test_segment_split_across_polylines() {
+        let first = vec![Coord { x: -2.6, y: 51.4 }, Coord { x: -2.61, y: 51.41 }];
+        let second = vec![Coord { x: -2.61, y: 51.41 }, Coord { x: -2.62, y: 51.4 }];
+        let piece = |coords: &[Coord]| json!({ "pointsData": encode_coordinates(coords.to_vec(), POLYLINE_PRECISION).unwrap() });
+        let json = json!({
+            "maps": [
+                {
+                    "name": "In Pieces",
+                    "routes": [
+                        { "lineSegments": [{ "polylines": [piece(&first), piece(&second)] }] }
+                    ]
+                }
+            ]
+        });
+
+        let gpx = run_and_parse_gpx(&json.to_string());
+        assert_gpx_basics(&gpx, "In Pieces", 3);
+    }
+
+    #[test]
+    fn test_polyline_field_aliases() {
+        for segment in [
+            json!({ "polyline": { "encoded": "_p~iF~ps|U_ulLnnqC" } }),
+            json!({ "geometry": { "polyline": "_p~iF~ps|U_ulLnnqC" } }),
+        ] {
+            let json = json!({
+                "maps": [{ "name": "Aliased", "routes": [{ "lineSegments": [segment] }] }]
+            });
+            let gpx = run_and_parse_gpx(&json.to_string());
+            assert_gpx_basics(&gpx, "Aliased", 2);
+        }
+    }
+
+    #[test]
+    fn test_routes_geojson_fallback() {
+        let geojson = json!({
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": [[-120.2, 38.5, 1200.0], [-120.95, 40.7, 1250.0]]
+                }
+            }]
+        });
+        // Seen both as an object and as a string holding one.
+        for routes_geojson in [geojson.clone(), json!(geojson.to_string())] {
+            let json = json!({
+                "trails": [{
+                    "name": "GeoJSON Route",
+                    "defaultMap": { "routesGeojson": routes_geojson }
+                }]
+            });
+            let gpx = run_and_parse_gpx(&json.to_string());
+            assert_gpx_basics(&gpx, "GeoJSON Route", 2);
+            let point = &gpx.tracks[0].segments[0].points[1];
+            assert_eq!(point.elevation, Some(1250.0));
+        }
+    }
+
+    #[test]
+    fn test_graphql_response() {
+        let json = json!({
+            "data": {
+                "trail": {
+                    "name": "GraphQL Route",
+                    "defaultMap": {
+                        "routes": {
+                            "edges": [{
+                                "node": {
+                                    "lineSegments": [
+                                        { "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }
+                                    ]
+                                }
+                            }]
+                        }
+                    }
+                }
+            }
+        });
+        let gpx = run_and_parse_gpx(&json.to_string());
+        assert_gpx_basics(&gpx, "GraphQL Route", 2);
+    }
+
+    #[test]
+    fn test_trail_detail_response() {
+        let json = json!({
+            "trail_detail": {
+                "trail": {
+                    "id": 10_236_086,
+                    "name": "Bristol and Abbots Leigh Circular",
+                    "overview": "A loop through the woods.",
+                    "geo_stats": { "length": 9817.4, "elevation_gain": 213.4 },
+                    "map": {
+                        "routes": [{
+                            "line_segments": [
+                                { "polyline": { "points_data": "_p~iF~ps|U_ulLnnqC" } }
+                            ]
+                        }]
+                    }
+                }
+            }
+        });
+        let mut output = Vec::new();
+        let conversion = run_with_options(
+            json.to_string().as_bytes(),
+            &mut output,
+            &Options::default(),
+        )
+        .unwrap();
+        assert_eq!(conversion.trail_id, Some(10_236_086));
+
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_gpx_basics(&gpx, "Bristol and Abbots Leigh Circular", 2);
+    }
+
+    #[test]
+    fn test_synced_trails_payload() {
+        let synced = |name: &str| {
+            json!({
+                "trail": { "name": name },
+                "map": {
+                    "routes": [{
+                        "lineSegments": [
+                            { "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }
+                        ]
+                    }]
+                }
+            })
+        };
+        let json = json!({ "syncedTrails": [synced("First"), synced("Second")] });
+
+        let options = Options {
+            trail_index: 1,
+            ..Options::default()
+        };
+        let conversion = convert(json.to_string().as_bytes(), &options).unwrap();
+        assert_eq!(conversion.name, "Second");
+
+        let listings = list_trails(json.to_string().as_bytes()).unwrap();
+        assert_eq!(listings.len(), 2);
+    }
+
+    #[test]
+    fn test_swapped_axes_are_corrected() {
+        // Sydney, encoded with latitude and longitude the wrong way around. The polyline crate
+        // refuses to encode out of range latitudes, so this is written out by hand.
+        let polyline = "_gzy[~gtmEo}@n}@";
+        let json = json!({
+            "maps": [
+                {
+                    "name": "Harbour Walk",
+                    "routes": [{ "lineSegments": [{ "polyline": { "pointsData": polyline } }] }]
+                }
+            ]
+        });
+
+        let mut output = Vec::new();
+        let conversion = run_with_options(
+            json.to_string().as_bytes(),
+            &mut output,
+            &Options::default(),
+        )
+        .unwrap();
+        assert!(conversion.swapped_latlon);
+
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        let point = gpx.tracks[0].segments[0].points[0].point();
+        assert!((point.x() - 151.2).abs() < 1e-6 && (point.y() + 33.86).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_missing_polyline_explains_divergence() {
+        let json = json!({ "trails": [{ "name": "No Routes", "defaultMap": {} }] });
+
+        let err = run(json.to_string().as_bytes(), Vec::new()).unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("found /trails/0/defaultMap but no \"routes\""),
+            "{message}"
+        );
+        assert!(
+            message.contains("the document has no \"maps\""),
+            "{message}"
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_run_async() {
+        let json = json!({
+            "maps": [
+                {
+                    "name": "Async Trail",
+                    "routes": [
+                        { "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }] }
+                    ]
+                }
+            ]
+        })
+        .to_string();
+
+        let mut output = Vec::new();
+        let conversion = run_async(json.as_bytes(), &mut output, &Options::default())
+            .await
+            .unwrap();
+        assert_eq!(conversion.name, "Async Trail");
+        assert_eq!(conversion.stats.points, 2);
+
+        let gpx = gpx::read(output.as_slice()).unwrap();
+        assert_eq!(gpx.tracks[0].name.as_deref(), Some("Async Trail"));
+    }
+
+    #[test]
+    fn test_decode_error_has_position() {
+        let json = json!({
+            "maps": [
+                {
+                    "name": "Broken",
+                    "routes": [
+                        {
+                            "lineSegments": [
+                                { "polyline": { "pointsData": "_p~iF~ps|U" } },
+                                { "polyline": { "pointsData": "_p~iF~ps|U_ulL" } }
+                            ]
+                        }
+                    ]
+                }
+            ]
+        });
+
+        match run(json.to_string().as_bytes(), Vec::new()) {
+            Err(Error::InvalidPolyline { pointer, source }) => {
+                assert_eq!(
+                    pointer.as_str(),
+                    "/maps/0/routes/0/lineSegments/1/polyline/pointsData"
+                );
+                assert_eq!(
+                    (pointer.trail_index(), pointer.route_index()),
+                    (Some(0), Some(0))
+                );
+                assert_eq!(pointer.segment_index(), Some(1));
+                assert_eq!(source.offset, 14);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_owned_polyline_and_name() {
+        let json = json!({
+            "maps": [
+                {
+                    "name": "Owned Trail",
+                    "routes": [
+                        { "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }] }
+                    ]
+                }
+            ]
+        });
+
+        let polyline = PolylineBuf::from(extract_polyline(&json).unwrap());
+        let name = RouteNameBuf::from(extract_route_name(&json).unwrap());
+        drop(json);
+
+        let track = std::thread::spawn(move || {
+            let line_string = polyline.as_polyline().decode().unwrap();
+            create_gpx(line_string, (&name).into())
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(track.name.as_deref(), Some("Owned Trail"));
+        assert_eq!(track.segments[0].points.len(), 2);
+    }
+
     fn run_and_parse_gpx(json_input: &str) -> Gpx {
         let mut output_buffer: Vec<u8> = Vec::new();
         run(json_input.as_bytes(), &mut output_buffer).unwrap_or_else(|e| {
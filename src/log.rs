@@ -0,0 +1,132 @@
+//! What gets printed to stderr, either as plain messages or as one JSON object per line for
+//! log pipelines.
+
+use crate::Conversion;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum LogFormat {
+    /// Plain messages, for people.
+    #[default]
+    Text,
+
+    /// One JSON object per line, with the level, message, and the file, trail ID and
+    /// conversion duration where they're known.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// A single log message and what it's about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record<'a> {
+    pub level: Level,
+    pub message: String,
+    /// Where the route was written.
+    pub file: Option<&'a str>,
+    pub trail_id: Option<u64>,
+    pub duration: Option<Duration>,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(level: Level, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            file: None,
+            trail_id: None,
+            duration: None,
+        }
+    }
+
+    /// Attaches the details of a conversion written to `file`.
+    pub fn about(mut self, file: &'a str, conversion: &Conversion) -> Self {
+        self.file = Some(file);
+        self.trail_id = conversion.trail_id;
+        self.duration = Some(conversion.elapsed);
+        self
+    }
+
+    /// The record as a line of the given format, without the newline. Text lines are just
+    /// the message, as details like the file are already part of it where they matter.
+    pub fn to_line(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text => self.message.clone(),
+            LogFormat::Json => {
+                let mut line = json!({
+                    "level": self.level.as_str(),
+                    "message": self.message,
+                });
+                // Missing details are left out rather than null, like most structured loggers.
+                if let Value::Object(fields) = &mut line {
+                    if let Some(file) = self.file {
+                        fields.insert("file".to_string(), json!(file));
+                    }
+                    if let Some(trail_id) = self.trail_id {
+                        fields.insert("trail_id".to_string(), json!(trail_id));
+                    }
+                    if let Some(duration) = self.duration {
+                        let ms = duration.as_secs_f64() * 1000.0;
+                        fields.insert("duration_ms".to_string(), json!(ms));
+                    }
+                }
+                line.to_string()
+            }
+        }
+    }
+}
+
+/// Prints a record to stderr.
+pub fn log(format: LogFormat, record: &Record<'_>) {
+    eprintln!("{}", record.to_line(format));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_line() {
+        let record = Record {
+            file: Some("out/abbots.gpx"),
+            trail_id: Some(10_236_086),
+            duration: Some(Duration::from_millis(250)),
+            ..Record::new(Level::Info, "Wrote 2 points")
+        };
+        let line: Value = serde_json::from_str(&record.to_line(LogFormat::Json)).unwrap();
+        assert_eq!(
+            line,
+            json!({
+                "level": "info",
+                "message": "Wrote 2 points",
+                "file": "out/abbots.gpx",
+                "trail_id": 10_236_086,
+                "duration_ms": 250.0,
+            })
+        );
+
+        let warning = Record::new(Level::Warn, "No elevation");
+        assert_eq!(warning.to_line(LogFormat::Text), "No elevation");
+        assert_eq!(
+            warning.to_line(LogFormat::Json),
+            r#"{"level":"warn","message":"No elevation"}"#
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use serde_json::Value;
+use std::fmt;
+use std::ops::Range;
+
+/// A JSON pointer into the input document, saying where something was found. The trail,
+/// route and segment it refers to can be read back out of it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonPointer(String);
+
+impl JsonPointer {
+    pub fn new(pointer: impl Into<String>) -> Self {
+        Self(pointer.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Extends the pointer by one token, which may be an object key or an array index.
+    pub fn join(&self, token: impl fmt::Display) -> Self {
+        Self(format!("{}/{token}", self.0))
+    }
+
+    /// Index into the "trails" (offline format) or "maps" (deep format) array.
+    pub fn trail_index(&self) -> Option<usize> {
+        self.index_after(&["trails", "maps"])
+    }
+
+    pub fn route_index(&self) -> Option<usize> {
+        self.index_after(&["routes"])
+    }
+
+    pub fn segment_index(&self) -> Option<usize> {
+        self.index_after(&["lineSegments"])
+    }
+
+    fn index_after(&self, keys: &[&str]) -> Option<usize> {
+        let tokens: Vec<&str> = self.0.split('/').collect();
+        tokens
+            .windows(2)
+            .find(|pair| keys.contains(&pair[0]))
+            .and_then(|pair| pair[1].parse().ok())
+    }
+}
+
+impl AsRef<str> for JsonPointer {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Why none of a set of JSON pointers matched a document, for telling the user how their input
+/// differs from what was expected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupFailure {
+    pub attempts: Vec<Attempt>,
+}
+
+/// How far a single pointer got before the document diverged from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    /// The pointer that was tried.
+    pub path: String,
+    /// The longest prefix of `path` that exists in the document, "" being the root.
+    pub found: String,
+    pub divergence: Divergence,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The object at `found` has no such key.
+    MissingKey(String),
+    /// The array at `found` has no such element.
+    MissingIndex { index: String, len: usize },
+    /// The value at `found` is a scalar, so can't be descended into. Holds its kind, such as
+    /// "a string".
+    NotContainer(&'static str),
+}
+
+/// Looks up the first of `paths` that exists, returning it along with its value. If none
+/// exist, the failure describes where each one diverged from the document.
+pub fn lookup<'json, 'path>(
+    json: &'json Value,
+    paths: &[&'path str],
+) -> Result<(&'path str, &'json Value), LookupFailure> {
+    paths
+        .iter()
+        .find_map(|path| json.pointer(path).map(|value| (*path, value)))
+        .ok_or_else(|| LookupFailure::new(json, paths))
+}
+
+impl LookupFailure {
+    pub fn new(json: &Value, paths: &[impl AsRef<str>]) -> Self {
+        Self {
+            attempts: paths
+                .iter()
+                .map(|path| Attempt::new(json, path.as_ref()))
+                .collect(),
+        }
+    }
+}
+
+impl Attempt {
+    fn new(json: &Value, path: &str) -> Self {
+        let tokens: Vec<&str> = path.split('/').skip(1).collect();
+        let mut current = json;
+        let mut depth = 0;
+
+        let divergence = loop {
+            let Some(token) = tokens.get(depth) else {
+                // The whole path exists, so the caller must have objected to its value.
+                break Divergence::NotContainer(kind(current));
+            };
+            let key = token.replace("~1", "/").replace("~0", "~");
+
+            let next = match current {
+                Value::Object(map) => map.get(&key).ok_or(Divergence::MissingKey(key)),
+                Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)).ok_or(
+                    Divergence::MissingIndex {
+                        index: key,
+                        len: items.len(),
+                    },
+                ),
+                scalar => Err(Divergence::NotContainer(kind(scalar))),
+            };
+
+            match next {
+                Ok(value) => {
+                    current = value;
+                    depth += 1;
+                }
+                Err(divergence) => break divergence,
+            }
+        };
+
+        Self {
+            path: path.to_string(),
+            found: tokens[..depth].iter().map(|t| format!("/{t}")).collect(),
+            divergence,
+        }
+    }
+}
+
+/// Finds the bytes of `text`, a JSON document, holding the value at `pointer`. This scans the
+/// text rather than parsing it, since parsing loses where things were.
+pub fn pointer_span(text: &str, pointer: &str) -> Option<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut pos = skip_whitespace(bytes, 0);
+    for token in pointer.split('/').skip(1) {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        match bytes.get(pos)? {
+            b'{' => loop {
+                pos = skip_whitespace(bytes, pos + 1);
+                let key_end = skip_value(bytes, pos).filter(|_| bytes[pos] == b'"')?;
+                let key = &text[pos + 1..key_end - 1];
+                pos = skip_whitespace(bytes, key_end);
+                if bytes.get(pos) != Some(&b':') {
+                    return None;
+                }
+                pos = skip_whitespace(bytes, pos + 1);
+                if key == token {
+                    break;
+                }
+                pos = skip_whitespace(bytes, skip_value(bytes, pos)?);
+                if bytes.get(pos) != Some(&b',') {
+                    return None;
+                }
+            },
+            b'[' => {
+                let index: usize = token.parse().ok()?;
+                pos = skip_whitespace(bytes, pos + 1);
+                for _ in 0..index {
+                    pos = skip_whitespace(bytes, skip_value(bytes, pos)?);
+                    if bytes.get(pos) != Some(&b',') {
+                        return None;
+                    }
+                    pos = skip_whitespace(bytes, pos + 1);
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(pos..skip_value(bytes, pos)?)
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Where the JSON value starting at `pos` ends, or `None` if there isn't one.
+fn skip_value(bytes: &[u8], pos: usize) -> Option<usize> {
+    match bytes.get(pos)? {
+        b'"' => {
+            let mut i = pos + 1;
+            loop {
+                match bytes.get(i)? {
+                    b'\\' => i += 2,
+                    b'"' => return Some(i + 1),
+                    _ => i += 1,
+                }
+            }
+        }
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut i = pos;
+            loop {
+                match bytes.get(i)? {
+                    b'"' => {
+                        i = skip_value(bytes, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+        }
+        b'}' | b']' | b',' | b':' => None,
+        _ => {
+            let end = bytes[pos..]
+                .iter()
+                .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+                .map_or(bytes.len(), |len| pos + len);
+            Some(end)
+        }
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+impl fmt::Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let found = if self.found.is_empty() {
+            "the document"
+        } else {
+            self.found.as_str()
+        };
+
+        match &self.divergence {
+            Divergence::MissingKey(key) if self.found.is_empty() => {
+                write!(f, "{}: the document has no \"{key}\"", self.path)
+            }
+            Divergence::MissingKey(key) => {
+                write!(f, "{}: found {found} but no \"{key}\"", self.path)
+            }
+            Divergence::MissingIndex { index, len } => write!(
+                f,
+                "{}: found {found} but it has no element {index} ({len} elements)",
+                self.path
+            ),
+            Divergence::NotContainer(kind) => {
+                write!(f, "{}: found {found} but it is {kind}", self.path)
+            }
+        }
+    }
+}
+
+impl fmt::Display for LookupFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried ")?;
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{attempt}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_pointer_indices() {
+        let pointer = JsonPointer::new("/trails/2/defaultMap/routes/1").join("lineSegments");
+        let pointer = pointer.join(3).join("polyline");
+        assert_eq!(
+            pointer.as_str(),
+            "/trails/2/defaultMap/routes/1/lineSegments/3/polyline"
+        );
+        assert_eq!(pointer.trail_index(), Some(2));
+        assert_eq!(pointer.route_index(), Some(1));
+        assert_eq!(pointer.segment_index(), Some(3));
+        assert_eq!(JsonPointer::new("/maps/0/name").route_index(), None);
+    }
+
+    #[test]
+    fn test_pointer_span() {
+        let text = r#"{ "trails": [ {"name": "a,]"}, { "name" : "Abbots Leigh", "id": 1 } ] }"#;
+        let span = |pointer| pointer_span(text, pointer).map(|span| &text[span]);
+        assert_eq!(span("/trails/1/name"), Some(r#""Abbots Leigh""#));
+        assert_eq!(span("/trails/1/id"), Some("1"));
+        assert_eq!(span("/trails/0"), Some(r#"{"name": "a,]"}"#));
+        assert_eq!(span(""), Some(text));
+        assert_eq!(span("/trails/2"), None);
+        assert_eq!(span("/maps"), None);
+    }
+
+    #[test]
+    fn test_lookup_failure_describes_divergence() {
+        let json = json!({ "trails": [{ "defaultMap": { "name": "x" } }] });
+        let failure = lookup(
+            &json,
+            &[
+                "/trails/0/defaultMap/routes/0/lineSegments",
+                "/trails/1/defaultMap",
+                "/maps/0/routes",
+                "/trails/0/defaultMap/name/first",
+            ],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            failure.to_string(),
+            "tried /trails/0/defaultMap/routes/0/lineSegments: found /trails/0/defaultMap but \
+             no \"routes\"; /trails/1/defaultMap: found /trails but it has no element 1 (1 \
+             elements); /maps/0/routes: the document has no \"maps\"; \
+             /trails/0/defaultMap/name/first: found /trails/0/defaultMap/name but it is a string"
+        );
+    }
+}
@@ -1,11 +1,443 @@
-use alltrailsgpx::{get_input_reader, get_output_writer, run, Args};
-use clap::Parser;
+use alltrailsgpx::archive::is_zip_path;
+use alltrailsgpx::cues::{cue_sheet, write_cues_csv};
+use alltrailsgpx::diagnostic::diagnose;
+use alltrailsgpx::diff::diff;
+use alltrailsgpx::format::{numbered_path, output_path, Format};
+use alltrailsgpx::log::{log, Level, LogFormat, Record};
+use alltrailsgpx::metadata::write_metadata;
+use alltrailsgpx::profile::{elevation_profile, write_profile_svg};
+use alltrailsgpx::report::{write_report, write_table};
+use alltrailsgpx::scan::scan;
+use alltrailsgpx::stats::format_minutes;
+use alltrailsgpx::summary::write_summary;
+use alltrailsgpx::{
+    convert, convert_polyline, convert_trails, list_trails, read_input, run_batch,
+    run_split_routes, run_streaming, write_conversion, write_merged_gpx, Args, Command, Conversion,
+    Error, InputSource, Options, OutputSink,
+};
+use std::path::Path;
 
-fn main() -> Result<(), alltrailsgpx::Error> {
-    let args = Args::parse();
+/// Converts one input, saying which it was if it fails.
+fn convert_file(input: &InputSource, options: &Options) -> Result<Conversion, Error> {
+    input
+        .open()
+        .and_then(|reader| convert(reader, options))
+        .map_err(|source| Error::BatchInputError {
+            path: input.to_string(),
+            source: Box::new(source),
+        })
+}
+
+/// Converts each input without writing it, printing whether it worked and any warnings.
+fn validate(inputs: &[InputSource], options: &Options) -> Result<(), Error> {
+    let mut failed = 0;
+    for input in inputs {
+        match input.open().and_then(|reader| convert(reader, options)) {
+            Ok(conversion) => {
+                println!("{input}: ok, {} points", conversion.stats.points);
+                for warning in &conversion.warnings {
+                    println!("{input}: warning: {warning}");
+                }
+            }
+            Err(e) => {
+                println!("{input}: {e}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Error::ValidationFailed {
+            failed,
+            total: inputs.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Prints the error along with the part of the input it's about and exits, if it's the kind
+/// of error that can be explained that way.
+fn exit_with_diagnostic(error: &Error, name: &str, input: &[u8], options: &Options) {
+    // Picking another trail or route reorders the document, and filters and extractors read
+    // it differently, so pointers no longer match it.
+    if options.trail_index != 0
+        || options.route_id.is_some()
+        || options.filter.is_some()
+        || !options.extractors.is_empty()
+    {
+        return;
+    }
+    if let Some(diagnostic) = diagnose(error, name, input) {
+        eprintln!("{:?}", miette::Report::new(diagnostic));
+        std::process::exit(1);
+    }
+}
+
+/// Writes the --report of a run, if one was asked for.
+fn report_run(
+    args: &Args,
+    converted: &[(String, Conversion)],
+    failed: &[(String, String)],
+) -> Result<(), Error> {
+    if let Some(path) = &args.report {
+        let mut sink = OutputSink::new(Some(path));
+        write_report(converted, failed, sink.writer()?).map_err(Error::OutputWriteError)?;
+    }
+    Ok(())
+}
+
+/// Says which inputs of a batch failed to convert, if any did: as a table of every input in
+/// text logs, or as an error record each in JSON ones.
+fn print_batch_failures(
+    args: &Args,
+    converted: &[(String, Conversion)],
+    failed: &[(String, String)],
+) {
+    if failed.is_empty() {
+        return;
+    }
+    match args.log_format {
+        LogFormat::Text => {
+            let _ = write_table(converted, failed, std::io::stderr().lock());
+        }
+        LogFormat::Json => {
+            for (input, error) in failed {
+                let message = format!("Failed to convert {input}: {error}");
+                log(args.log_format, &Record::new(Level::Error, message));
+            }
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args = alltrailsgpx::cli::parse()?;
+    let log_format = args.log_format;
+    let result = run(args);
+    // In text format, returning the error prints it as usual.
+    if let (Err(e), LogFormat::Json) = (&result, log_format) {
+        log(log_format, &Record::new(Level::Error, e.to_string()));
+        std::process::exit(1);
+    }
+    result
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let log_format = args.log_format;
+    let options = args.options()?;
+    let formats = args.formats();
+    let units = options.units;
+    let mut output = OutputSink::new(args.output.as_deref());
+
+    #[cfg(feature = "mvt")]
+    if let Some(tile) = args.mvt_tile {
+        let inputs = args.inputs();
+        let reader = inputs[0].open()?;
+        let tracks = alltrailsgpx::run_mvt(
+            reader,
+            output.writer()?,
+            tile,
+            args.mvt_layer.as_deref(),
+            args.bbox,
+        )?;
+        if !args.quiet {
+            let message = format!("Wrote {tracks} tracks to {output}");
+            log(log_format, &Record::new(Level::Info, message));
+        }
+        return Ok(());
+    }
+
+    if args.scan {
+        let inputs = args.inputs();
+        for input in &inputs {
+            let candidates = input.open().and_then(scan)?;
+            if candidates.is_empty() && !args.quiet {
+                let message = format!("{input}: no strings decode to a plausible route");
+                log(log_format, &Record::new(Level::Warn, message));
+            }
+            for candidate in candidates {
+                match inputs.len() {
+                    1 => println!("{candidate}"),
+                    _ => println!("{input}: {candidate}"),
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if args.stream {
+        let input = match args.inputs().as_slice() {
+            [input] if !input.is_archive() => input.clone(),
+            _ => return Err(Error::StreamNeedsSingleGpx),
+        };
+        if formats != [Format::Gpx] || args.polyline.is_some() {
+            return Err(Error::StreamNeedsSingleGpx);
+        }
+        let reader = input.open()?;
+        let points = run_streaming(reader, output.writer()?, &options)?;
+        if !args.quiet {
+            let message = format!("Wrote {points} points to {output}");
+            log(log_format, &Record::new(Level::Info, message));
+        }
+        return Ok(());
+    }
+
+    match &args.command {
+        None | Some(Command::Convert) => {}
+        Some(Command::Diff { old, new }) => {
+            let (old, new) = (convert_file(old, &options)?, convert_file(new, &options)?);
+            println!("{}", diff(&old.track, &new.track));
+            return Ok(());
+        }
+        Some(Command::Stats) => {
+            for input in args.inputs() {
+                let conversion = convert_file(&input, &options)?;
+                let alltrails_estimate = conversion
+                    .trail_stats
+                    .as_ref()
+                    .and_then(|trail_stats| trail_stats.duration_minutes)
+                    .map(|minutes| format!(" (AllTrails: {})", format_minutes(minutes)))
+                    .unwrap_or_default();
+                println!(
+                    "{}: {}{alltrails_estimate}",
+                    conversion.name,
+                    conversion.stats.describe(units)
+                );
+            }
+            return Ok(());
+        }
+        Some(Command::List) => {
+            let inputs = args.inputs();
+            for input in &inputs {
+                let trails = input.open().and_then(list_trails).map_err(|source| {
+                    Error::BatchInputError {
+                        path: input.to_string(),
+                        source: Box::new(source),
+                    }
+                })?;
+                for trail in trails {
+                    match inputs.len() {
+                        1 => println!("{trail}"),
+                        _ => println!("{input}: {trail}"),
+                    }
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Merge) => {
+            let conversions = args
+                .inputs()
+                .iter()
+                .map(|input| convert_file(input, &options))
+                .collect::<Result<Vec<_>, _>>()?;
+            write_merged_gpx(&conversions, &options, output.writer()?)?;
+            if !args.quiet {
+                let message = format!("Wrote {} tracks to {output}", conversions.len());
+                log(log_format, &Record::new(Level::Info, message));
+            }
+            return Ok(());
+        }
+        Some(Command::Validate) => return validate(&args.inputs(), &options),
+    }
+
+    let inputs = args.inputs();
+    let mut failed = Vec::new();
+    let batch = inputs.len() > 1
+        || inputs.iter().any(InputSource::is_archive)
+        || args.output.as_deref().is_some_and(is_zip_path);
+    let conversions = if args.split_routes {
+        let input = match inputs.as_slice() {
+            [input] if !input.is_archive() => input,
+            _ => return Err(Error::SplitRoutesNeedsSingleInput),
+        };
+        let output = args
+            .output
+            .as_deref()
+            .filter(|output| *output != "-")
+            .ok_or(Error::SplitRoutesNeedsOutput)?;
+        if args.profile.is_some() {
+            return Err(Error::ProfileNeedsSingleRoute);
+        }
+        if args.cues.is_some() {
+            return Err(Error::CuesNeedSingleRoute);
+        }
+        run_split_routes(input, output, &args.route_file_name, &formats, &options)?
+    } else if args.include_bundled {
+        let input = match inputs.as_slice() {
+            [input] if !batch => input,
+            _ => return Err(Error::BundledNeedsSingleInput),
+        };
+        if formats != [Format::Gpx] {
+            return Err(Error::BundledNeedsGpx);
+        }
+        let conversions = input
+            .open()
+            .and_then(|reader| convert_trails(reader, &options))?;
+        write_merged_gpx(&conversions, &options, output.writer()?)?;
+        let written_to = output.to_string();
+        conversions
+            .into_iter()
+            .map(|conversion| (written_to.clone(), conversion))
+            .collect()
+    } else if batch && args.polyline.is_none() {
+        let output = args.output.as_deref().ok_or(Error::BatchOutputRequired)?;
+        if args.profile.is_some() {
+            return Err(Error::ProfileNeedsSingleRoute);
+        }
+        if args.cues.is_some() {
+            return Err(Error::CuesNeedSingleRoute);
+        }
+        let run = match run_batch(&inputs, output, &formats, &options) {
+            Ok(run) => run,
+            Err(e) => {
+                // With --fail-fast, the run stops at the first input that fails, so that's the
+                // only one to report.
+                if let Error::BatchInputError { path, source } = &e {
+                    report_run(&args, &[], &[(path.clone(), source.to_string())])?;
+                }
+                return Err(e);
+            }
+        };
+        failed = run
+            .failed
+            .iter()
+            .map(|(input, e)| (input.clone(), e.to_string()))
+            .collect();
+        if run.converted.is_empty() && !failed.is_empty() {
+            print_batch_failures(&args, &[], &failed);
+            report_run(&args, &[], &failed)?;
+            return Err(Error::ValidationFailed {
+                failed: failed.len(),
+                total: failed.len(),
+            });
+        }
+        run.converted
+    } else {
+        let metadata_path = match args.output.as_deref() {
+            _ if !args.metadata_out => None,
+            None | Some("-") => return Err(Error::MetadataNeedsOutput),
+            Some(output) => Some(Path::new(output).with_extension("metadata.json")),
+        };
+        let conversion = match &args.polyline {
+            Some(polyline) => convert_polyline(polyline, &options)?,
+            None => {
+                let source = &inputs[0];
+                let input = read_input(source.open()?, options.max_input_size)?;
+                convert(input.as_slice(), &options).inspect_err(|e| {
+                    if log_format == LogFormat::Text {
+                        exit_with_diagnostic(e, &source.to_string(), &input, &options);
+                    }
+                })?
+            }
+        };
+
+        let parts = conversion.split(options.max_points_per_file, options.start_number);
+        let written_to = if let ([format], [part]) = (&formats[..], &parts[..]) {
+            write_conversion(part, *format, &options, output.writer()?)?;
+            output.to_string()
+        } else {
+            let output = args
+                .output
+                .as_deref()
+                .filter(|output| *output != "-")
+                .ok_or(if parts.len() > 1 {
+                    Error::SplitNeedsOutput
+                } else {
+                    Error::MultipleFormatsNeedOutput
+                })?;
+            let mut written_to = Vec::with_capacity(formats.len() * parts.len());
+            for &format in &formats {
+                for (i, part) in parts.iter().enumerate() {
+                    let mut path = output_path(output, format);
+                    if parts.len() > 1 {
+                        path = numbered_path(&path, i + 1);
+                    }
+                    write_conversion(
+                        part,
+                        format,
+                        &options,
+                        OutputSink::File(path.clone()).writer()?,
+                    )?;
+                    written_to.push(path);
+                }
+            }
+            written_to.join(", ")
+        };
+        if let Some(path) = metadata_path {
+            let mut sink = OutputSink::File(path.display().to_string());
+            write_metadata(&written_to, &conversion, sink.writer()?)
+                .map_err(Error::OutputWriteError)?;
+        }
+        vec![(written_to, conversion)]
+    };
+
+    if let (Some(path), [(written_to, conversion)]) = (&args.profile, conversions.as_slice()) {
+        let profile = elevation_profile(&conversion.track);
+        if profile.is_empty() {
+            if !args.quiet {
+                let message = format!(
+                    "{}: the route has no elevation data, so no profile was drawn",
+                    conversion.name
+                );
+                let record = Record::new(Level::Warn, message).about(written_to, conversion);
+                log(log_format, &record);
+            }
+        } else {
+            let mut sink = OutputSink::from(path.as_str());
+            write_profile_svg(&profile, units, sink.writer()?).map_err(Error::OutputWriteError)?;
+        }
+    }
+
+    if let (Some(path), [(_, conversion)]) = (&args.cues, conversions.as_slice()) {
+        let cues = cue_sheet(&conversion.track, &conversion.waypoints);
+        let mut sink = OutputSink::from(path.as_str());
+        write_cues_csv(&cues, units, sink.writer()?).map_err(Error::OutputWriteError)?;
+    }
+
+    if let Some(path) = &args.summary {
+        let mut sink = OutputSink::from(path.as_str());
+        write_summary(&conversions, units, sink.writer()?).map_err(Error::OutputWriteError)?;
+    }
+
+    report_run(&args, &conversions, &failed)?;
+
+    if args.quiet {
+        print_batch_failures(&args, &conversions, &failed);
+        return Ok(());
+    }
 
-    let reader = get_input_reader(&args.input)?;
-    let writer = get_output_writer(&args.output)?;
+    for (written_to, conversion) in &conversions {
+        if conversion.swapped_latlon && !args.swap_latlon {
+            let message = format!(
+                "{}: latitude and longitude looked swapped, so they were swapped back",
+                conversion.name
+            );
+            let record = Record::new(Level::Warn, message).about(written_to, conversion);
+            log(log_format, &record);
+        }
+        for warning in &conversion.warnings {
+            let message = format!("{}: {warning}", conversion.name);
+            let record = Record::new(Level::Warn, message).about(written_to, conversion);
+            log(log_format, &record);
+        }
+        let stats = &conversion.stats;
+        let message = format!(
+            "Wrote {}: {} points, {} to {written_to}",
+            conversion.name,
+            stats.points,
+            units.distance(stats.distance_m, 1)
+        );
+        log(
+            log_format,
+            &Record::new(Level::Info, message).about(written_to, conversion),
+        );
+        if args.stats {
+            let message = format!("{}: {}", conversion.name, stats.describe(units));
+            log(
+                log_format,
+                &Record::new(Level::Info, message).about(written_to, conversion),
+            );
+        }
+    }
+    print_batch_failures(&args, &conversions, &failed);
 
-    run(reader, writer)
+    Ok(())
 }
@@ -0,0 +1,99 @@
+//! A JSON sidecar describing what was extracted from a route, so that converted files can be
+//! indexed without reading the AllTrails response again.
+
+use crate::{first_time, Conversion};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Describes a conversion written to `written_to`. Anything the input didn't say is null.
+pub fn metadata(written_to: &str, conversion: &Conversion) -> Value {
+    let stats = &conversion.stats;
+    let trail_stats = conversion.trail_stats.as_ref().map(|trail_stats| {
+        json!({
+            "length_m": trail_stats.length,
+            "elevation_gain_m": trail_stats.elevation_gain,
+            "elevation_max_m": trail_stats.elevation_max,
+            "duration_minutes": trail_stats.duration_minutes,
+            "route_type": trail_stats.route_type,
+            "difficulty": trail_stats.difficulty,
+        })
+    });
+    let start_time = first_time(&conversion.track)
+        .and_then(|time| OffsetDateTime::from(time).format(&Rfc3339).ok());
+    json!({
+        "name": conversion.name,
+        "trail_id": conversion.trail_id,
+        "link": conversion.link,
+        "output": written_to,
+        "stats": {
+            "points": stats.points,
+            "distance_m": stats.distance_m,
+            "ascent_m": stats.elevation.map(|elevation| elevation.ascent_m),
+            "descent_m": stats.elevation.map(|elevation| elevation.descent_m),
+            "shape": stats.shape.map(|shape| shape.to_string()),
+            "start_time": start_time,
+            "estimated_minutes": stats.estimated_minutes,
+        },
+        "trail_stats": trail_stats,
+        "waypoints": conversion.waypoints.len(),
+        "swapped_latlon": conversion.swapped_latlon,
+        "sources": conversion
+            .sources
+            .iter()
+            .map(|pointer| pointer.as_str())
+            .collect::<Vec<_>>(),
+        "warnings": conversion
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Writes the metadata of a conversion as pretty-printed JSON.
+pub fn write_metadata(
+    written_to: &str,
+    conversion: &Conversion,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, &metadata(written_to, conversion))?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert, Options};
+
+    #[test]
+    fn test_metadata() {
+        let json = json!({
+            "trails": [{
+                "id": 10_236_086,
+                "name": "Abbots Leigh",
+                "difficultyRating": "3",
+                "trailGeoStats": { "length": 9817.4 },
+                "defaultMap": {
+                    "routes": [{
+                        "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }]
+                    }]
+                }
+            }]
+        });
+        let conversion = convert(json.to_string().as_bytes(), &Options::default()).unwrap();
+        let metadata = metadata("out/abbots.gpx", &conversion);
+
+        assert_eq!(metadata["name"], "Abbots Leigh");
+        assert_eq!(metadata["trail_id"], 10_236_086);
+        assert_eq!(metadata["output"], "out/abbots.gpx");
+        assert_eq!(metadata["stats"]["points"], 2);
+        assert_eq!(metadata["trail_stats"]["difficulty"], "Moderate");
+        assert_eq!(
+            metadata["sources"],
+            json!(["/trails/0/defaultMap/routes/0/lineSegments/0/polyline/pointsData"])
+        );
+    }
+}
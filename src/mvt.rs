@@ -0,0 +1,354 @@
+//! Reads trail geometry out of Mapbox Vector Tiles, for trails that aren't available through
+//! the JSON endpoints. Only the small part of the protobuf wire format that MVT uses is
+//! implemented, which is enough to pull out LineString features and their names.
+
+use crate::Error;
+use geo_types::Coord;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+const DEFAULT_EXTENT: u32 = 4096;
+const GEOM_TYPE_LINESTRING: u64 = 2;
+
+/// Identifies a tile in the usual z/x/y web mercator scheme, which is needed to turn the
+/// tile-local coordinates in an MVT back into longitude and latitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileId {
+    pub z: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl FromStr for TileId {
+    type Err = String;
+
+    /// Parses "z/x/y".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split('/')
+            .map(str::parse::<u32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid tile coordinate: {e}"))?;
+
+        match parts[..] {
+            [z, x, y] if z < 32 && x >> z == 0 && y >> z == 0 => Ok(Self { z, x, y }),
+            [_, _, _] => Err("tile x and y must be below 2^z".to_string()),
+            _ => Err("expected a tile in the form z/x/y".to_string()),
+        }
+    }
+}
+
+impl TileId {
+    fn to_lon_lat(self, x: i64, y: i64, extent: u32) -> Coord<f64> {
+        let tiles = f64::from(1u32 << self.z);
+        let extent = f64::from(extent);
+        let px = (f64::from(self.x) + x as f64 / extent) / tiles;
+        let py = (f64::from(self.y) + y as f64 / extent) / tiles;
+
+        Coord {
+            x: px * 360.0 - 180.0,
+            y: (PI * (1.0 - 2.0 * py)).sinh().atan().to_degrees(),
+        }
+    }
+}
+
+/// A LineString feature from a tile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLine {
+    pub layer: String,
+    pub name: Option<String>,
+    pub coords: Vec<Coord<f64>>,
+}
+
+/// Decodes every LineString feature in an uncompressed tile. A feature with several parts
+/// produces one line per part.
+pub fn decode_lines(data: &[u8], tile: TileId) -> Result<Vec<TileLine>, Error> {
+    let mut lines = Vec::new();
+    let mut reader = Reader::new(data);
+
+    while !reader.is_empty() {
+        match reader.field()? {
+            (3, WIRE_LEN) => decode_layer(reader.bytes()?, tile, &mut lines)?,
+            (_, wire) => reader.skip(wire)?,
+        }
+    }
+
+    Ok(lines)
+}
+
+fn decode_layer(data: &[u8], tile: TileId, lines: &mut Vec<TileLine>) -> Result<(), Error> {
+    let mut name = String::new();
+    let mut extent = DEFAULT_EXTENT;
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    let mut features = Vec::new();
+
+    let mut reader = Reader::new(data);
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_LEN) => name = reader.string()?.to_string(),
+            (2, WIRE_LEN) => features.push(reader.bytes()?),
+            (3, WIRE_LEN) => keys.push(reader.string()?),
+            (4, WIRE_LEN) => values.push(decode_string_value(reader.bytes()?)?),
+            (5, WIRE_VARINT) => extent = reader.varint_u32()?,
+            (_, wire) => reader.skip(wire)?,
+        }
+    }
+
+    if extent == 0 {
+        return Err(Error::MvtDecodeError("layer extent is zero"));
+    }
+
+    for feature in features {
+        let feature = Feature::decode(feature)?;
+        if feature.geom_type != GEOM_TYPE_LINESTRING {
+            continue;
+        }
+
+        let feature_name = feature
+            .tags
+            .chunks_exact(2)
+            .find(|tag| keys.get(tag[0] as usize) == Some(&"name"))
+            .and_then(|tag| values.get(tag[1] as usize).cloned().flatten());
+
+        for part in decode_geometry(&feature.geometry)? {
+            if part.len() < 2 {
+                continue;
+            }
+            lines.push(TileLine {
+                layer: name.clone(),
+                name: feature_name.clone(),
+                coords: part
+                    .into_iter()
+                    .map(|(x, y)| tile.to_lon_lat(x, y, extent))
+                    .collect(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Only string values are interesting to us, since they're all we use for naming.
+fn decode_string_value(data: &[u8]) -> Result<Option<String>, Error> {
+    let mut value = None;
+    let mut reader = Reader::new(data);
+    while !reader.is_empty() {
+        match reader.field()? {
+            (1, WIRE_LEN) => value = Some(reader.string()?.to_string()),
+            (_, wire) => reader.skip(wire)?,
+        }
+    }
+    Ok(value)
+}
+
+#[derive(Default)]
+struct Feature {
+    tags: Vec<u32>,
+    geom_type: u64,
+    geometry: Vec<u32>,
+}
+
+impl Feature {
+    fn decode(data: &[u8]) -> Result<Self, Error> {
+        let mut feature = Self::default();
+        let mut reader = Reader::new(data);
+        while !reader.is_empty() {
+            match reader.field()? {
+                (2, WIRE_LEN) => feature.tags = Reader::new(reader.bytes()?).packed_u32()?,
+                (3, WIRE_VARINT) => feature.geom_type = reader.varint()?,
+                (4, WIRE_LEN) => feature.geometry = Reader::new(reader.bytes()?).packed_u32()?,
+                (_, wire) => reader.skip(wire)?,
+            }
+        }
+        Ok(feature)
+    }
+}
+
+/// Runs the geometry command stream, returning tile-local coordinates for each part.
+fn decode_geometry(commands: &[u32]) -> Result<Vec<Vec<(i64, i64)>>, Error> {
+    const MOVE_TO: u32 = 1;
+    const LINE_TO: u32 = 2;
+    const CLOSE_PATH: u32 = 7;
+
+    let mut parts: Vec<Vec<(i64, i64)>> = Vec::new();
+    let (mut x, mut y) = (0_i64, 0_i64);
+    let mut commands = commands.iter().copied();
+
+    while let Some(command) = commands.next() {
+        let (id, count) = (command & 0x7, command >> 3);
+        match id {
+            MOVE_TO | LINE_TO => {
+                for _ in 0..count {
+                    let (Some(dx), Some(dy)) = (commands.next(), commands.next()) else {
+                        return Err(Error::MvtDecodeError("truncated geometry"));
+                    };
+                    x += zigzag(dx);
+                    y += zigzag(dy);
+                    if id == MOVE_TO {
+                        parts.push(Vec::new());
+                    }
+                    parts
+                        .last_mut()
+                        .ok_or(Error::MvtDecodeError("LineTo before MoveTo"))?
+                        .push((x, y));
+                }
+            }
+            CLOSE_PATH => {}
+            _ => return Err(Error::MvtDecodeError("unknown geometry command")),
+        }
+    }
+
+    Ok(parts)
+}
+
+fn zigzag(n: u32) -> i64 {
+    i64::from(n >> 1) ^ -i64::from(n & 1)
+}
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_64BIT: u8 = 1;
+const WIRE_LEN: u8 = 2;
+const WIRE_32BIT: u8 = 5;
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self
+                .data
+                .split_first()
+                .ok_or(Error::MvtDecodeError("truncated varint"))?;
+            self.data = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::MvtDecodeError("varint too long"))
+    }
+
+    fn varint_u32(&mut self) -> Result<u32, Error> {
+        u32::try_from(self.varint()?).map_err(|_| Error::MvtDecodeError("value exceeds 32 bits"))
+    }
+
+    fn field(&mut self) -> Result<(u64, u8), Error> {
+        let key = self.varint()?;
+        Ok((key >> 3, (key & 0x7) as u8))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.data.len() {
+            return Err(Error::MvtDecodeError("field extends past end of data"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = usize::try_from(self.varint()?)
+            .map_err(|_| Error::MvtDecodeError("field length too large"))?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<&'a str, Error> {
+        std::str::from_utf8(self.bytes()?).map_err(|_| Error::MvtDecodeError("invalid UTF-8"))
+    }
+
+    fn packed_u32(mut self) -> Result<Vec<u32>, Error> {
+        let mut values = Vec::new();
+        while !self.is_empty() {
+            values.push(self.varint_u32()?);
+        }
+        Ok(values)
+    }
+
+    fn skip(&mut self, wire: u8) -> Result<(), Error> {
+        match wire {
+            WIRE_VARINT => self.varint().map(|_| ()),
+            WIRE_64BIT => self.take(8).map(|_| ()),
+            WIRE_LEN => self.bytes().map(|_| ()),
+            WIRE_32BIT => self.take(4).map(|_| ()),
+            _ => Err(Error::MvtDecodeError("unsupported wire type")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn len_field(field: u64, payload: &[u8], out: &mut Vec<u8>) {
+        varint((field << 3) | u64::from(WIRE_LEN), out);
+        varint(payload.len() as u64, out);
+        out.extend_from_slice(payload);
+    }
+
+    fn packed(values: &[u32]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &v in values {
+            varint(u64::from(v), &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn test_decode_linestring() {
+        let mut feature = Vec::new();
+        len_field(2, &packed(&[0, 0]), &mut feature);
+        varint(3 << 3, &mut feature);
+        varint(GEOM_TYPE_LINESTRING, &mut feature);
+        // MoveTo(2048, 2048), LineTo(+2048, 0), i.e. the centre to the right edge.
+        len_field(4, &packed(&[9, 4096, 4096, 10, 4096, 0]), &mut feature);
+
+        let mut value = Vec::new();
+        len_field(1, b"Ridge Path", &mut value);
+
+        let mut layer = Vec::new();
+        len_field(1, b"trails", &mut layer);
+        len_field(2, &feature, &mut layer);
+        len_field(3, b"name", &mut layer);
+        len_field(4, &value, &mut layer);
+
+        let mut tile = Vec::new();
+        len_field(3, &layer, &mut tile);
+
+        let lines = decode_lines(&tile, "1/1/0".parse().unwrap()).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].layer, "trails");
+        assert_eq!(lines[0].name.as_deref(), Some("Ridge Path"));
+
+        // Tile 1/1/0 is the north-east quarter of the world.
+        let [start, end] = lines[0].coords[..] else {
+            panic!("expected two coordinates");
+        };
+        assert!((start.x - 90.0).abs() < 1e-9 && (start.y - 66.513_26).abs() < 1e-4);
+        assert!((end.x - 180.0).abs() < 1e-9 && (end.y - start.y).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rejects_truncated_tile() {
+        assert!(decode_lines(&[0x1a, 0x05, 0x0a], "0/0/0".parse().unwrap()).is_err());
+    }
+}
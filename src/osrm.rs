@@ -0,0 +1,209 @@
+//! Snapping a route onto OpenStreetMap paths using the match service of an OSRM server, for
+//! AllTrails polylines that cut corners or wander off the trail. This is meant for a server
+//! running locally (for example osrm-backend's Docker image with a foot profile), so only
+//! plain HTTP is spoken, and just enough of it to make a GET request.
+
+use crate::geo::Located;
+use crate::Error;
+use geo_types::Coord;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+/// osrm-routed refuses to match more than 100 points in one request by default.
+const MAX_POINTS_PER_REQUEST: usize = 100;
+
+/// How far, in meters, each point may be from the path it's snapped to. OSRM's default
+/// assumes GPS-quality points, which AllTrails' hand-drawn routes often aren't.
+const SEARCH_RADIUS_M: u32 = 25;
+
+/// Where to find an OSRM server, parsed from a URL like "http://localhost:5000".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    /// Anything after the host, without a trailing slash.
+    prefix: String,
+}
+
+impl Endpoint {
+    pub fn parse(url: &str) -> Result<Self, Error> {
+        let error = |reason: &str| Error::MapMatchError(format!("{url}: {reason}"));
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| error("only http:// URLs are supported"))?;
+        let (authority, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| error("invalid port"))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(error("no host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            prefix: prefix.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn match_path(&self, coords: &[Coord<f64>]) -> String {
+        let join = |parts: Vec<String>| parts.join(";");
+        let positions = join(coords.iter().map(|c| format!("{},{}", c.x, c.y)).collect());
+        let radiuses = join(vec![SEARCH_RADIUS_M.to_string(); coords.len()]);
+        let prefix = match self.prefix.as_str() {
+            "" => String::new(),
+            prefix => format!("/{prefix}"),
+        };
+        format!(
+            "{prefix}/match/v1/foot/{positions}?geometries=geojson&overview=full&radiuses={radiuses}"
+        )
+    }
+
+    fn get(&self, path: &str) -> Result<Value, Error> {
+        let io_error =
+            |e: std::io::Error| Error::MapMatchError(format!("{}:{}: {e}", self.host, self.port));
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).map_err(io_error)?;
+        // HTTP/1.0 so that the response isn't chunked and ends when the connection closes.
+        write!(
+            stream,
+            "GET {path} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+            self.host
+        )
+        .map_err(io_error)?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).map_err(io_error)?;
+        parse_response(&response)
+    }
+}
+
+/// Snaps each line onto the paths OSRM knows about. Long lines are matched in overlapping
+/// pieces and joined back up. Nothing the points carry, such as their elevation, is carried
+/// over, as the snapped points are new.
+pub fn match_lines<P: Located>(
+    endpoint: &Endpoint,
+    lines: Vec<Vec<P>>,
+) -> Result<Vec<Vec<P>>, Error> {
+    let mut matched = Vec::with_capacity(lines.len());
+    for line in lines {
+        if line.len() < 2 {
+            matched.push(line);
+            continue;
+        }
+        let line: Vec<Coord<f64>> = line.iter().map(Located::coord).collect();
+        let mut coords: Vec<Coord<f64>> = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = (start + MAX_POINTS_PER_REQUEST).min(line.len());
+            let path = endpoint.match_path(&line[start..end]);
+            append(&mut coords, endpoint.get(&path)?)?;
+            if end == line.len() {
+                break;
+            }
+            // Each piece starts where the last one ended, so that they join up.
+            start = end - 1;
+        }
+        matched.push(coords.into_iter().map(P::from).collect());
+    }
+    Ok(matched)
+}
+
+/// Adds the matched geometry from an OSRM response to `coords`. OSRM may split the match
+/// where it couldn't find a path, but the pieces are joined back into one line here.
+fn append(coords: &mut Vec<Coord<f64>>, response: Value) -> Result<(), Error> {
+    let matchings = response
+        .get("matchings")
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::MapMatchError("response has no matchings".to_string()))?;
+    for matching in matchings {
+        let positions = matching
+            .pointer("/geometry/coordinates")
+            .and_then(Value::as_array)
+            .ok_or_else(|| Error::MapMatchError("matching has no geometry".to_string()))?;
+        for position in positions {
+            let number = |i: usize| position.get(i).and_then(Value::as_f64);
+            let (Some(x), Some(y)) = (number(0), number(1)) else {
+                return Err(Error::MapMatchError(format!(
+                    "invalid position {position} in matching"
+                )));
+            };
+            let coord = Coord { x, y };
+            if coords.last() != Some(&coord) {
+                coords.push(coord);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_response(response: &[u8]) -> Result<Value, Error> {
+    let error = Error::MapMatchError;
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| error("malformed HTTP response".to_string()))?;
+    let (head, body) = (&response[..split], &response[split + 4..]);
+    let status = String::from_utf8_lossy(head)
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1).map(str::to_string))
+        .unwrap_or_default();
+
+    let json: Option<Value> = serde_json::from_slice(body).ok();
+    // OSRM explains failures, such as finding no paths nearby, in a JSON body.
+    let code = json
+        .as_ref()
+        .and_then(|j| j.get("code"))
+        .and_then(Value::as_str);
+    if status != "200" || code != Some("Ok") {
+        let message = json
+            .as_ref()
+            .and_then(|j| j.get("message"))
+            .and_then(Value::as_str)
+            .or(code)
+            .unwrap_or("no explanation given");
+        return Err(error(format!("server returned status {status}: {message}")));
+    }
+    Ok(json.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_endpoint() {
+        let endpoint = Endpoint::parse("http://localhost:5000/osrm/").unwrap();
+        let path =
+            endpoint.match_path(&[Coord { x: -2.6, y: 51.45 }, Coord { x: -2.61, y: 51.46 }]);
+        assert_eq!(
+            path,
+            "/osrm/match/v1/foot/-2.6,51.45;-2.61,51.46?geometries=geojson&overview=full&radiuses=25;25"
+        );
+        assert_eq!(Endpoint::parse("http://example.com").unwrap().port, 80);
+        assert!(Endpoint::parse("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_append_joins_matchings() {
+        let response = json!({
+            "code": "Ok",
+            "matchings": [
+                { "geometry": { "type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]] } },
+                { "geometry": { "type": "LineString", "coordinates": [[1.0, 1.0], [2.0, 2.0]] } }
+            ]
+        });
+        let mut coords = vec![Coord { x: 0.0, y: 0.0 }];
+        append(&mut coords, response).unwrap();
+        assert_eq!(coords.len(), 3);
+    }
+
+    #[test]
+    fn test_error_response() {
+        let response = b"HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\n\r\n\
+            {\"code\":\"NoMatch\",\"message\":\"Could not match the trace.\"}";
+        let error = parse_response(response).unwrap_err().to_string();
+        assert!(error.contains("Could not match the trace."), "{error}");
+    }
+}
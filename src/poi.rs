@@ -0,0 +1,201 @@
+//! Points of interest along a trail, such as parking and viewpoints, as GPX waypoints with
+//! symbols that GPS devices know how to draw.
+
+use crate::{find_in_json, Error};
+use geo_types::Point;
+use gpx::Waypoint;
+use serde_json::Value;
+use std::fs;
+
+/// Garmin's names for the symbols of common trail features, which most other devices and
+/// apps understand too.
+const DEFAULT_SYMBOLS: &[(&str, &str)] = &[
+    ("parking", "Parking Area"),
+    ("trailhead", "Trail Head"),
+    ("viewpoint", "Scenic Area"),
+    ("view", "Scenic Area"),
+    ("overlook", "Scenic Area"),
+    ("water", "Drinking Water"),
+    ("spring", "Drinking Water"),
+    ("summit", "Summit"),
+    ("peak", "Summit"),
+    ("restroom", "Restroom"),
+    ("toilet", "Restroom"),
+    ("campsite", "Campground"),
+    ("camp", "Campground"),
+    ("picnic", "Picnic Area"),
+    ("shelter", "Lodge"),
+    ("bridge", "Bridge"),
+];
+
+/// Maps kinds of point of interest, such as "parking", to GPX symbol names.
+#[derive(Debug, Clone)]
+pub struct SymbolMap {
+    /// Earlier entries win, so overrides go at the front.
+    entries: Vec<(String, String)>,
+}
+
+impl Default for SymbolMap {
+    fn default() -> Self {
+        Self {
+            entries: DEFAULT_SYMBOLS
+                .iter()
+                .map(|&(kind, symbol)| (kind.to_string(), symbol.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SymbolMap {
+    /// Loads overrides from a file of `kind = symbol` lines, on top of the defaults. Blank
+    /// lines and lines starting with `#` are ignored.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::FileError {
+            path: path.to_string(),
+            source,
+        })?;
+        let mut overrides = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (kind, symbol) = line
+                .split_once('=')
+                .map(|(kind, symbol)| (kind.trim(), symbol.trim()))
+                .filter(|(kind, symbol)| !kind.is_empty() && !symbol.is_empty())
+                .ok_or_else(|| Error::InvalidSymbolMap {
+                    path: path.to_string(),
+                    line: i + 1,
+                })?;
+            overrides.push((kind.to_lowercase(), symbol.to_string()));
+        }
+
+        let mut map = Self::default();
+        map.entries.splice(0..0, overrides);
+        Ok(map)
+    }
+
+    /// The symbol for a kind of point of interest, ignoring case. Failing an exact match, the
+    /// first word with a symbol is used, so "Upper parking lot" gets the one for "parking".
+    pub fn symbol(&self, text: &str) -> Option<&str> {
+        let text = text.to_lowercase();
+        if let Some((_, symbol)) = self.entries.iter().find(|(kind, _)| *kind == text) {
+            return Some(symbol);
+        }
+        text.split(|c: char| !c.is_alphanumeric()).find_map(|word| {
+            self.entries
+                .iter()
+                .find(|(kind, _)| kind == word)
+                .map(|(_, symbol)| symbol.as_str())
+        })
+    }
+
+    /// Fills in the waypoint's symbol from its type, or failing that its name, unless it
+    /// already has one.
+    pub fn apply(&self, waypoint: &mut Waypoint) {
+        if waypoint.symbol.is_some() {
+            return;
+        }
+        let symbol = [&waypoint.type_, &waypoint.name]
+            .into_iter()
+            .flatten()
+            .find_map(|text| self.symbol(text));
+        waypoint.symbol = symbol.map(str::to_string);
+    }
+}
+
+/// Reads the waypoints AllTrails has marked on the trail's map. Ones without a usable
+/// location are skipped, since a waypoint is no use without one.
+pub(crate) fn extract_waypoints(json: &Value) -> Vec<Waypoint> {
+    raw_waypoints(json)
+        .iter()
+        .filter_map(|poi| {
+            let number = |pointer: &str| poi.pointer(pointer).and_then(Value::as_f64);
+            let mut waypoint = Waypoint::new(location(poi)?);
+            waypoint.elevation = number("/location/elevation");
+            waypoint.name = text(poi, "/name");
+            waypoint.description = text(poi, "/description");
+            waypoint.type_ = text(poi, "/type").or_else(|| text(poi, "/type/name"));
+            Some(waypoint)
+        })
+        .collect()
+}
+
+/// The names of the waypoints [`extract_waypoints`] skips for want of a usable location, with
+/// `None` for those without a name either.
+pub(crate) fn unlocated_waypoints(json: &Value) -> Vec<Option<String>> {
+    raw_waypoints(json)
+        .iter()
+        .filter(|poi| location(poi).is_none())
+        .map(|poi| text(poi, "/name"))
+        .collect()
+}
+
+fn raw_waypoints(json: &Value) -> &[Value] {
+    find_in_json(
+        json,
+        &["/trails/0/defaultMap/waypoints", "/maps/0/waypoints"],
+    )
+    .and_then(Value::as_array)
+    .map_or(&[], Vec::as_slice)
+}
+
+fn location(poi: &Value) -> Option<Point> {
+    let number = |pointer: &str| poi.pointer(pointer).and_then(Value::as_f64);
+    let (latitude, longitude) = (
+        number("/location/latitude")?,
+        number("/location/longitude")?,
+    );
+    (latitude.abs() <= 90.0 && longitude.abs() <= 180.0).then(|| Point::new(longitude, latitude))
+}
+
+fn text(poi: &Value, pointer: &str) -> Option<String> {
+    poi.pointer(pointer)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_symbol_lookup() {
+        let symbols = SymbolMap::default();
+        assert_eq!(symbols.symbol("Parking"), Some("Parking Area"));
+        assert_eq!(symbols.symbol("Upper parking lot"), Some("Parking Area"));
+        assert_eq!(symbols.symbol("Waterfall"), None);
+
+        let mut waypoint = Waypoint::new(Point::new(0.0, 0.0));
+        waypoint.name = Some("Cheddar Gorge".to_string());
+        waypoint.type_ = Some("viewpoint".to_string());
+        symbols.apply(&mut waypoint);
+        assert_eq!(waypoint.symbol.as_deref(), Some("Scenic Area"));
+    }
+
+    #[test]
+    fn test_extract_waypoints() {
+        let json = json!({
+            "maps": [{
+                "waypoints": [
+                    {
+                        "name": "Car park",
+                        "type": "parking",
+                        "location": { "latitude": 51.45, "longitude": -2.6, "elevation": 20.0 }
+                    },
+                    { "name": "Nowhere" }
+                ]
+            }]
+        });
+        let waypoints = extract_waypoints(&json);
+        assert_eq!(waypoints.len(), 1);
+        assert_eq!(waypoints[0].point(), Point::new(-2.6, 51.45));
+        assert_eq!(waypoints[0].type_.as_deref(), Some("parking"));
+        assert_eq!(waypoints[0].elevation, Some(20.0));
+        assert_eq!(unlocated_waypoints(&json), [Some("Nowhere".to_string())]);
+    }
+}
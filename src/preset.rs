@@ -0,0 +1,69 @@
+//! Presets that set up the output for a particular app or device, so that people don't have
+//! to learn which combination of options each one needs.
+
+use crate::color::TrackColor;
+use crate::Options;
+
+/// Most Garmin units can't show tracks with more points than this.
+pub const GARMIN_MAX_POINTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Preset {
+    /// Garmin devices: tracks are joined into one segment, which they handle better, and
+    /// split into files of at most 10,000 points, the most many units can show.
+    Garmin,
+
+    /// Locus Map: each trail gets its own color, so that several can be told apart.
+    Locus,
+
+    /// OruxMaps: each trail gets its own color, so that several can be told apart.
+    Orux,
+
+    /// Apple Fitness, through the apps that import workouts into it: every point needs a
+    /// time, and the route should be a single segment.
+    Apple,
+}
+
+impl Preset {
+    /// Fills in the options the preset implies. Options taking a value are left alone if they
+    /// were already set, but flags are always turned on, since off is also what they are when
+    /// nobody set them.
+    pub fn apply(self, options: &mut Options) {
+        match self {
+            Preset::Garmin => {
+                options.merge_segments = true;
+                options.max_points_per_file.get_or_insert(GARMIN_MAX_POINTS);
+            }
+            Preset::Locus | Preset::Orux => {
+                options.color.get_or_insert(TrackColor::Auto);
+            }
+            Preset::Apple => {
+                options.synthetic_times = true;
+                options.merge_segments = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Rgb;
+
+    #[test]
+    fn test_apply() {
+        let mut options = Options::default();
+        Preset::Garmin.apply(&mut options);
+        assert!(options.merge_segments);
+        assert_eq!(options.max_points_per_file, Some(GARMIN_MAX_POINTS));
+
+        let red = TrackColor::Fixed(Rgb(255, 0, 0));
+        let mut options = Options {
+            color: Some(red),
+            ..Default::default()
+        };
+        Preset::Locus.apply(&mut options);
+        assert_eq!(options.color, Some(red));
+    }
+}
@@ -0,0 +1,175 @@
+//! Elevation profiles: how high the route is at each point along it.
+
+use crate::geo::haversine_distance;
+use crate::svg::nice_length;
+use crate::units::Units;
+use gpx::Track;
+use std::io::{self, Write};
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 300.0;
+/// Room on the left and bottom for axis labels.
+const LEFT: f64 = 60.0;
+const BOTTOM: f64 = 40.0;
+const PADDING: f64 = 15.0;
+
+/// Pairs the distance along the track with the elevation, both in meters, for each point that
+/// has an elevation. Distance doesn't accumulate across gaps between segments.
+pub fn elevation_profile(track: &Track) -> Vec<(f64, f64)> {
+    let mut profile = Vec::new();
+    let mut distance = 0.0;
+    for segment in &track.segments {
+        for pair in segment.points.windows(2) {
+            if profile.is_empty() {
+                // Covers the first point of the track, which has no predecessor.
+                profile.extend(pair[0].elevation.map(|e| (distance, e)));
+            }
+            distance += haversine_distance(pair[0].point().0, pair[1].point().0);
+            profile.extend(pair[1].elevation.map(|e| (distance, e)));
+        }
+    }
+    profile
+}
+
+/// Draws the profile as a filled distance against elevation chart, labelled in `units`.
+pub fn write_profile_svg(
+    profile: &[(f64, f64)],
+    units: Units,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let max_distance = profile.last().map_or(0.0, |&(d, _)| d).max(1.0);
+    let (low, high) = profile.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(low, high), &(_, e)| (low.min(e), high.max(e)),
+    );
+    // Keep flat routes from being drawn as wild swings by showing at least 50m of range.
+    let (low, high) = if profile.is_empty() {
+        (0.0, 50.0)
+    } else {
+        let middle = (low + high) / 2.0;
+        (low.min(middle - 25.0), high.max(middle + 25.0))
+    };
+
+    let plot_width = WIDTH - LEFT - PADDING;
+    let plot_height = HEIGHT - BOTTOM - PADDING;
+    let x = |d: f64| LEFT + d / max_distance * plot_width;
+    let y = |e: f64| PADDING + (high - e) / (high - low) * plot_height;
+    let baseline = y(low);
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="11">"#
+    )?;
+    writeln!(
+        writer,
+        r##"  <rect width="100%" height="100%" fill="#fbfaf7"/>"##
+    )?;
+
+    let (per_distance, distance_symbol) = units.distance_unit();
+    let step = nice_length(max_distance / per_distance / 4.0);
+    for tick in ticks(0.0, max_distance / per_distance, step) {
+        writeln!(
+            writer,
+            r##"  <line x1="{x:.1}" y1="{PADDING}" x2="{x:.1}" y2="{baseline:.1}" stroke="#dddddd"/><text x="{x:.1}" y="{:.1}" text-anchor="middle">{tick} {distance_symbol}</text>"##,
+            baseline + 16.0,
+            x = x(tick * per_distance),
+        )?;
+    }
+
+    let (per_height, height_symbol) = units.height_unit();
+    let (low_units, high_units) = (low / per_height, high / per_height);
+    let step = nice_length((high_units - low_units) / 4.0);
+    for tick in ticks((low_units / step).ceil() * step, high_units, step) {
+        writeln!(
+            writer,
+            r##"  <line x1="{LEFT}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="#dddddd"/><text x="{:.1}" y="{:.1}" text-anchor="end">{tick} {height_symbol}</text>"##,
+            WIDTH - PADDING,
+            LEFT - 6.0,
+            y(tick * per_height) + 4.0,
+            y = y(tick * per_height),
+        )?;
+    }
+
+    if let (Some(&(first, _)), Some(&(last, _))) = (profile.first(), profile.last()) {
+        let points: Vec<String> = profile
+            .iter()
+            .map(|&(d, e)| format!("{:.1},{:.1}", x(d), y(e)))
+            .collect();
+        writeln!(
+            writer,
+            r##"  <polygon points="{:.1},{baseline:.1} {} {:.1},{baseline:.1}" fill="#d63e2a" fill-opacity="0.25" stroke="none"/>"##,
+            x(first),
+            points.join(" "),
+            x(last),
+        )?;
+        writeln!(
+            writer,
+            r##"  <polyline points="{}" fill="none" stroke="#d63e2a" stroke-width="2"/>"##,
+            points.join(" ")
+        )?;
+    }
+
+    writeln!(writer, "</svg>")?;
+    writer.flush()
+}
+
+/// Evenly spaced values from `start` up to `end`, rounded so that labels don't pick up
+/// floating point noise like 0.6000000000000001.
+fn ticks(start: f64, end: f64, step: f64) -> impl Iterator<Item = f64> {
+    (0..)
+        .map(move |i| ((start + f64::from(i) * step) * 1e6).round() / 1e6)
+        .take_while(move |&tick| tick <= end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::{TrackSegment, Waypoint};
+
+    fn point(lon: f64, elevation: Option<f64>) -> Waypoint {
+        let mut waypoint = Waypoint::new(Point::new(lon, 0.0));
+        waypoint.elevation = elevation;
+        waypoint
+    }
+
+    #[test]
+    fn test_profile_skips_points_without_elevation() {
+        let track = Track {
+            segments: vec![TrackSegment {
+                points: vec![
+                    point(0.0, Some(10.0)),
+                    point(0.01, None),
+                    point(0.02, Some(30.0)),
+                ],
+            }],
+            ..Default::default()
+        };
+
+        let profile = elevation_profile(&track);
+        assert_eq!(profile.len(), 2);
+        assert_eq!(profile[0], (0.0, 10.0));
+        // 0.02 degrees of longitude at the equator is about 2.2km.
+        assert!((profile[1].0 - 2224.0).abs() < 5.0);
+        assert_eq!(profile[1].1, 30.0);
+    }
+
+    #[test]
+    fn test_profile_svg_has_axes_and_line() {
+        let profile = [(0.0, 100.0), (500.0, 180.0), (1000.0, 120.0)];
+        let mut output = Vec::new();
+        write_profile_svg(&profile, Units::Metric, &mut output).unwrap();
+        let svg = String::from_utf8(output).unwrap();
+
+        assert!(svg.contains("<polyline"));
+        assert!(svg.contains(">0 km</text>"));
+        assert!(svg.contains(">100 m</text>"));
+        assert!(svg.contains(">0.6 km</text>"));
+
+        let mut output = Vec::new();
+        write_profile_svg(&profile, Units::Imperial, &mut output).unwrap();
+        let svg = String::from_utf8(output).unwrap();
+        assert!(svg.contains(">0.6 mi</text>"));
+        assert!(svg.contains(">400 ft</text>"));
+    }
+}
@@ -0,0 +1,85 @@
+//! Where a converted route came from, written into the GPX track's extensions so that a file
+//! can always be traced back to its origin.
+
+use crate::builder::escape;
+use std::fmt::Write;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+pub const PROVENANCE_PREFIX: &str = "alltrailsgpx";
+pub const PROVENANCE_NAMESPACE: &str = "https://github.com/cdown/alltrailsgpx/provenance/v1";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// The trail's ID on AllTrails.
+    pub trail_id: Option<u64>,
+    /// The trail's page on AllTrails.
+    pub link: Option<String>,
+    /// When the route was converted, which for a freshly saved response is about when it was
+    /// retrieved. Left out of deterministic output.
+    pub converted: Option<OffsetDateTime>,
+    /// The version of alltrailsgpx that converted it.
+    pub version: String,
+}
+
+impl Provenance {
+    /// Provenance for a route being converted now by this version of alltrailsgpx.
+    pub fn now(trail_id: Option<u64>, link: Option<String>, deterministic: bool) -> Self {
+        Self {
+            trail_id,
+            link,
+            converted: (!deterministic).then(OffsetDateTime::now_utc),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// The provenance as an element for a track's extensions.
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!("<{PROVENANCE_PREFIX}:provenance>\n");
+        let mut element = |name: &str, value: &str| {
+            let _ = writeln!(
+                xml,
+                "  <{PROVENANCE_PREFIX}:{name}>{}</{PROVENANCE_PREFIX}:{name}>",
+                escape(value)
+            );
+        };
+        if let Some(trail_id) = self.trail_id {
+            element("trailId", &trail_id.to_string());
+        }
+        if let Some(link) = &self.link {
+            element("link", link);
+        }
+        if let Some(converted) = self.converted.and_then(|time| time.format(&Rfc3339).ok()) {
+            element("converted", &converted);
+        }
+        element("version", &self.version);
+        xml.push_str(&format!("</{PROVENANCE_PREFIX}:provenance>"));
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_xml() {
+        let provenance = Provenance {
+            trail_id: Some(10_236_086),
+            link: Some("https://www.alltrails.com/trail/a?b=1&c=2".to_string()),
+            converted: Some(OffsetDateTime::UNIX_EPOCH),
+            version: "0.2.0".to_string(),
+        };
+        assert_eq!(
+            provenance.to_xml(),
+            "<alltrailsgpx:provenance>\n\
+             \x20 <alltrailsgpx:trailId>10236086</alltrailsgpx:trailId>\n\
+             \x20 <alltrailsgpx:link>https://www.alltrails.com/trail/a?b=1&amp;c=2</alltrailsgpx:link>\n\
+             \x20 <alltrailsgpx:converted>1970-01-01T00:00:00Z</alltrailsgpx:converted>\n\
+             \x20 <alltrailsgpx:version>0.2.0</alltrailsgpx:version>\n\
+             </alltrailsgpx:provenance>"
+        );
+
+        assert!(Provenance::now(None, None, true).converted.is_none());
+    }
+}
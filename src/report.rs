@@ -0,0 +1,127 @@
+//! A JSON report of a run, for pipelines that convert trails unattended and need to know
+//! what happened without parsing log messages.
+
+use crate::Conversion;
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+/// Describes a run that converted each of `converted`, written where it says, and failed to
+/// convert each of `failed`, given as the input along with why it failed.
+pub fn report(converted: &[(String, Conversion)], failed: &[(String, String)]) -> Value {
+    let points: usize = converted.iter().map(|(_, c)| c.stats.points).sum();
+    let distance_m: f64 = converted.iter().map(|(_, c)| c.stats.distance_m).sum();
+    json!({
+        "processed": converted.len() + failed.len(),
+        "succeeded": converted.len(),
+        "failed": failed.len(),
+        "points": points,
+        "distance_m": distance_m,
+        "conversions": converted
+            .iter()
+            .map(|(written_to, conversion)| {
+                json!({
+                    "name": conversion.name,
+                    "output": written_to,
+                    "points": conversion.stats.points,
+                    "distance_m": conversion.stats.distance_m,
+                    "warnings": conversion
+                        .warnings
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>(),
+        "failures": failed
+            .iter()
+            .map(|(input, error)| json!({ "input": input, "error": error }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Writes the report of a run as pretty-printed JSON.
+pub fn write_report(
+    converted: &[(String, Conversion)],
+    failed: &[(String, String)],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, &report(converted, failed))?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Writes a table of a batch run for people to read: a row per input saying whether it
+/// converted and where to or why not, followed by the totals.
+pub fn write_table(
+    converted: &[(String, Conversion)],
+    failed: &[(String, String)],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let rows: Vec<(&str, &str, &str)> = converted
+        .iter()
+        .map(|(written_to, conversion)| ("ok", conversion.name.as_str(), written_to.as_str()))
+        .chain(
+            failed
+                .iter()
+                .map(|(input, error)| ("failed", input.as_str(), error.as_str())),
+        )
+        .collect();
+    let width = rows.iter().map(|(_, name, _)| name.chars().count()).max();
+    let width = width.unwrap_or(0).max("INPUT".len());
+
+    writeln!(writer, "{:<6}  {:<width$}  DETAILS", "STATUS", "INPUT")?;
+    for (status, name, details) in rows {
+        writeln!(writer, "{status:<6}  {name:<width$}  {details}")?;
+    }
+    writeln!(
+        writer,
+        "{} converted, {} failed",
+        converted.len(),
+        failed.len()
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert, Options};
+
+    #[test]
+    fn test_report() {
+        let json = json!({
+            "trails": [{
+                "name": "Abbots Leigh",
+                "defaultMap": {
+                    "routes": [{
+                        "lineSegments": [{ "polyline": { "pointsData": "_p~iF~ps|U_ulLnnqC" } }]
+                    }]
+                }
+            }]
+        });
+        let conversion = convert(json.to_string().as_bytes(), &Options::default()).unwrap();
+        let failed = [(
+            "broken.json".to_string(),
+            "Failed to parse JSON input".to_string(),
+        )];
+        let converted = [("out/abbots.gpx".to_string(), conversion)];
+        let report = report(&converted, &failed);
+
+        assert_eq!(report["processed"], 2);
+        assert_eq!(report["succeeded"], 1);
+        assert_eq!(report["failed"], 1);
+        assert_eq!(report["points"], 2);
+        assert_eq!(report["conversions"][0]["output"], "out/abbots.gpx");
+        assert_eq!(report["failures"][0]["input"], "broken.json");
+
+        let mut table = Vec::new();
+        write_table(&converted, &failed, &mut table).unwrap();
+        assert_eq!(
+            String::from_utf8(table).unwrap(),
+            "STATUS  INPUT         DETAILS\n\
+             ok      Abbots Leigh  out/abbots.gpx\n\
+             failed  broken.json   Failed to parse JSON input\n\
+             1 converted, 1 failed\n"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Catching routes that can't be what they claim to be, as happens when a polyline is
+//! corrupted or decoded at the wrong precision.
+
+use crate::geo::{haversine_distance, Located};
+use geo_types::Coord;
+use std::fmt;
+
+/// Longer than any trail on AllTrails, the longest of which are a few thousand kilometers.
+const MAX_LENGTH_M: f64 = 10_000_000.0;
+
+/// Further than any gap between consecutive points of a real route.
+const MAX_JUMP_M: f64 = 500_000.0;
+
+/// Something about a route that means it's almost certainly broken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    TooLong {
+        length_m: f64,
+    },
+    Jump {
+        from: Coord<f64>,
+        to: Coord<f64>,
+        distance_m: f64,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::TooLong { length_m } => {
+                write!(f, "the route is {:.0} km long", length_m / 1000.0)
+            }
+            Problem::Jump {
+                from,
+                to,
+                distance_m,
+            } => write!(
+                f,
+                "consecutive points at {},{} and {},{} are {:.0} km apart",
+                from.y,
+                from.x,
+                to.y,
+                to.x,
+                distance_m / 1000.0
+            ),
+        }
+    }
+}
+
+/// Looks for signs that the route is corrupted. Only the first jump is reported, since a
+/// corrupted polyline tends to have them everywhere after it.
+pub fn check<P: Located>(lines: &[Vec<P>]) -> Vec<Problem> {
+    let mut checker = Checker::default();
+    for line in lines {
+        checker.start_line();
+        for point in line {
+            checker.push(point.coord());
+        }
+    }
+    checker.finish()
+}
+
+/// [`check`], a point at a time, for routes that aren't held in memory.
+#[derive(Debug, Default)]
+pub(crate) struct Checker {
+    length_m: f64,
+    last: Option<Coord<f64>>,
+    jump: Option<Problem>,
+}
+
+impl Checker {
+    /// Starts a new line, which isn't joined to the end of the last.
+    pub(crate) fn start_line(&mut self) {
+        self.last = None;
+    }
+
+    pub(crate) fn push(&mut self, coord: Coord<f64>) {
+        if let Some(last) = self.last.replace(coord) {
+            let distance_m = haversine_distance(last, coord);
+            self.length_m += distance_m;
+            if self.jump.is_none() && distance_m > MAX_JUMP_M {
+                self.jump = Some(Problem::Jump {
+                    from: last,
+                    to: coord,
+                    distance_m,
+                });
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if self.length_m > MAX_LENGTH_M {
+            problems.push(Problem::TooLong {
+                length_m: self.length_m,
+            });
+        }
+        problems.extend(self.jump);
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::LineString;
+
+    #[test]
+    fn test_check() {
+        let hike = LineString::from(vec![(-2.6, 51.45), (-2.61, 51.46), (-2.62, 51.45)]);
+        assert_eq!(check(&[hike.0]), Vec::new());
+
+        // Bristol, then New York.
+        let broken = LineString::from(vec![(-2.6, 51.45), (-2.61, 51.46), (-74.0, 40.7)]);
+        let problems = check(&[broken.0]);
+        assert_eq!(problems.len(), 1);
+        assert!(
+            matches!(problems[0], Problem::Jump { distance_m, .. } if distance_m > 5_000_000.0)
+        );
+        assert!(problems[0]
+            .to_string()
+            .starts_with("consecutive points at 51.46,-2.61"));
+    }
+}
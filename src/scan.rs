@@ -0,0 +1,163 @@
+//! Mining documents of unfamiliar shapes for geometry, by trying every long enough string as
+//! an encoded polyline and scoring how much the result looks like a route.
+
+use crate::decode::decode_polyline;
+use crate::geo::{self, haversine_distance, path_length};
+use crate::lookup::JsonPointer;
+use crate::{json, sanity, Error, POLYLINE_PRECISION};
+use serde_json::Value;
+use std::fmt;
+use std::io::Read;
+
+/// Shorter strings decode to too few points to tell a route from an ID or a word.
+const MIN_STRING_LENGTH: usize = 16;
+
+/// Routes with at least this many points aren't marked down for having few.
+const FULL_SCORE_POINTS: usize = 50;
+
+/// A string that decodes to something that could be a route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub pointer: JsonPointer,
+    pub points: usize,
+    pub length_m: f64,
+    /// Whether the coordinates only make sense with their axes swapped.
+    pub swapped_latlon: bool,
+    /// How much the decoded points look like a route, from 0 to 1.
+    pub score: f64,
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.2} {}: {} points, {:.1} km",
+            self.score,
+            self.pointer.as_str(),
+            self.points,
+            self.length_m / 1000.0
+        )?;
+        if self.swapped_latlon {
+            write!(f, ", axes swapped")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a JSON document and lists the strings in it that decode to plausible routes, most
+/// plausible first.
+pub fn scan(mut reader: impl Read) -> Result<Vec<Candidate>, Error> {
+    let mut input = Vec::new();
+    reader
+        .read_to_end(&mut input)
+        .map_err(Error::InputReadError)?;
+    let json = json::parse(&input)?;
+    Ok(candidates(&json))
+}
+
+/// Lists the strings in `json` that decode to plausible routes, most plausible first.
+pub fn candidates(json: &Value) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    collect(json, &JsonPointer::new(""), &mut candidates);
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates
+}
+
+fn collect(json: &Value, pointer: &JsonPointer, candidates: &mut Vec<Candidate>) {
+    match json {
+        Value::String(text) => candidates.extend(candidate(text, pointer)),
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                collect(item, &pointer.join(i), candidates);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                collect(value, &pointer.join(key), candidates);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn candidate(text: &str, pointer: &JsonPointer) -> Option<Candidate> {
+    if text.len() < MIN_STRING_LENGTH {
+        return None;
+    }
+    let mut coords = decode_polyline(text, POLYLINE_PRECISION).ok()?;
+    let swapped_latlon = geo::axes_look_swapped(coords.iter().copied(), None);
+    if swapped_latlon {
+        geo::swap_axes(&mut coords);
+    }
+    if coords.len() < 2 || coords.iter().any(|c| c.y.abs() > 90.0 || c.x.abs() > 180.0) {
+        return None;
+    }
+
+    let length_m = path_length(&coords);
+    if length_m == 0.0 {
+        return None;
+    }
+
+    // Real routes have plenty of points, a few meters to a few kilometers apart, and none of
+    // the problems of a corrupted polyline. Text that happens to decode usually has none of
+    // these.
+    let points_score = (coords.len() as f64 / FULL_SCORE_POINTS as f64).min(1.0);
+    let mut steps: Vec<f64> = coords
+        .windows(2)
+        .map(|pair| haversine_distance(pair[0], pair[1]))
+        .collect();
+    steps.sort_by(f64::total_cmp);
+    let median_step = steps[steps.len() / 2];
+    let step_score = if (0.5..=5_000.0).contains(&median_step) {
+        1.0
+    } else {
+        0.3
+    };
+    let sanity_score = if sanity::check(&[coords.clone()]).is_empty() {
+        1.0
+    } else {
+        0.1
+    };
+
+    Some(Candidate {
+        pointer: pointer.clone(),
+        points: coords.len(),
+        length_m,
+        swapped_latlon,
+        score: points_score * step_score * sanity_score,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Coord;
+    use serde_json::json;
+
+    #[test]
+    fn test_candidates() {
+        let route: Vec<Coord<f64>> = (0..60)
+            .map(|i| Coord {
+                x: -2.6 + f64::from(i) * 0.0005,
+                y: 51.45 + f64::from(i) * 0.0003,
+            })
+            .collect();
+        let encoded = polyline::encode_coordinates(route, POLYLINE_PRECISION).unwrap();
+        let json = json!({
+            "payload": {
+                "title": "Somewhere along the river",
+                "shape": { "enc": encoded },
+                "token": "abcdefghijklmnopqrstuvwxyz",
+            }
+        });
+
+        let candidates = candidates(&json);
+        assert_eq!(candidates[0].pointer.as_str(), "/payload/shape/enc");
+        assert_eq!(candidates[0].points, 60);
+        assert!(candidates[0].score > 0.9);
+        assert!(candidates
+            .iter()
+            .all(|c| c.pointer.as_str() != "/payload/title"));
+        assert!(candidates.iter().skip(1).all(|c| c.score < 0.5));
+    }
+}
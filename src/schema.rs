@@ -0,0 +1,474 @@
+//! Checking GPX against the GPX 1.1 schema, to catch output that picky importers would
+//! refuse before it reaches them.
+//!
+//! The content model is transcribed from <https://www.topografix.com/GPX/1/1/gpx.xsd>: which
+//! elements each element may contain and in what order, which attributes it must have, and
+//! what values they and the text of simple elements may take. Every element in the schema
+//! is optional, and extensions may hold anything.
+
+use crate::builder::GPX_NAMESPACE;
+use crate::xml::{self, Token};
+
+/// The values the schema allows in an attribute or the text of an element.
+#[derive(Debug, Clone, Copy)]
+enum Simple {
+    String,
+    Decimal,
+    DateTime,
+    NonNegativeInteger,
+    Latitude,
+    Longitude,
+    Degrees,
+    Fix,
+    DgpsStation,
+    Year,
+    Version,
+}
+
+#[derive(Debug)]
+enum Content {
+    /// These elements, in this order.
+    Sequence(&'static [Particle]),
+    Text(Simple),
+    /// Anything at all, as in extensions.
+    Any,
+}
+
+#[derive(Debug)]
+struct Kind {
+    /// The attributes, all of which are required.
+    attributes: &'static [(&'static str, Simple)],
+    content: Content,
+}
+
+#[derive(Debug)]
+struct Particle {
+    name: &'static str,
+    kind: Kind,
+    repeats: bool,
+}
+
+const fn text(simple: Simple) -> Kind {
+    Kind {
+        attributes: &[],
+        content: Content::Text(simple),
+    }
+}
+
+const fn one(name: &'static str, kind: Kind) -> Particle {
+    Particle {
+        name,
+        kind,
+        repeats: false,
+    }
+}
+
+const fn many(name: &'static str, kind: Kind) -> Particle {
+    Particle {
+        name,
+        kind,
+        repeats: true,
+    }
+}
+
+const STRING: Kind = text(Simple::String);
+
+const EXTENSIONS: Kind = Kind {
+    attributes: &[],
+    content: Content::Any,
+};
+
+const LINK: Kind = Kind {
+    attributes: &[("href", Simple::String)],
+    content: Content::Sequence(&[one("text", STRING), one("type", STRING)]),
+};
+
+const WPT: Kind = Kind {
+    attributes: &[("lat", Simple::Latitude), ("lon", Simple::Longitude)],
+    content: Content::Sequence(&[
+        one("ele", text(Simple::Decimal)),
+        one("time", text(Simple::DateTime)),
+        one("magvar", text(Simple::Degrees)),
+        one("geoidheight", text(Simple::Decimal)),
+        one("name", STRING),
+        one("cmt", STRING),
+        one("desc", STRING),
+        one("src", STRING),
+        many("link", LINK),
+        one("sym", STRING),
+        one("type", STRING),
+        one("fix", text(Simple::Fix)),
+        one("sat", text(Simple::NonNegativeInteger)),
+        one("hdop", text(Simple::Decimal)),
+        one("vdop", text(Simple::Decimal)),
+        one("pdop", text(Simple::Decimal)),
+        one("ageofdgpsdata", text(Simple::Decimal)),
+        one("dgpsid", text(Simple::DgpsStation)),
+        one("extensions", EXTENSIONS),
+    ]),
+};
+
+const PERSON: Kind = Kind {
+    attributes: &[],
+    content: Content::Sequence(&[
+        one("name", STRING),
+        one(
+            "email",
+            Kind {
+                attributes: &[("id", Simple::String), ("domain", Simple::String)],
+                content: Content::Sequence(&[]),
+            },
+        ),
+        one("link", LINK),
+    ]),
+};
+
+const METADATA: Kind = Kind {
+    attributes: &[],
+    content: Content::Sequence(&[
+        one("name", STRING),
+        one("desc", STRING),
+        one("author", PERSON),
+        one(
+            "copyright",
+            Kind {
+                attributes: &[("author", Simple::String)],
+                content: Content::Sequence(&[
+                    one("year", text(Simple::Year)),
+                    one("license", STRING),
+                ]),
+            },
+        ),
+        many("link", LINK),
+        one("time", text(Simple::DateTime)),
+        one("keywords", STRING),
+        one(
+            "bounds",
+            Kind {
+                attributes: &[
+                    ("minlat", Simple::Latitude),
+                    ("minlon", Simple::Longitude),
+                    ("maxlat", Simple::Latitude),
+                    ("maxlon", Simple::Longitude),
+                ],
+                content: Content::Sequence(&[]),
+            },
+        ),
+        one("extensions", EXTENSIONS),
+    ]),
+};
+
+const RTE: Kind = Kind {
+    attributes: &[],
+    content: Content::Sequence(&[
+        one("name", STRING),
+        one("cmt", STRING),
+        one("desc", STRING),
+        one("src", STRING),
+        many("link", LINK),
+        one("number", text(Simple::NonNegativeInteger)),
+        one("type", STRING),
+        one("extensions", EXTENSIONS),
+        many("rtept", WPT),
+    ]),
+};
+
+const TRK: Kind = Kind {
+    attributes: &[],
+    content: Content::Sequence(&[
+        one("name", STRING),
+        one("cmt", STRING),
+        one("desc", STRING),
+        one("src", STRING),
+        many("link", LINK),
+        one("number", text(Simple::NonNegativeInteger)),
+        one("type", STRING),
+        one("extensions", EXTENSIONS),
+        many(
+            "trkseg",
+            Kind {
+                attributes: &[],
+                content: Content::Sequence(&[many("trkpt", WPT), one("extensions", EXTENSIONS)]),
+            },
+        ),
+    ]),
+};
+
+static GPX: Kind = Kind {
+    attributes: &[("version", Simple::Version), ("creator", Simple::String)],
+    content: Content::Sequence(&[
+        one("metadata", METADATA),
+        many("wpt", WPT),
+        many("rte", RTE),
+        many("trk", TRK),
+        one("extensions", EXTENSIONS),
+    ]),
+};
+
+/// An element being read, and how far through its content it is.
+struct Open<'a> {
+    name: &'a str,
+    kind: &'static Kind,
+    /// Which of the kind's particles the last child matched, and how many times.
+    position: usize,
+    count: usize,
+    text: String,
+}
+
+/// Checks a GPX document against the GPX 1.1 schema, describing the first problem found.
+pub fn validate_gpx(gpx: &str) -> Result<(), String> {
+    let mut tokens = xml::tokens(gpx);
+    let mut open: Vec<Open> = Vec::new();
+    // How deep inside the content of an element that may hold anything.
+    let mut skipping = 0;
+    let mut seen_root = false;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Start(_) if skipping > 0 => skipping += 1,
+            Token::End(_) if skipping > 0 => skipping -= 1,
+            Token::Start(name) => {
+                let kind = match open.last_mut() {
+                    None if seen_root => return Err(format!("<{name}> is outside <gpx>")),
+                    None if name != "gpx" => {
+                        return Err(format!("the root element is <{name}>, not <gpx>"))
+                    }
+                    None => &GPX,
+                    Some(parent) => match parent.kind.content {
+                        Content::Any => {
+                            skipping = 1;
+                            continue;
+                        }
+                        Content::Text(_) => {
+                            return Err(format!("<{}> can't contain <{name}>", parent.name))
+                        }
+                        Content::Sequence(particles) => child(parent, particles, name)?,
+                    },
+                };
+                let attributes = tokens.attributes();
+                if open.is_empty() && !attributes.contains(&("xmlns", GPX_NAMESPACE.to_string())) {
+                    return Err("<gpx> isn't in the GPX 1.1 namespace".to_string());
+                }
+                check_attributes(name, kind, &attributes)?;
+                seen_root = true;
+                open.push(Open {
+                    name,
+                    kind,
+                    position: 0,
+                    count: 0,
+                    text: String::new(),
+                });
+            }
+            Token::End(_) => {
+                let Some(element) = open.pop() else {
+                    return Err("the document has an end tag without a start".to_string());
+                };
+                if let Content::Text(simple) = element.kind.content {
+                    if !is_valid(simple, &element.text) {
+                        return Err(format!(
+                            "<{}> has an invalid value: {:?}",
+                            element.name, element.text
+                        ));
+                    }
+                }
+            }
+            Token::Text(text) => match open.last_mut() {
+                Some(element) => match element.kind.content {
+                    Content::Text(_) => element.text.push_str(&text),
+                    Content::Sequence(_) if !text.trim().is_empty() => {
+                        return Err(format!("<{}> can't contain text", element.name))
+                    }
+                    _ => {}
+                },
+                None if !text.trim().is_empty() => {
+                    return Err("the document has text outside <gpx>".to_string())
+                }
+                None => {}
+            },
+        }
+    }
+
+    match open.last() {
+        Some(element) => Err(format!("<{}> isn't closed", element.name)),
+        None if !seen_root => Err("the document has no <gpx> element".to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Moves through the parent's content to the child element, returning its kind, or fails if
+/// the child can't come next.
+fn child(
+    parent: &mut Open<'_>,
+    particles: &'static [Particle],
+    name: &str,
+) -> Result<&'static Kind, String> {
+    while let Some(particle) = particles.get(parent.position) {
+        if particle.name == name {
+            parent.count += 1;
+            if parent.count > 1 && !particle.repeats {
+                return Err(format!("<{}> has more than one <{name}>", parent.name));
+            }
+            return Ok(&particle.kind);
+        }
+        parent.position += 1;
+        parent.count = 0;
+    }
+    if particles.iter().any(|particle| particle.name == name) {
+        Err(format!("<{name}> is out of order in <{}>", parent.name))
+    } else {
+        Err(format!("<{}> can't contain <{name}>", parent.name))
+    }
+}
+
+fn check_attributes(name: &str, kind: &Kind, attributes: &[(&str, String)]) -> Result<(), String> {
+    for (attribute, value) in attributes {
+        // Namespace declarations and schema hints can go on any element.
+        if *attribute == "xmlns" || attribute.starts_with("xmlns:") || attribute.starts_with("xsi:")
+        {
+            continue;
+        }
+        let Some(&(_, simple)) = kind.attributes.iter().find(|(a, _)| a == attribute) else {
+            return Err(format!("<{name}> can't have a {attribute} attribute"));
+        };
+        if !is_valid(simple, value) {
+            return Err(format!("<{name}> has an invalid {attribute}: {value:?}"));
+        }
+    }
+    match kind
+        .attributes
+        .iter()
+        .find(|(required, _)| !attributes.iter().any(|(a, _)| a == required))
+    {
+        Some((missing, _)) => Err(format!("<{name}> is missing its {missing} attribute")),
+        None => Ok(()),
+    }
+}
+
+fn is_valid(simple: Simple, value: &str) -> bool {
+    let value = value.trim();
+    let number = || value.parse::<f64>().ok().filter(|_| is_decimal(value));
+    match simple {
+        Simple::String => true,
+        Simple::Decimal => number().is_some(),
+        Simple::Latitude => number().is_some_and(|n| (-90.0..=90.0).contains(&n)),
+        Simple::Longitude => number().is_some_and(|n| (-180.0..180.0).contains(&n)),
+        Simple::Degrees => number().is_some_and(|n| (0.0..360.0).contains(&n)),
+        Simple::NonNegativeInteger => is_digits(value.strip_prefix('+').unwrap_or(value)),
+        Simple::DgpsStation => is_digits(value) && value.parse().is_ok_and(|n: u32| n <= 1023),
+        Simple::Fix => matches!(value, "none" | "2d" | "3d" | "dgps" | "pps"),
+        Simple::Year => {
+            let year = value.strip_prefix('-').unwrap_or(value);
+            year.len() >= 4 && is_digits(year)
+        }
+        Simple::DateTime => is_date_time(value),
+        Simple::Version => value == "1.1",
+    }
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Whether a value is an xsd:decimal: digits with an optional sign and decimal point, but no
+/// exponent.
+fn is_decimal(value: &str) -> bool {
+    let unsigned = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    (!whole.is_empty() || !fraction.is_empty())
+        && (whole.is_empty() || is_digits(whole))
+        && (fraction.is_empty() || is_digits(fraction))
+}
+
+/// Whether a value is an xsd:dateTime, such as 2024-05-01T10:00:00.5Z.
+fn is_date_time(value: &str) -> bool {
+    let Some((date, time)) = value.split_once('T') else {
+        return false;
+    };
+    let date = date.strip_prefix('-').unwrap_or(date);
+    let date_ok = match date.rsplitn(3, '-').collect::<Vec<_>>()[..] {
+        [day, month, year] => {
+            year.len() >= 4
+                && is_digits(year)
+                && is_number_in(month, 1..=12)
+                && is_number_in(day, 1..=31)
+        }
+        _ => false,
+    };
+
+    let (time, zone) = match time.find(['Z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => (time, ""),
+    };
+    let zone_ok = match zone {
+        "" | "Z" => true,
+        zone => match zone[1..].split_once(':') {
+            Some((hours, minutes)) => is_number_in(hours, 0..=14) && is_number_in(minutes, 0..=59),
+            None => false,
+        },
+    };
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let time_ok = match time.split(':').collect::<Vec<_>>()[..] {
+        [hours, minutes, seconds] => {
+            is_number_in(hours, 0..=24)
+                && is_number_in(minutes, 0..=59)
+                && is_number_in(seconds, 0..=60)
+        }
+        _ => false,
+    };
+    date_ok && time_ok && zone_ok && is_digits(fraction)
+}
+
+/// Whether a value is two digits making a number in the range.
+fn is_number_in(value: &str, range: std::ops::RangeInclusive<u32>) -> bool {
+    value.len() == 2 && is_digits(value) && value.parse().is_ok_and(|n| range.contains(&n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gpx(body: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">{body}</gpx>"#
+        )
+    }
+
+    #[test]
+    fn test_valid() {
+        let document = gpx(r#"
+  <metadata><name>Abbots Leigh</name><time>2024-05-01T10:00:00.5Z</time></metadata>
+  <wpt lat="51.45" lon="-2.6"><ele>20</ele><name>Car park</name><sym>Parking Area</sym></wpt>
+  <trk>
+    <name>Abbots Leigh</name>
+    <number>1</number>
+    <extensions><gpxx:TrackExtension xmlns:gpxx="urn:x"><gpxx:DisplayColor>Red</gpxx:DisplayColor></gpxx:TrackExtension></extensions>
+    <trkseg>
+      <trkpt lat="51.45" lon="-2.6"><ele>20.5</ele><time>2024-05-01T10:00:00+01:00</time></trkpt>
+      <trkpt lat="51.46" lon="-2.61"/>
+    </trkseg>
+  </trk>"#);
+        assert_eq!(validate_gpx(&document), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = [
+            r#"<trk><trkseg/><name>Late</name></trk>"#,
+            r#"<trk><name>A</name><name>B</name></trk>"#,
+            r#"<wpt lat="91" lon="0"/>"#,
+            r#"<wpt lat="51.45"/>"#,
+            r#"<wpt lat="51.45" lon="-2.6" colour="red"/>"#,
+            r#"<wpt lat="51.45" lon="-2.6"><ele>1e3</ele></wpt>"#,
+            r#"<wpt lat="51.45" lon="-2.6"><time>yesterday</time></wpt>"#,
+            r#"<trk><trkpt lat="51.45" lon="-2.6"/></trk>"#,
+            r#"<trk><name><b>Bold</b></name></trk>"#,
+            r#"<trk>loose text</trk>"#,
+        ];
+        for body in invalid {
+            assert!(validate_gpx(&gpx(body)).is_err(), "{body}");
+        }
+        assert!(validate_gpx(r#"<gpx version="1.1" creator="test"/>"#).is_err());
+        assert!(validate_gpx("<kml/>").is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use crate::geo::{haversine_distance, Located, EARTH_RADIUS_M};
+use geo_types::Coord;
+
+/// Joins consecutive segments into one. If `max_gap_m` is given, segments further apart than
+/// that are left separate rather than bridged with a straight line.
+pub fn merge<P: Located>(segments: Vec<Vec<P>>, max_gap_m: Option<f64>) -> Vec<Vec<P>> {
+    let mut merged: Vec<Vec<P>> = Vec::with_capacity(segments.len());
+
+    for segment in segments {
+        let Some(previous) = merged.last_mut() else {
+            merged.push(segment);
+            continue;
+        };
+
+        let (Some(end), Some(start)) = (previous.last(), segment.first()) else {
+            // One of them is empty, so there is no gap to speak of.
+            previous.extend(segment);
+            continue;
+        };
+        let (end, start) = (end.coord(), start.coord());
+
+        if max_gap_m.is_some_and(|max| haversine_distance(end, start) > max) {
+            merged.push(segment);
+            continue;
+        }
+
+        // Segments usually share their joining point, which shouldn't be duplicated.
+        let skip = usize::from(end == start);
+        previous.extend(segment.into_iter().skip(skip));
+    }
+
+    merged
+}
+
+/// Starts a new segment wherever consecutive points are more than `max_gap_m` meters apart,
+/// so that the gap isn't drawn as a long straight line.
+pub fn split_at_gaps<P: Located>(segments: Vec<Vec<P>>, max_gap_m: f64) -> Vec<Vec<P>> {
+    let mut split = Vec::with_capacity(segments.len());
+
+    for segment in segments {
+        let mut current: Vec<P> = Vec::new();
+        for point in segment {
+            if current
+                .last()
+                .is_some_and(|last| haversine_distance(last.coord(), point.coord()) > max_gap_m)
+            {
+                split.push(std::mem::take(&mut current));
+            }
+            current.push(point);
+        }
+        split.push(current);
+    }
+
+    split
+}
+
+/// Splits segments into parts of at most `max_points` points each, for devices that limit how
+/// many points a track may have. Where a segment is cut, its last point in one part is
+/// repeated as its first in the next, so that the parts join up. `max_points` must be at
+/// least two.
+pub fn split_by_points<T: Clone>(segments: Vec<Vec<T>>, max_points: usize) -> Vec<Vec<Vec<T>>> {
+    let mut parts = Vec::new();
+    let mut part: Vec<Vec<T>> = Vec::new();
+    let mut count = 0;
+
+    for segment in segments {
+        let mut rest = segment.as_slice();
+        while !rest.is_empty() {
+            let room = max_points - count;
+            if rest.len() <= room {
+                part.push(rest.to_vec());
+                count += rest.len();
+                break;
+            }
+            // A single point can't carry the segment on, so leave it for the next part.
+            if room >= 2 {
+                part.push(rest[..room].to_vec());
+                rest = &rest[room - 1..];
+            }
+            parts.push(std::mem::take(&mut part));
+            count = 0;
+        }
+    }
+    if !part.is_empty() {
+        parts.push(part);
+    }
+    parts
+}
+
+/// Moves each end of the route to a point up to `radius_m` from where it really is, dropping
+/// the points in between, so that the route still starts and finishes somewhere plausible
+/// without pinpointing where. The offsets are derived from the route itself, so fuzzing the
+/// same route again gives the same result rather than a new sample to average out.
+pub fn fuzz_ends<P: Located>(segments: Vec<Vec<P>>, radius_m: f64) -> Vec<Vec<P>> {
+    let mut random = Random::seeded_from(&segments);
+    let fuzzed = fuzz_start(segments, radius_m, &mut random);
+    reversed(fuzz_start(reversed(fuzzed), radius_m, &mut random))
+}
+
+fn fuzz_start<P: Located>(
+    segments: Vec<Vec<P>>,
+    radius_m: f64,
+    random: &mut Random,
+) -> Vec<Vec<P>> {
+    let Some(start) = segments.first().and_then(|segment| segment.first()) else {
+        return segments;
+    };
+    let start = start.coord();
+    let outside = |p: &P| haversine_distance(start, p.coord()) >= radius_m;
+
+    let mut segments = segments
+        .into_iter()
+        .skip_while(|segment| !segment.iter().any(outside));
+    let Some(first) = segments.next() else {
+        // The whole route is within the radius, so there's no middle to keep.
+        return Vec::new();
+    };
+
+    // Anywhere in the circle, uniformly by area.
+    let distance = radius_m * random.next_f64().sqrt();
+    let bearing = random.next_f64() * std::f64::consts::TAU;
+    let mut fuzzed = vec![P::from(offset(start, distance, bearing))];
+    fuzzed.extend(first.into_iter().skip_while(|p| !outside(p)));
+
+    let mut kept = vec![fuzzed];
+    kept.extend(segments);
+    kept
+}
+
+/// Moves a point the given distance along a bearing measured clockwise from north.
+fn offset(c: Coord<f64>, distance_m: f64, bearing: f64) -> Coord<f64> {
+    let angle = (distance_m / EARTH_RADIUS_M).to_degrees();
+    Coord {
+        x: c.x + angle * bearing.sin() / c.y.to_radians().cos(),
+        y: c.y + angle * bearing.cos(),
+    }
+}
+
+/// A small deterministic random number generator (SplitMix64), to avoid a dependency.
+struct Random(u64);
+
+impl Random {
+    fn seeded_from<P: Located>(segments: &[Vec<P>]) -> Self {
+        // FNV-1a over the coordinates.
+        let seed = segments
+            .iter()
+            .flatten()
+            .map(Located::coord)
+            .flat_map(|c| [c.x.to_bits(), c.y.to_bits()])
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, bits| {
+                (hash ^ bits).wrapping_mul(0x0100_0000_01b3)
+            });
+        Self(seed)
+    }
+
+    /// A number in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+/// Reverses the route, both the order of the segments and of the points within them.
+fn reversed<P>(segments: Vec<Vec<P>>) -> Vec<Vec<P>> {
+    segments
+        .into_iter()
+        .rev()
+        .map(|mut segment| {
+            segment.reverse();
+            segment
+        })
+        .collect()
+}
+
+/// Follows the route back to its start, for planning an out and back along a one-way route.
+/// The way back is added as separate segments, in reverse.
+pub fn round_trip<P: Located>(segments: Vec<Vec<P>>) -> Vec<Vec<P>> {
+    let back = reversed(segments.clone());
+    segments.into_iter().chain(back).collect()
+}
+
+/// Removes the first and last `meters` of the route, measured along it, so as not to give away
+/// exactly where it starts and ends. Segments that fall entirely within a trimmed stretch are
+/// dropped, and the cuts are interpolated between points.
+pub fn trim_ends<P: Located>(segments: Vec<Vec<P>>, meters: f64) -> Vec<Vec<P>> {
+    reversed(trim_start(reversed(trim_start(segments, meters)), meters))
+}
+
+fn trim_start<P: Located>(segments: Vec<Vec<P>>, meters: f64) -> Vec<Vec<P>> {
+    let mut remaining = meters;
+    let mut segments = segments.into_iter();
+    let mut kept = Vec::new();
+
+    for points in segments.by_ref() {
+        let mut cut = None;
+        for (i, pair) in points.windows(2).enumerate() {
+            let length = haversine_distance(pair[0].coord(), pair[1].coord());
+            if length > remaining {
+                cut = Some((i, remaining / length));
+                break;
+            }
+            remaining -= length;
+        }
+
+        // Either the whole segment is within the trimmed stretch, or this is where it ends.
+        if let Some((i, fraction)) = cut {
+            let (a, b) = (points[i].coord(), points[i + 1].coord());
+            let start = Coord {
+                x: a.x + (b.x - a.x) * fraction,
+                y: a.y + (b.y - a.y) * fraction,
+            };
+            let mut rest = vec![P::from(start)];
+            rest.extend_from_slice(&points[i + 1..]);
+            kept.push(rest);
+            break;
+        }
+    }
+
+    kept.extend(segments);
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Coord;
+
+    fn line(coords: &[(f64, f64)]) -> Vec<Coord<f64>> {
+        coords.iter().map(|&(x, y)| Coord { x, y }).collect()
+    }
+
+    #[test]
+    fn test_merge_drops_shared_points() {
+        let merged = merge(
+            vec![
+                line(&[(0.0, 51.0), (0.0, 51.001)]),
+                line(&[(0.0, 51.001), (0.0, 51.002)]),
+            ],
+            None,
+        );
+        assert_eq!(
+            merged,
+            vec![line(&[(0.0, 51.0), (0.0, 51.001), (0.0, 51.002)])]
+        );
+    }
+
+    #[test]
+    fn test_merge_respects_max_gap() {
+        let segments = vec![
+            line(&[(0.0, 51.0), (0.0, 51.001)]),
+            line(&[(0.0, 51.0011), (0.0, 51.002)]),
+            line(&[(0.0, 52.0), (0.0, 52.001)]),
+        ];
+        let merged = merge(segments, Some(50.0));
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].len(), 4);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let there = vec![
+            line(&[(0.0, 51.0), (0.0, 51.001)]),
+            line(&[(0.0, 51.002), (0.0, 51.003)]),
+        ];
+        assert_eq!(
+            round_trip(there),
+            vec![
+                line(&[(0.0, 51.0), (0.0, 51.001)]),
+                line(&[(0.0, 51.002), (0.0, 51.003)]),
+                line(&[(0.0, 51.003), (0.0, 51.002)]),
+                line(&[(0.0, 51.001), (0.0, 51.0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_at_gaps() {
+        let split = split_at_gaps(
+            vec![line(&[
+                (0.0, 51.0),
+                (0.0, 51.001),
+                (0.0, 52.0),
+                (0.0, 52.001),
+            ])],
+            1000.0,
+        );
+        assert_eq!(
+            split,
+            vec![
+                line(&[(0.0, 51.0), (0.0, 51.001)]),
+                line(&[(0.0, 52.0), (0.0, 52.001)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trim_ends() {
+        let step = haversine_distance(Coord { x: 0.0, y: 0.0 }, Coord { x: 0.0, y: 0.01 });
+        let line: Vec<Coord<f64>> = (0..=10)
+            .map(|i| Coord {
+                x: 0.0,
+                y: f64::from(i) * 0.01,
+            })
+            .collect();
+        let trimmed = trim_ends(vec![line], 1.5 * step);
+
+        assert_eq!(trimmed.len(), 1);
+        let coords = &trimmed[0];
+        assert_eq!(coords.len(), 9);
+        assert!((coords[0].y - 0.015).abs() < 1e-6);
+        assert!((coords[8].y - 0.085).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_trim_ends_drops_short_segments() {
+        let short = vec![Coord { x: 0.0, y: 0.0 }, Coord { x: 0.0, y: 0.001 }];
+        let long = vec![Coord { x: 0.0, y: 0.01 }, Coord { x: 0.0, y: 0.05 }];
+        let trimmed = trim_ends(vec![short, long], 500.0);
+
+        assert_eq!(trimmed.len(), 1);
+        assert!(trimmed[0][0].y > 0.01);
+        assert!(trimmed[0][1].y < 0.05);
+        assert!(trim_ends(trimmed, 100_000.0).is_empty());
+    }
+
+    #[test]
+    fn test_fuzz_ends() {
+        let line: Vec<Coord<f64>> = (0..=20)
+            .map(|i| Coord {
+                x: -2.6,
+                y: 51.4 + f64::from(i) * 0.001,
+            })
+            .collect();
+        let (start, end) = (line[0], line[20]);
+
+        let fuzzed = fuzz_ends(vec![line.clone()], 300.0);
+        assert_eq!(
+            fuzzed,
+            fuzz_ends(vec![line], 300.0),
+            "fuzzing is repeatable"
+        );
+
+        let coords = &fuzzed[0];
+        assert_ne!(coords[0], start);
+        assert!(haversine_distance(coords[0], start) <= 300.0);
+        assert!(haversine_distance(*coords.last().unwrap(), end) <= 300.0);
+        // The middle of the route is untouched, and nothing else is near the ends.
+        assert!(coords.contains(&Coord { x: -2.6, y: 51.41 }));
+        assert!(coords[1..coords.len() - 1].iter().all(
+            |c| haversine_distance(*c, start) >= 300.0 && haversine_distance(*c, end) >= 300.0
+        ));
+    }
+
+    #[test]
+    fn test_split_by_points() {
+        let parts = split_by_points(vec![(0..7).collect(), vec![10, 11]], 3);
+        assert_eq!(
+            parts,
+            vec![
+                vec![vec![0, 1, 2]],
+                vec![vec![2, 3, 4]],
+                vec![vec![4, 5, 6]],
+                vec![vec![10, 11]],
+            ]
+        );
+
+        // Short segments share parts, and nothing is split when it all fits.
+        let parts = split_by_points(vec![vec![0, 1], vec![2], vec![3, 4, 5]], 4);
+        assert_eq!(parts, vec![vec![vec![0, 1], vec![2]], vec![vec![3, 4, 5]]]);
+        assert_eq!(split_by_points(vec![vec![0, 1]], 2).len(), 1);
+    }
+}
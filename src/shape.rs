@@ -0,0 +1,172 @@
+//! Telling loops, out and backs and point to point routes apart.
+
+use crate::geo::project_local;
+use crate::similarity::distance_to_segment;
+use geo_types::Coord;
+use gpx::Track;
+use std::fmt;
+
+/// Routes finishing within this many meters of their start are treated as returning to it.
+const SAME_PLACE_M: f64 = 250.0;
+
+/// How close, in meters, the way back has to be to the way out to count as retracing it.
+const RETRACE_TOLERANCE_M: f64 = 30.0;
+
+/// How much of the way back has to retrace the way out for the route to be an out and back
+/// rather than a loop. Less than all of it, to allow for short lollipop-shaped loops at the
+/// far end.
+const OUT_AND_BACK_MIN_RETRACED: f64 = 0.7;
+
+/// Points on the way back checked against the way out, which is plenty for a proportion.
+const SAMPLES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteShape {
+    Loop,
+    OutAndBack,
+    PointToPoint,
+}
+
+impl fmt::Display for RouteShape {
+    /// Uses the same wording as AllTrails' route types.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Loop => "Loop",
+            Self::OutAndBack => "Out & back",
+            Self::PointToPoint => "Point to point",
+        })
+    }
+}
+
+/// Works out the shape of a route from where it starts and finishes, and how much of its
+/// second half goes back over its first. Segments are taken as one continuous route. Routes
+/// with no length have no shape.
+pub fn route_shape(track: &Track) -> Option<RouteShape> {
+    let coords: Vec<Coord<f64>> = track
+        .segments
+        .iter()
+        .flat_map(|segment| &segment.points)
+        .map(|point| point.point().0)
+        .collect();
+    let projected = project_local(&coords);
+    let (&start, &finish) = (projected.first()?, projected.last()?);
+
+    let mut along = vec![0.0];
+    for pair in projected.windows(2) {
+        along.push(along[along.len() - 1] + distance(pair[0], pair[1]));
+    }
+    let length = along[along.len() - 1];
+    if length == 0.0 {
+        return None;
+    }
+
+    if distance(start, finish) > SAME_PLACE_M {
+        return Some(RouteShape::PointToPoint);
+    }
+
+    // The way back is sampled evenly by distance rather than at its vertices, which can be
+    // too sparse to say anything. The middle and the finish are on or near the way out
+    // whatever the shape, so they're left out.
+    let middle = along.partition_point(|&distance| distance < length / 2.0);
+    let out = &projected[..=middle];
+    let step = length / 2.0 / (SAMPLES + 1) as f64;
+    let on_back: Vec<Coord<f64>> = (1..=SAMPLES)
+        .map(|i| point_at(&projected, &along, length / 2.0 + step * i as f64))
+        .collect();
+    let mut samples: Vec<Coord<f64>> = on_back
+        .iter()
+        .copied()
+        .filter(|&point| distance(point, start) > SAME_PLACE_M)
+        .collect();
+    if samples.is_empty() {
+        // The whole route is close to its start, so there's nothing else to go by.
+        samples = on_back;
+    }
+    let retraced = samples
+        .iter()
+        .filter(|&&point| distance_to_line(point, out) <= RETRACE_TOLERANCE_M)
+        .count();
+
+    Some(
+        if retraced as f64 >= samples.len() as f64 * OUT_AND_BACK_MIN_RETRACED {
+            RouteShape::OutAndBack
+        } else {
+            RouteShape::Loop
+        },
+    )
+}
+
+/// The point `at` meters along a line, given how far along it each of its points is.
+fn point_at(line: &[Coord<f64>], along: &[f64], at: f64) -> Coord<f64> {
+    let i = along.partition_point(|&distance| distance < at);
+    if i == 0 || i == line.len() {
+        return line[i.min(line.len() - 1)];
+    }
+    let (a, b) = (line[i - 1], line[i]);
+    let t = (at - along[i - 1]) / (along[i] - along[i - 1]);
+    Coord {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+    }
+}
+
+fn distance(a: Coord<f64>, b: Coord<f64>) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+fn distance_to_line(point: Coord<f64>, line: &[Coord<f64>]) -> f64 {
+    match line {
+        [only] => distance(point, *only),
+        _ => line
+            .windows(2)
+            .map(|pair| distance_to_segment(point, pair[0], pair[1]))
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::{TrackSegment, Waypoint};
+
+    fn track(coords: &[(f64, f64)]) -> Track {
+        Track {
+            segments: vec![TrackSegment {
+                points: coords
+                    .iter()
+                    .map(|&(x, y)| Waypoint::new(Point::new(x, y)))
+                    .collect(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_route_shape() {
+        let square = [
+            (0.0, 0.0),
+            (0.01, 0.0),
+            (0.01, 0.01),
+            (0.0, 0.01),
+            (0.0, 0.0),
+        ];
+        assert_eq!(route_shape(&track(&square)), Some(RouteShape::Loop));
+
+        // The midpoint and finish are the only vertices on the way back, and both are on the
+        // way out.
+        let triangle = [(0.0, 0.0), (0.02, 0.0), (0.01, 0.02), (0.0, 0.0)];
+        assert_eq!(route_shape(&track(&triangle)), Some(RouteShape::Loop));
+
+        let there_and_back = [(0.0, 0.0), (0.0, 0.01), (0.0, 0.02), (0.0, 0.0001)];
+        assert_eq!(
+            route_shape(&track(&there_and_back)),
+            Some(RouteShape::OutAndBack)
+        );
+
+        let across = [(0.0, 0.0), (0.01, 0.01), (0.02, 0.01)];
+        assert_eq!(route_shape(&track(&across)), Some(RouteShape::PointToPoint));
+
+        assert_eq!(route_shape(&track(&[(0.0, 0.0)])), None);
+    }
+}
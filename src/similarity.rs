@@ -0,0 +1,162 @@
+//! Measuring how closely two routes match, for example a recorded activity against the
+//! AllTrails route it was meant to follow.
+
+use crate::geo::project_local;
+use geo_types::Coord;
+use gpx::Track;
+
+/// A route's segments, projected into meters.
+type Segments = Vec<Vec<Coord<f64>>>;
+
+/// Routes are filled in to have a point at least this often, in meters, before their
+/// Fréchet distance is measured.
+const FRECHET_SPACING_M: f64 = 10.0;
+
+/// The Hausdorff distance between two routes in meters: the furthest any point of either is
+/// from the nearest part of the other. This ignores direction, so a route and its reverse
+/// are a distance of zero apart.
+pub fn hausdorff_distance(a: &Track, b: &Track) -> f64 {
+    let (a, b) = project_together(a, b);
+    max_distance_to(&a, &b).max(max_distance_to(&b, &a))
+}
+
+/// The Fréchet distance between two routes in meters: how far apart two walkers following
+/// them from start to finish must get, at worst, if each may vary their pace but never go
+/// back. Unlike the Hausdorff distance, this notices routes that go in a different order or
+/// direction.
+///
+/// This is the discrete Fréchet distance, computed after filling in each route to have a
+/// point every 10 meters, so is an overestimate by at most a few meters. Segments are
+/// joined end to end.
+pub fn frechet_distance(a: &Track, b: &Track) -> f64 {
+    let (a, b) = project_together(a, b);
+    let (a, b) = (densify(&a.concat()), densify(&b.concat()));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    // The classic dynamic programming approach, keeping only the previous row.
+    let mut previous: Vec<f64> = Vec::with_capacity(b.len());
+    for &p in &a {
+        let mut row: Vec<f64> = Vec::with_capacity(b.len());
+        for (j, &q) in b.iter().enumerate() {
+            let reachable = match (previous.is_empty(), j) {
+                (true, 0) => 0.0,
+                (true, _) => row[j - 1],
+                (false, 0) => previous[0],
+                (false, _) => previous[j].min(previous[j - 1]).min(row[j - 1]),
+            };
+            row.push(reachable.max(distance(p, q)));
+        }
+        previous = row;
+    }
+    previous[b.len() - 1]
+}
+
+pub(crate) fn segment_coords(track: &Track) -> impl Iterator<Item = Vec<Coord<f64>>> + '_ {
+    track
+        .segments
+        .iter()
+        .map(|segment| segment.points.iter().map(|p| p.point().0).collect())
+}
+
+/// Projects both routes onto the same local plane, so that distances between them are in
+/// meters.
+fn project_together(a: &Track, b: &Track) -> (Segments, Segments) {
+    let segments: Segments = segment_coords(a).chain(segment_coords(b)).collect();
+    let mut projected = project_local(&segments.concat()).into_iter();
+    let mut take = |count: usize| projected.by_ref().take(count).collect::<Vec<_>>();
+
+    let split = a.segments.len();
+    let a = segments[..split].iter().map(|s| take(s.len())).collect();
+    let b = segments[split..].iter().map(|s| take(s.len())).collect();
+    (a, b)
+}
+
+/// The furthest any point in `from` is from the nearest part of `to`.
+fn max_distance_to(from: &[Vec<Coord<f64>>], to: &[Vec<Coord<f64>>]) -> f64 {
+    from.iter()
+        .flatten()
+        .map(|&point| {
+            to.iter()
+                .flat_map(|segment| {
+                    // A lone point is a segment of zero length.
+                    let pairs = segment.windows(2).map(|pair| (pair[0], pair[1]));
+                    let lone = segment.first().filter(|_| segment.len() == 1);
+                    pairs.chain(lone.map(|&c| (c, c)))
+                })
+                .map(|(a, b)| distance_to_segment(point, a, b))
+                .fold(f64::INFINITY, f64::min)
+        })
+        .filter(|distance| distance.is_finite())
+        .fold(0.0, f64::max)
+}
+
+pub(crate) fn distance_to_segment(p: Coord<f64>, a: Coord<f64>, b: Coord<f64>) -> f64 {
+    let (ab, ap) = (b - a, p - a);
+    let length_squared = ab.x * ab.x + ab.y * ab.y;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        ((ap.x * ab.x + ap.y * ab.y) / length_squared).clamp(0.0, 1.0)
+    };
+    let offset = p - (a + ab * t);
+    offset.x.hypot(offset.y)
+}
+
+fn distance(a: Coord<f64>, b: Coord<f64>) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+/// Adds points along each leg so that none is longer than `FRECHET_SPACING_M`.
+fn densify(coords: &[Coord<f64>]) -> Vec<Coord<f64>> {
+    let mut dense = Vec::with_capacity(coords.len());
+    for pair in coords.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let steps = (distance(a, b) / FRECHET_SPACING_M).ceil().max(1.0);
+        dense.extend((0..steps as usize).map(|i| a + (b - a) * (i as f64 / steps)));
+    }
+    dense.extend(coords.last());
+    dense
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::{TrackSegment, Waypoint};
+
+    fn track(coords: &[(f64, f64)]) -> Track {
+        Track {
+            segments: vec![TrackSegment {
+                points: coords
+                    .iter()
+                    .map(|&(x, y)| Waypoint::new(Point::new(x, y)))
+                    .collect(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_detour() {
+        let planned = track(&[(0.0, 0.0), (0.0, 0.01), (0.0, 0.02)]);
+        // About 111 m east halfway along.
+        let recorded = track(&[(0.0, 0.0), (0.001, 0.01), (0.0, 0.02)]);
+
+        let hausdorff = hausdorff_distance(&planned, &recorded);
+        assert!((hausdorff - 111.2).abs() < 0.5);
+        let frechet = frechet_distance(&planned, &recorded);
+        assert!(frechet >= hausdorff && frechet - hausdorff < 1.0);
+    }
+
+    #[test]
+    fn test_direction_only_matters_to_frechet() {
+        let forward = track(&[(0.0, 0.0), (0.0, 0.01)]);
+        let backward = track(&[(0.0, 0.01), (0.0, 0.0)]);
+
+        assert_eq!(hausdorff_distance(&forward, &backward), 0.0);
+        assert!((frechet_distance(&forward, &backward) - 1111.9).abs() < 1.0);
+        assert_eq!(frechet_distance(&forward, &forward), 0.0);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::geo::{project_local, Located};
+use geo_types::Coord;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Algorithm {
+    /// Ramer-Douglas-Peucker: keeps points that deviate furthest from the simplified line.
+    #[default]
+    #[cfg_attr(feature = "cli", value(alias = "dp"))]
+    DouglasPeucker,
+
+    /// Visvalingam-Whyatt: repeatedly drops the point forming the smallest triangle with its
+    /// neighbours, which tends to keep the overall shape smoother.
+    #[cfg_attr(feature = "cli", value(alias = "vw"))]
+    VisvalingamWhyatt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// Drop detail smaller than this many meters. For Visvalingam-Whyatt this is the square
+    /// root of the smallest triangle area to keep.
+    Tolerance(f64),
+
+    /// Keep at most this many points, picking whatever tolerance gets there.
+    Points(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimplifyOptions {
+    pub algorithm: Algorithm,
+    pub target: Target,
+}
+
+/// Simplifies a lon/lat path, always keeping its first and last points.
+pub fn simplify<P: Located>(points: &[P], options: &SimplifyOptions) -> Vec<P> {
+    if points.len() <= 2 {
+        return points.to_vec();
+    }
+
+    let coords: Vec<Coord<f64>> = points.iter().map(Located::coord).collect();
+    let projected = project_local(&coords);
+    let importance = match options.algorithm {
+        Algorithm::DouglasPeucker => douglas_peucker_importance(&projected),
+        Algorithm::VisvalingamWhyatt => visvalingam_whyatt_importance(&projected),
+    };
+
+    let keep: Vec<usize> = match options.target {
+        Target::Tolerance(tolerance) => (0..coords.len())
+            .filter(|&i| importance[i] > tolerance)
+            .collect(),
+        Target::Points(budget) => {
+            let mut by_importance: Vec<usize> = (0..coords.len()).collect();
+            by_importance.sort_by(|&a, &b| importance[b].total_cmp(&importance[a]));
+            by_importance.truncate(budget.max(2));
+            by_importance.sort_unstable();
+            by_importance
+        }
+    };
+
+    keep.into_iter().map(|i| points[i].clone()).collect()
+}
+
+/// For every point, the largest tolerance at which Douglas-Peucker would still keep it.
+/// Endpoints are always kept, so they get infinite importance.
+fn douglas_peucker_importance(points: &[Coord<f64>]) -> Vec<f64> {
+    let last = points.len() - 1;
+    let mut importance = vec![0.0; points.len()];
+    importance[0] = f64::INFINITY;
+    importance[last] = f64::INFINITY;
+
+    let mut stack = vec![(0, last, f64::INFINITY)];
+    while let Some((start, end, parent)) = stack.pop() {
+        if end <= start + 1 {
+            continue;
+        }
+
+        let (index, distance) = (start + 1..end)
+            .map(|i| (i, segment_distance(points[i], points[start], points[end])))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("range between non-adjacent points is not empty");
+
+        // A point can never outlive the point that split its parent range.
+        let significance = distance.min(parent);
+        importance[index] = significance;
+        stack.push((start, index, significance));
+        stack.push((index, end, significance));
+    }
+
+    importance
+}
+
+/// For every point, the square root of its effective triangle area at the time
+/// Visvalingam-Whyatt would remove it.
+fn visvalingam_whyatt_importance(points: &[Coord<f64>]) -> Vec<f64> {
+    #[derive(PartialEq)]
+    struct Candidate {
+        area: f64,
+        index: usize,
+    }
+
+    impl Eq for Candidate {}
+
+    impl Ord for Candidate {
+        fn cmp(&self, other: &Self) -> Ordering {
+            // Reversed so that BinaryHeap pops the smallest area first.
+            other.area.total_cmp(&self.area)
+        }
+    }
+
+    impl PartialOrd for Candidate {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    let last = points.len() - 1;
+    let mut prev: Vec<usize> = (0..points.len()).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (0..points.len()).map(|i| (i + 1).min(last)).collect();
+    let mut area = vec![f64::INFINITY; points.len()];
+    let mut removed = vec![false; points.len()];
+    let mut heap = BinaryHeap::new();
+
+    for index in 1..last {
+        area[index] = triangle_area(points[index - 1], points[index], points[index + 1]);
+        heap.push(Candidate {
+            area: area[index],
+            index,
+        });
+    }
+
+    let mut importance = vec![f64::INFINITY; points.len()];
+    let mut largest_removed: f64 = 0.0;
+    while let Some(Candidate {
+        area: popped,
+        index,
+    }) = heap.pop()
+    {
+        if removed[index] || popped != area[index] {
+            continue; // Stale entry superseded by a recomputed area.
+        }
+
+        largest_removed = largest_removed.max(popped);
+        importance[index] = largest_removed.sqrt();
+        removed[index] = true;
+
+        let (before, after) = (prev[index], next[index]);
+        next[before] = after;
+        prev[after] = before;
+
+        for neighbour in [before, after] {
+            if neighbour == 0 || neighbour == last {
+                continue;
+            }
+            area[neighbour] = triangle_area(
+                points[prev[neighbour]],
+                points[neighbour],
+                points[next[neighbour]],
+            );
+            heap.push(Candidate {
+                area: area[neighbour],
+                index: neighbour,
+            });
+        }
+    }
+
+    importance
+}
+
+fn segment_distance(p: Coord<f64>, a: Coord<f64>, b: Coord<f64>) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / length_sq).clamp(0.0, 1.0)
+    };
+    ((p.x - (a.x + t * dx)).powi(2) + (p.y - (a.y + t * dy)).powi(2)).sqrt()
+}
+
+fn triangle_area(a: Coord<f64>, b: Coord<f64>, c: Coord<f64>) -> f64 {
+    ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corner() -> Vec<Coord<f64>> {
+        // Straight north for five points, then straight east for five, with a little jitter.
+        (0..=10)
+            .map(|i| {
+                let jitter = if i % 2 == 0 { 0.0 } else { 0.000_01 };
+                Coord {
+                    x: f64::from(i.max(5) - 5) * 0.001 + jitter,
+                    y: 51.0 + f64::from(i.min(5)) * 0.001 + jitter,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tolerance_keeps_corner_only() {
+        for algorithm in [Algorithm::DouglasPeucker, Algorithm::VisvalingamWhyatt] {
+            let options = SimplifyOptions {
+                algorithm,
+                target: Target::Tolerance(50.0),
+            };
+            let simplified = simplify(&corner(), &options);
+            assert_eq!(simplified.len(), 3, "{algorithm:?}");
+            assert_eq!(simplified[1], corner()[5], "{algorithm:?}");
+        }
+    }
+
+    #[test]
+    fn test_target_points_budget() {
+        for algorithm in [Algorithm::DouglasPeucker, Algorithm::VisvalingamWhyatt] {
+            let options = SimplifyOptions {
+                algorithm,
+                target: Target::Points(4),
+            };
+            let simplified = simplify(&corner(), &options);
+            assert_eq!(simplified.len(), 4, "{algorithm:?}");
+            assert_eq!(simplified.first(), corner().first());
+            assert_eq!(simplified.last(), corner().last());
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Where output is written. Everything that writes a single output goes through
+//! [`OutputSink`], so a new kind of destination only has to be added here.
+
+use crate::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+#[derive(Debug)]
+pub enum OutputSink<'a> {
+    Stdout,
+    File(String),
+    /// Kept in memory, for library users who want the output back rather than written out.
+    Memory(Vec<u8>),
+    /// An entry called `name` in the zip archive at `archive`, which is started when the sink
+    /// is opened.
+    ZipEntry {
+        archive: String,
+        name: String,
+        zip: &'a mut ZipWriter<File>,
+        options: SimpleFileOptions,
+    },
+}
+
+impl OutputSink<'_> {
+    /// The sink an output argument names: stdout when there isn't one or it's "-", or
+    /// otherwise a file.
+    pub fn new(output: Option<&str>) -> Self {
+        match output {
+            None | Some("-") => Self::Stdout,
+            Some(path) => Self::File(path.to_string()),
+        }
+    }
+
+    /// Opens the sink for writing, creating or truncating a file, or starting a zip entry.
+    /// Output written to memory is appended to what's already there.
+    pub fn writer(&mut self) -> Result<BufWriter<Box<dyn Write + '_>>, Error> {
+        let writer: Box<dyn Write + '_> = match self {
+            Self::Stdout => Box::new(std::io::stdout().lock()),
+            Self::File(path) => {
+                let file = File::create(&*path).map_err(|source| Error::FileError {
+                    path: path.clone(),
+                    source,
+                })?;
+                Box::new(file)
+            }
+            Self::Memory(buffer) => Box::new(buffer),
+            Self::ZipEntry {
+                name, zip, options, ..
+            } => {
+                zip.start_file(name.as_str(), *options)?;
+                Box::new(&mut **zip)
+            }
+        };
+        Ok(BufWriter::new(writer))
+    }
+}
+
+impl From<&str> for OutputSink<'_> {
+    fn from(output: &str) -> Self {
+        Self::new(Some(output))
+    }
+}
+
+impl fmt::Display for OutputSink<'_> {
+    /// Says where the output went, for messages such as "Wrote 3 tracks to stdout".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::File(path) => write!(f, "{path}"),
+            Self::Memory(_) => write!(f, "memory"),
+            Self::ZipEntry { archive, name, .. } => write!(f, "{name} in {archive}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory() {
+        let mut sink = OutputSink::Memory(Vec::new());
+        sink.writer().unwrap().write_all(b"<gpx/>").unwrap();
+        assert!(matches!(sink, OutputSink::Memory(buffer) if buffer == b"<gpx/>"));
+        assert_eq!(OutputSink::new(Some("-")).to_string(), "stdout");
+        assert_eq!(OutputSink::from("walk.gpx").to_string(), "walk.gpx");
+    }
+
+    #[test]
+    fn test_zip_entry() {
+        let path = std::env::temp_dir().join(format!("alltrailsgpx-{}.zip", std::process::id()));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        let mut sink = OutputSink::ZipEntry {
+            archive: "walks.zip".to_string(),
+            name: "walk.gpx".to_string(),
+            zip: &mut zip,
+            options: SimpleFileOptions::default(),
+        };
+        sink.writer().unwrap().write_all(b"<gpx/>").unwrap();
+        assert_eq!(sink.to_string(), "walk.gpx in walks.zip");
+        zip.finish().unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("walk.gpx").unwrap(), &mut contents)
+            .unwrap();
+        assert_eq!(contents, "<gpx/>");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! Where input is read from. Everything that reads input goes through [`InputSource`], so a
+//! new kind of source only has to be added here.
+
+use crate::archive::{is_tar_path, read_tar};
+use crate::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSource {
+    Stdin,
+    File(String),
+    /// A file inside a tar archive, already read out of it.
+    ArchiveEntry {
+        archive: String,
+        name: String,
+        contents: Vec<u8>,
+    },
+}
+
+impl InputSource {
+    /// Opens the source for reading.
+    pub fn open(&self) -> Result<Box<dyn BufRead + '_>, Error> {
+        match self {
+            Self::Stdin => Ok(Box::new(std::io::stdin().lock())),
+            Self::File(path) => {
+                let file = File::open(path).map_err(|source| Error::FileError {
+                    path: path.clone(),
+                    source,
+                })?;
+                Ok(Box::new(BufReader::new(file)))
+            }
+            Self::ArchiveEntry { contents, .. } => Ok(Box::new(contents.as_slice())),
+        }
+    }
+
+    /// Whether this is an archive of routes, which [`Self::expand`] splits up.
+    pub fn is_archive(&self) -> bool {
+        matches!(self, Self::File(path) if is_tar_path(path))
+    }
+
+    /// Passes `f` the source of each route in an archive as it's read, in the order they're
+    /// stored, or just this source if it isn't one. A problem reading the archive, such as a
+    /// file larger than `max_size` bytes failing with [`Error::InputTooLarge`], is passed to
+    /// `f` too, after which nothing more is read. An error returned by `f` stops the reading
+    /// and is returned.
+    pub fn expand(
+        &self,
+        max_size: Option<u64>,
+        mut f: impl FnMut(Result<InputSource, Error>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        match self {
+            Self::File(archive) if self.is_archive() => read_tar(archive, max_size, |entry| {
+                f(entry.map(|(name, contents)| Self::ArchiveEntry {
+                    archive: archive.clone(),
+                    name,
+                    contents,
+                }))
+            }),
+            _ => f(Ok(self.clone())),
+        }
+    }
+}
+
+impl From<&str> for InputSource {
+    /// The source an input argument names: "-" for stdin, or otherwise a file.
+    fn from(input: &str) -> Self {
+        match input {
+            "-" => Self::Stdin,
+            path => Self::File(path.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for InputSource {
+    /// Names the source as it was given, with files in archives named like a path inside them.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => write!(f, "-"),
+            Self::File(path) => write!(f, "{path}"),
+            Self::ArchiveEntry { archive, name, .. } => write!(f, "{archive}/{name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_from_argument() {
+        assert_eq!(InputSource::from("-"), InputSource::Stdin);
+        let source = InputSource::from("walks.tar.gz");
+        assert!(source.is_archive());
+        assert_eq!(source.to_string(), "walks.tar.gz");
+        assert!(!InputSource::from("walk.json").is_archive());
+    }
+
+    #[test]
+    fn test_archive_entry() {
+        let source = InputSource::ArchiveEntry {
+            archive: "walks.tar".to_string(),
+            name: "bristol/leigh-woods.json".to_string(),
+            contents: b"{}".to_vec(),
+        };
+        assert_eq!(source.to_string(), "walks.tar/bristol/leigh-woods.json");
+        let mut expanded = Vec::new();
+        source
+            .expand(None, |entry| {
+                expanded.push(entry?);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(expanded, vec![source.clone()]);
+
+        let mut contents = String::new();
+        source
+            .open()
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "{}");
+    }
+}
@@ -0,0 +1,233 @@
+use crate::fingerprint::fingerprint;
+use crate::geo::path_length;
+use crate::shape::{route_shape, RouteShape};
+use crate::units::Units;
+use geo_types::Coord;
+use gpx::Track;
+use serde::Deserialize;
+use std::fmt;
+
+/// Elevation changes smaller than this many meters are treated as noise by default.
+pub const DEFAULT_ELEVATION_THRESHOLD: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackStats {
+    pub points: usize,
+    pub distance_m: f64,
+    pub elevation: Option<ElevationStats>,
+    /// See [`fingerprint`].
+    pub fingerprint: u64,
+    /// Whether the route is a loop, out and back or point to point, if it has any length.
+    pub shape: Option<RouteShape>,
+    /// How long the route would take to walk, by [`naismith_minutes`].
+    pub estimated_minutes: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElevationStats {
+    pub ascent_m: f64,
+    pub descent_m: f64,
+    pub min_m: f64,
+    pub max_m: f64,
+}
+
+/// The statistics AllTrails itself reports for a trail, from its `trailGeoStats` block.
+/// Every field is optional since AllTrails omits whatever it doesn't know.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrailStats {
+    /// Length in meters.
+    pub length: Option<f64>,
+    /// Total elevation gain in meters.
+    pub elevation_gain: Option<f64>,
+    /// Highest elevation in meters.
+    pub elevation_max: Option<f64>,
+    /// AllTrails' estimate of how long the trail takes.
+    pub duration_minutes: Option<f64>,
+    /// For example "Loop", "Out & back" or "Point to point". This lives next to rather than
+    /// inside `trailGeoStats`, so is filled in separately.
+    #[serde(skip)]
+    pub route_type: Option<String>,
+    /// "Easy", "Moderate" or "Hard", which also lives outside `trailGeoStats`.
+    #[serde(skip)]
+    pub difficulty: Option<String>,
+}
+
+/// Computes point count, distance, shape, a fingerprint, an estimated walking time and, if any
+/// points carry elevation, ascent and descent.
+/// Elevation changes are only counted once they exceed `elevation_threshold` meters, so that
+/// noisy data doesn't inflate the totals.
+pub fn track_stats(track: &Track, elevation_threshold: f64) -> TrackStats {
+    let points = track.segments.iter().flat_map(|s| &s.points);
+    let elevation = elevation_stats(
+        points.clone().filter_map(|p| p.elevation),
+        elevation_threshold,
+    );
+
+    let distance_m = track
+        .segments
+        .iter()
+        .map(|segment| {
+            let coords: Vec<Coord<f64>> = segment.points.iter().map(|p| p.point().0).collect();
+            path_length(&coords)
+        })
+        .sum();
+
+    TrackStats {
+        points: points.count(),
+        distance_m,
+        elevation,
+        fingerprint: fingerprint(track),
+        shape: route_shape(track),
+        estimated_minutes: naismith_minutes(distance_m, elevation.map_or(0.0, |e| e.ascent_m)),
+    }
+}
+
+/// How many minutes a walk takes by Naismith's rule: an hour for every 5 km, plus an hour for
+/// every 600 m climbed.
+pub fn naismith_minutes(distance_m: f64, ascent_m: f64) -> f64 {
+    distance_m / 5000.0 * 60.0 + ascent_m / 600.0 * 60.0
+}
+
+/// Formats a number of minutes as hours and minutes, such as "2h05m".
+pub fn format_minutes(minutes: f64) -> String {
+    let minutes = minutes.round() as u64;
+    format!("{}h{:02}m", minutes / 60, minutes % 60)
+}
+
+pub fn elevation_stats(
+    elevations: impl IntoIterator<Item = f64>,
+    threshold: f64,
+) -> Option<ElevationStats> {
+    let mut elevations = elevations.into_iter();
+    let first = elevations.next()?;
+
+    let mut stats = ElevationStats {
+        ascent_m: 0.0,
+        descent_m: 0.0,
+        min_m: first,
+        max_m: first,
+    };
+    let mut reference = first;
+
+    for elevation in elevations {
+        stats.min_m = stats.min_m.min(elevation);
+        stats.max_m = stats.max_m.max(elevation);
+
+        let change = elevation - reference;
+        if change.abs() >= threshold {
+            if change > 0.0 {
+                stats.ascent_m += change;
+            } else {
+                stats.descent_m -= change;
+            }
+            reference = elevation;
+        }
+    }
+
+    Some(stats)
+}
+
+impl TrackStats {
+    /// Describes the stats on one line, with distances and heights in `units`.
+    pub fn describe(&self, units: Units) -> String {
+        let mut description = format!(
+            "{} points, {}",
+            self.points,
+            units.distance(self.distance_m, 2)
+        );
+        if let Some(shape) = self.shape {
+            description.push_str(&format!(", {}", shape.to_string().to_lowercase()));
+        }
+        if let Some(elevation) = &self.elevation {
+            description.push_str(&format!(", {}", elevation.describe(units)));
+        }
+        description.push_str(&format!(
+            ", fingerprint {:016x}, about {} walking",
+            self.fingerprint,
+            format_minutes(self.estimated_minutes)
+        ));
+        description
+    }
+}
+
+impl fmt::Display for TrackStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(Units::Metric))
+    }
+}
+
+impl ElevationStats {
+    /// Describes the ascent, descent and range of elevation in `units`.
+    pub fn describe(&self, units: Units) -> String {
+        let (per_unit, _) = units.height_unit();
+        format!(
+            "{} ascent, {} descent, elevation {:.0} to {}",
+            units.height(self.ascent_m),
+            units.height(self.descent_m),
+            self.min_m / per_unit,
+            units.height(self.max_m),
+        )
+    }
+}
+
+impl fmt::Display for ElevationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(Units::Metric))
+    }
+}
+
+impl fmt::Display for TrailStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(length) = self.length {
+            parts.push(format!("Length: {:.2} km", length / 1000.0));
+        }
+        if let Some(gain) = self.elevation_gain {
+            parts.push(format!("Elevation gain: {gain:.0} m"));
+        }
+        if let Some(max) = self.elevation_max {
+            parts.push(format!("Highest point: {max:.0} m"));
+        }
+        if let Some(route_type) = &self.route_type {
+            parts.push(format!("Route type: {route_type}"));
+        }
+        if let Some(difficulty) = &self.difficulty {
+            parts.push(format!("Difficulty: {difficulty}"));
+        }
+        if let Some(minutes) = self.duration_minutes {
+            parts.push(format!("Estimated time: {}", format_minutes(minutes)));
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elevation_threshold_ignores_noise() {
+        let profile = [100.0, 102.0, 99.0, 101.0, 150.0, 148.0, 151.0, 120.0];
+
+        let raw = elevation_stats(profile, 0.0).unwrap();
+        assert_eq!(raw.ascent_m, 2.0 + 2.0 + 49.0 + 3.0);
+        assert_eq!(raw.descent_m, 3.0 + 2.0 + 31.0);
+
+        let smoothed = elevation_stats(profile, 5.0).unwrap();
+        assert_eq!(smoothed.ascent_m, 50.0);
+        assert_eq!(smoothed.descent_m, 30.0);
+        assert_eq!((smoothed.min_m, smoothed.max_m), (99.0, 151.0));
+    }
+
+    #[test]
+    fn test_no_elevation() {
+        assert_eq!(elevation_stats([], 5.0), None);
+    }
+
+    #[test]
+    fn test_naismith() {
+        assert_eq!(format_minutes(naismith_minutes(10_000.0, 0.0)), "2h00m");
+        assert_eq!(format_minutes(naismith_minutes(10_000.0, 450.0)), "2h45m");
+    }
+}
@@ -0,0 +1,142 @@
+//! A Markdown summary of converted trails, for pasting into trip plans.
+
+use crate::geo::BoundingBox;
+use crate::units::Units;
+use crate::Conversion;
+use std::io::{self, Write};
+
+/// Writes a section per trail listing what was converted and where it went. `conversions`
+/// pairs each conversion with a description of where it was written.
+pub fn write_summary(
+    conversions: &[(String, Conversion)],
+    units: Units,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    writeln!(writer, "# Trip summary")?;
+
+    for (written_to, conversion) in conversions {
+        writeln!(writer)?;
+        writeln!(writer, "## {}", escape(&conversion.name))?;
+        writeln!(writer)?;
+
+        let stats = &conversion.stats;
+        writeln!(
+            writer,
+            "- Distance: {}",
+            units.distance(stats.distance_m, 1)
+        )?;
+        // Prefer AllTrails' own figure, since ours depends on the elevation data we were given.
+        let gain = conversion
+            .trail_stats
+            .as_ref()
+            .and_then(|trail_stats| trail_stats.elevation_gain)
+            .or(stats.elevation.map(|elevation| elevation.ascent_m));
+        if let Some(gain) = gain {
+            writeln!(writer, "- Elevation gain: {}", units.height(gain))?;
+        }
+        let coords = conversion
+            .track
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.points)
+            .map(|point| point.point().0);
+        if let Some(bbox) = BoundingBox::around(coords) {
+            writeln!(
+                writer,
+                "- Bounds: {:.5},{:.5},{:.5},{:.5}",
+                bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y
+            )?;
+        }
+        if let Some(link) = &conversion.link {
+            writeln!(writer, "- Link: <{link}>")?;
+        }
+        writeln!(writer, "- Points: {}", stats.points)?;
+        writeln!(writer, "- Output: `{written_to}`")?;
+    }
+
+    writer.flush()
+}
+
+/// Backslash-escapes characters that Markdown would otherwise treat as formatting.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{TrackStats, TrailStats};
+    use geo_types::Point;
+    use gpx::{Track, TrackSegment, Waypoint};
+    use std::time::Duration;
+
+    #[test]
+    fn test_summary_lists_each_trail() {
+        let conversion = Conversion {
+            name: "Abbots *Leigh* Circular".to_string(),
+            stats: TrackStats {
+                points: 2,
+                distance_m: 5432.1,
+                elevation: None,
+                fingerprint: 0,
+                shape: None,
+                estimated_minutes: 65.0,
+            },
+            trail_stats: Some(TrailStats {
+                elevation_gain: Some(123.4),
+                ..Default::default()
+            }),
+            swapped_latlon: false,
+            track: Track {
+                segments: vec![TrackSegment {
+                    points: vec![
+                        Waypoint::new(Point::new(-2.6, 51.45)),
+                        Waypoint::new(Point::new(-2.65, 51.47)),
+                    ],
+                }],
+                ..Default::default()
+            },
+            link: Some("https://www.alltrails.com/trail/england/bristol/abbots".to_string()),
+            sensors: Vec::new(),
+            waypoints: Vec::new(),
+            trail_id: None,
+            elapsed: Duration::ZERO,
+            warnings: Vec::new(),
+            sources: Vec::new(),
+            provenance: None,
+        };
+
+        let mut output = Vec::new();
+        write_summary(
+            &[("out/abbots.gpx".to_string(), conversion)],
+            Units::Metric,
+            &mut output,
+        )
+        .unwrap();
+        let markdown = String::from_utf8(output).unwrap();
+
+        assert_eq!(
+            markdown,
+            "# Trip summary\n\
+             \n\
+             ## Abbots \\*Leigh\\* Circular\n\
+             \n\
+             - Distance: 5.4 km\n\
+             - Elevation gain: 123 m\n\
+             - Bounds: -2.65000,51.45000,-2.60000,51.47000\n\
+             - Link: <https://www.alltrails.com/trail/england/bristol/abbots>\n\
+             - Points: 2\n\
+             - Output: `out/abbots.gpx`\n"
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! A static SVG rendering of a route, for embedding in documents.
+
+use crate::builder::escape;
+use crate::geo::project_local;
+use crate::units::Units;
+use geo_types::Coord;
+use gpx::{Track, Waypoint};
+use std::io::{self, Write};
+
+const WIDTH: f64 = 800.0;
+const MARGIN: f64 = 20.0;
+const MAX_HEIGHT: f64 = 1200.0;
+
+/// Draws each track segment as a line on a plain background, with the start, end and
+/// waypoints marked and a scale bar in the corner, measured in `units`. North is up.
+pub fn write_svg(
+    tracks: &[Track],
+    waypoints: &[Waypoint],
+    units: Units,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let segments: Vec<Vec<Coord<f64>>> = tracks
+        .iter()
+        .flat_map(|track| &track.segments)
+        .map(|segment| segment.points.iter().map(|wp| wp.point().0).collect())
+        .filter(|coords: &Vec<_>| !coords.is_empty())
+        .collect();
+
+    // Project everything together so that it all shares one origin.
+    let all: Vec<Coord<f64>> = segments
+        .iter()
+        .flatten()
+        .copied()
+        .chain(waypoints.iter().map(|wp| wp.point().0))
+        .collect();
+    let projected = project_local(&all);
+    let view = View::fit(&projected);
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}">"#,
+        w = WIDTH,
+        h = view.height,
+    )?;
+    writeln!(
+        writer,
+        r##"  <rect width="100%" height="100%" fill="#fbfaf7"/>"##
+    )?;
+
+    let mut points = projected.iter().map(|&c| view.to_svg(c));
+    for segment in &segments {
+        let path: Vec<String> = points
+            .by_ref()
+            .take(segment.len())
+            .map(|p| format!("{:.1},{:.1}", p.x, p.y))
+            .collect();
+        writeln!(
+            writer,
+            r##"  <polyline points="{}" fill="none" stroke="#d63e2a" stroke-width="3" stroke-linejoin="round" stroke-linecap="round"/>"##,
+            path.join(" ")
+        )?;
+    }
+
+    for (waypoint, point) in waypoints.iter().zip(points) {
+        let label = waypoint.name.as_deref().map(escape).unwrap_or_default();
+        write_marker(&mut writer, point, "#2a7bd6", &label)?;
+    }
+    let route_points = segments.iter().map(Vec::len).sum::<usize>();
+    if route_points > 0 {
+        write_marker(&mut writer, view.to_svg(projected[0]), "#2aa14a", "Start")?;
+        let end = view.to_svg(projected[route_points - 1]);
+        write_marker(&mut writer, end, "#333333", "End")?;
+    }
+
+    write_scale_bar(&mut writer, &view, units)?;
+    writeln!(writer, "</svg>")?;
+    writer.flush()
+}
+
+fn write_marker(
+    writer: &mut impl Write,
+    at: Coord<f64>,
+    colour: &str,
+    label: &str,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        r#"  <circle cx="{:.1}" cy="{:.1}" r="5" fill="{colour}" stroke="white" stroke-width="1.5"><title>{label}</title></circle>"#,
+        at.x, at.y
+    )
+}
+
+fn write_scale_bar(writer: &mut impl Write, view: &View, units: Units) -> io::Result<()> {
+    // Measured in kilometers or miles if there's room for one, and meters or feet if not.
+    let max = WIDTH / 4.0 / view.scale;
+    let (per_unit, symbol) = match units.distance_unit() {
+        (per_unit, symbol) if max >= per_unit => (per_unit, symbol),
+        _ => units.height_unit(),
+    };
+    let count = nice_length(max / per_unit);
+    let length = count * per_unit * view.scale;
+    let (x, y) = (MARGIN, view.height - MARGIN);
+    let label = format!("{count} {symbol}");
+    writeln!(
+        writer,
+        r#"  <path d="M{x:.1},{top:.1} V{y:.1} H{end:.1} V{top:.1}" fill="none" stroke="black" stroke-width="1.5"/>"#,
+        top = y - 5.0,
+        end = x + length,
+    )?;
+    writeln!(
+        writer,
+        r#"  <text x="{x:.1}" y="{:.1}" font-family="sans-serif" font-size="12">{label}</text>"#,
+        y - 9.0
+    )
+}
+
+/// The largest 1, 2 or 5 times a power of ten that's no longer than `max`.
+pub(crate) fn nice_length(max: f64) -> f64 {
+    if !max.is_finite() || max <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(max.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|&length| length <= max)
+        .unwrap_or(magnitude)
+}
+
+/// Maps projected meters onto the SVG canvas.
+struct View {
+    min: Coord<f64>,
+    max_y: f64,
+    scale: f64,
+    height: f64,
+}
+
+impl View {
+    fn fit(coords: &[Coord<f64>]) -> Self {
+        if coords.is_empty() {
+            return Self {
+                min: Coord { x: 0.0, y: 0.0 },
+                max_y: 0.0,
+                scale: 1.0,
+                height: WIDTH,
+            };
+        }
+
+        let fold = |f: fn(f64, f64) -> f64, init: f64, axis: fn(&Coord<f64>) -> f64| {
+            coords.iter().map(axis).fold(init, f)
+        };
+        let min = Coord {
+            x: fold(f64::min, f64::INFINITY, |c| c.x),
+            y: fold(f64::min, f64::INFINITY, |c| c.y),
+        };
+        let max = Coord {
+            x: fold(f64::max, f64::NEG_INFINITY, |c| c.x),
+            y: fold(f64::max, f64::NEG_INFINITY, |c| c.y),
+        };
+
+        // Fit the width, unless that would make a tall route absurdly tall.
+        let (span_x, span_y) = ((max.x - min.x).max(1.0), (max.y - min.y).max(1.0));
+        let inner = WIDTH - 2.0 * MARGIN;
+        let scale = (inner / span_x).min((MAX_HEIGHT - 2.0 * MARGIN) / span_y);
+        // Leave room below the route for the scale bar.
+        let height = (span_y * scale + 2.0 * MARGIN + 30.0).max(200.0);
+        let offset_x = (inner - span_x * scale) / 2.0;
+        Self {
+            min: Coord {
+                x: min.x - offset_x / scale,
+                y: min.y,
+            },
+            max_y: max.y,
+            scale,
+            height,
+        }
+    }
+
+    fn to_svg(&self, c: Coord<f64>) -> Coord<f64> {
+        Coord {
+            x: MARGIN + (c.x - self.min.x) * self.scale,
+            y: MARGIN + (self.max_y - c.y) * self.scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::TrackSegment;
+
+    #[test]
+    fn test_nice_length() {
+        assert_eq!(nice_length(730.0), 500.0);
+        assert_eq!(nice_length(2400.0), 2000.0);
+        assert_eq!(nice_length(1.5), 1.0);
+    }
+
+    #[test]
+    fn test_route_is_drawn_within_the_canvas() {
+        let track = Track {
+            segments: vec![TrackSegment {
+                points: vec![
+                    Waypoint::new(Point::new(-2.6, 51.45)),
+                    Waypoint::new(Point::new(-2.62, 51.46)),
+                    Waypoint::new(Point::new(-2.64, 51.45)),
+                ],
+            }],
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        write_svg(
+            std::slice::from_ref(&track),
+            &[],
+            Units::Metric,
+            &mut output,
+        )
+        .unwrap();
+        let svg = String::from_utf8(output).unwrap();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<polyline"));
+        assert!(svg.contains("<title>Start</title>"));
+        assert!(svg.contains(">500 m</text>"));
+        // The route runs east to west, so the start is on the right.
+        assert!(svg.contains(r#"<circle cx="780.0""#));
+
+        let mut output = Vec::new();
+        write_svg(&[track], &[], Units::Imperial, &mut output).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains(">2000 ft</text>"));
+    }
+}
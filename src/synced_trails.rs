@@ -0,0 +1,73 @@
+//! Payloads from the mobile app's sync API, which lists trails under `syncedTrails` with each
+//! trail's map alongside it rather than inside it.
+
+use serde_json::{json, Value};
+
+/// Where a synced entry keeps the trail's map, relative to the entry.
+const MAP_PATHS: &[&str] = &["/map", "/maps/0", "/trail/map", "/trail/maps/0"];
+
+/// Rewrites a sync payload into the offline layout, with each synced trail in `trails` and its
+/// map as the trail's `defaultMap`. Anything else is returned unchanged.
+pub(crate) fn unwrap(mut json: Value) -> Value {
+    let Some(entries) = json.get_mut("syncedTrails").and_then(Value::as_array_mut) else {
+        return json;
+    };
+    let trails: Vec<Value> = entries.iter_mut().map(Value::take).map(trail).collect();
+    json!({ "trails": trails })
+}
+
+/// The trail of a synced entry, which is either nested under `trail` or is the entry itself.
+fn trail(mut entry: Value) -> Value {
+    let map = MAP_PATHS
+        .iter()
+        .find_map(|path| entry.pointer_mut(path).map(Value::take));
+    let nested = entry
+        .get_mut("trail")
+        .filter(|trail| trail.is_object())
+        .map(Value::take);
+    let mut trail = nested.unwrap_or(entry);
+    if let (Some(map), Some(fields)) = (map, trail.as_object_mut()) {
+        fields.remove("map");
+        fields.remove("maps");
+        fields.entry("defaultMap").or_insert(map);
+    }
+    trail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unwrap() {
+        let payload = json!({
+            "syncedTrails": [
+                {
+                    "trail": { "id": 1, "name": "Nested" },
+                    "map": { "routes": [{ "lineSegments": [] }] },
+                    "syncedAt": "2024-05-01T10:00:00Z"
+                },
+                {
+                    "id": 2,
+                    "name": "Flat",
+                    "maps": [{ "waypoints": [] }]
+                }
+            ]
+        });
+        let unwrapped = unwrap(payload);
+        assert_eq!(unwrapped.pointer("/trails/0/name"), Some(&json!("Nested")));
+        assert_eq!(
+            unwrapped.pointer("/trails/0/defaultMap/routes/0/lineSegments"),
+            Some(&json!([]))
+        );
+        assert_eq!(unwrapped.pointer("/trails/1/id"), Some(&json!(2)));
+        assert_eq!(
+            unwrapped.pointer("/trails/1/defaultMap/waypoints"),
+            Some(&json!([]))
+        );
+        assert_eq!(unwrapped.pointer("/trails/1/maps"), None);
+
+        let deep = json!({ "maps": [{ "name": "Deep" }] });
+        assert_eq!(unwrap(deep.clone()), deep);
+    }
+}
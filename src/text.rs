@@ -0,0 +1,102 @@
+//! Cleaning up free text from AllTrails for display on GPS devices.
+
+/// How many characters of the trail overview to keep. Devices typically show descriptions in a
+/// small popup, so a paragraph or so is plenty.
+pub const MAX_DESCRIPTION_CHARS: usize = 500;
+
+/// Turns AllTrails' overview text into something fit for a GPX `<desc>`: markup is stripped,
+/// common entities are decoded, whitespace is collapsed, and the result is cut at a word
+/// boundary to at most `max_chars` characters, with an ellipsis if anything was dropped.
+pub fn sanitize_description(text: &str, max_chars: usize) -> Option<String> {
+    let plain = decode_entities(&strip_tags(text));
+    let collapsed = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    Some(truncate(&collapsed, max_chars))
+}
+
+/// Transliterates text to ASCII, such as "Zürich" to "Zurich", for devices and filesystems
+/// that mangle anything else.
+pub fn to_ascii(text: &str) -> String {
+    deunicode::deunicode(text)
+}
+
+fn strip_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            // Tags often separate paragraphs, so keep the words either side apart.
+            '>' if in_tag => {
+                in_tag = false;
+                out.push(' ');
+            }
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+pub(crate) fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    // Leave room for the ellipsis, then back off to the last space so words aren't split.
+    let cut = text
+        .char_indices()
+        .nth(max_chars.saturating_sub(1))
+        .map_or(text.len(), |(i, _)| i);
+    let kept = &text[..cut];
+    let kept = if text[cut..].starts_with(' ') {
+        kept
+    } else {
+        kept.rfind(' ').map_or(kept, |space| &kept[..space])
+    };
+    format!(
+        "{}…",
+        kept.trim_end_matches(|c: char| c.is_ascii_punctuation())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markup_and_whitespace_are_cleaned() {
+        let text = "<p>A lovely   loop.</p><p>Dogs &amp; horses welcome.\n</p>";
+        assert_eq!(
+            sanitize_description(text, 100).as_deref(),
+            Some("A lovely loop. Dogs & horses welcome.")
+        );
+    }
+
+    #[test]
+    fn test_long_text_is_cut_at_a_word() {
+        let text = "Enjoy this 5.2-mile loop trail near Bristol, England.";
+        assert_eq!(
+            sanitize_description(text, 20).as_deref(),
+            Some("Enjoy this 5.2-mile…")
+        );
+        assert!(sanitize_description(text, 20).unwrap().chars().count() <= 20);
+    }
+
+    #[test]
+    fn test_empty_text_is_none() {
+        assert_eq!(sanitize_description(" <br/> ", 100), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Made-up timestamps for routes, for apps that won't import a track without them.
+
+use crate::geo::haversine_distance;
+use gpx::{Time, Track};
+use time::{Duration, OffsetDateTime};
+
+/// A steady walking pace of 5 km/h.
+pub const WALKING_SPEED_MPS: f64 = 5.0 / 3.6;
+
+/// How the pace varies along the route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Pace {
+    /// A steady 5 km/h throughout.
+    #[default]
+    Steady,
+
+    /// Tobler's hiking function: about 5 km/h on the flat, fastest going gently downhill, and
+    /// slower on steeper slopes either way. Without elevation, every leg counts as flat.
+    Tobler,
+}
+
+impl Pace {
+    /// The speed in meters per second over a leg climbing `rise_m` in `distance_m`.
+    fn speed_mps(self, distance_m: f64, rise_m: f64) -> f64 {
+        match self {
+            Self::Steady => WALKING_SPEED_MPS,
+            Self::Tobler => tobler_speed_mps(rise_m / distance_m),
+        }
+    }
+}
+
+/// Walking speed in meters per second on a slope of `gradient` (rise over run) by Tobler's
+/// hiking function.
+pub fn tobler_speed_mps(gradient: f64) -> f64 {
+    6.0 * (-3.5 * (gradient + 0.05).abs()).exp() / 3.6
+}
+
+/// Gives each point of the track the time it would be reached at `pace` from `start`. Gaps
+/// between segments take no time, as how they're crossed isn't known.
+pub fn add_times(track: &mut Track, start: OffsetDateTime, pace: Pace) {
+    let mut elapsed_s = 0.0;
+    for segment in &mut track.segments {
+        let mut previous = None;
+        for point in &mut segment.points {
+            let coord = point.point().0;
+            if let Some((previous, previous_elevation)) = previous {
+                let distance_m = haversine_distance(previous, coord);
+                if distance_m > 0.0 {
+                    let rise_m = match (previous_elevation, point.elevation) {
+                        (Some(from), Some(to)) => to - from,
+                        _ => 0.0,
+                    };
+                    elapsed_s += distance_m / pace.speed_mps(distance_m, rise_m);
+                }
+            }
+            previous = Some((coord, point.elevation));
+            point.time = Some(Time::from(start + Duration::seconds_f64(elapsed_s)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::Point;
+    use gpx::{TrackSegment, Waypoint};
+
+    fn times(track: &Track) -> Vec<OffsetDateTime> {
+        track
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.points)
+            .map(|point| OffsetDateTime::from(point.time.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_add_times() {
+        // About 1.1 km due north, then the same again in a second segment.
+        let point = |lat: f64| Waypoint::new(Point::new(-2.6, lat));
+        let mut track = Track::new();
+        track.segments = vec![
+            TrackSegment {
+                points: vec![point(51.45), point(51.46)],
+            },
+            TrackSegment {
+                points: vec![point(51.47), point(51.48)],
+            },
+        ];
+        let start = OffsetDateTime::from_unix_timestamp(1_714_554_000).unwrap();
+        add_times(&mut track, start, Pace::Steady);
+
+        let times = times(&track);
+        assert_eq!(times[0], start);
+        // 1.11 km at 5 km/h is about 13 minutes 20 seconds.
+        let leg = times[1] - times[0];
+        assert!((leg.whole_seconds() - 800).abs() < 5, "{leg}");
+        assert_eq!(times[2], times[1]);
+        assert!((times[3] - times[2] - leg).whole_seconds().abs() <= 1);
+    }
+
+    #[test]
+    fn test_tobler() {
+        assert!((tobler_speed_mps(0.0) * 3.6 - 5.04).abs() < 0.01);
+        assert!(tobler_speed_mps(-0.05) > tobler_speed_mps(0.0));
+        assert!(tobler_speed_mps(0.2) < tobler_speed_mps(-0.2));
+
+        // Climbing 100 m over the leg takes longer than coming back down it.
+        let point = |lat: f64, elevation: f64| {
+            let mut point = Waypoint::new(Point::new(-2.6, lat));
+            point.elevation = Some(elevation);
+            point
+        };
+        let mut track = Track::new();
+        track.segments = vec![TrackSegment {
+            points: vec![point(51.45, 0.0), point(51.46, 100.0), point(51.47, 0.0)],
+        }];
+        add_times(&mut track, OffsetDateTime::UNIX_EPOCH, Pace::Tobler);
+
+        let times = times(&track);
+        assert!(times[1] - times[0] > times[2] - times[1]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Responses from the redesigned trail detail endpoint, which puts the trail under
+//! `trail_detail` and spells its keys in snake_case.
+
+use serde_json::{json, Value};
+
+/// Keys of the trail that were renamed as well as respelled, by their new and old names.
+const RENAMED_KEYS: &[(&str, &str)] = &[("map", "defaultMap"), ("geoStats", "trailGeoStats")];
+
+/// Whether the document came from the redesigned endpoint.
+fn is_trail_detail(json: &Value) -> bool {
+    json.get("trail_detail").is_some_and(Value::is_object)
+}
+
+/// Rewrites a trail detail response into the offline layout, with the trail at `/trails/0`.
+/// Anything else is returned unchanged.
+pub(crate) fn unwrap(mut json: Value) -> Value {
+    if !is_trail_detail(&json) {
+        return json;
+    }
+    let trails = match camel_case_keys(json["trail_detail"]["trail"].take()) {
+        Value::Object(trail) => vec![Value::Object(trail.into_iter().map(rename).collect())],
+        _ => Vec::new(),
+    };
+    json!({ "trails": trails })
+}
+
+fn rename((key, value): (String, Value)) -> (String, Value) {
+    let key = RENAMED_KEYS
+        .iter()
+        .find(|(new, _)| *new == key)
+        .map_or(key, |(_, old)| old.to_string());
+    (key, value)
+}
+
+/// Respells every snake_case key as camelCase, leaving leading underscores alone.
+fn camel_case_keys(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(camel_case_keys).collect()),
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (camel_case(&key), camel_case_keys(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn camel_case(key: &str) -> String {
+    let leading = key.len() - key.trim_start_matches('_').len();
+    let mut camel = key[..leading].to_string();
+    let mut upper = false;
+    for c in key[leading..].chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            camel.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camel_case() {
+        assert_eq!(camel_case("points_data"), "pointsData");
+        assert_eq!(camel_case("trail_geo_stats"), "trailGeoStats");
+        assert_eq!(camel_case("_geoloc"), "_geoloc");
+        assert_eq!(camel_case("name"), "name");
+    }
+
+    #[test]
+    fn test_unwrap() {
+        let response = json!({
+            "trail_detail": {
+                "trail": {
+                    "id": 10_236_086,
+                    "name": "Bristol and Abbots Leigh Circular",
+                    "_geoloc": { "lat": 51.45, "lng": -2.6 },
+                    "geo_stats": { "length": 9817.4, "elevation_gain": 213.4 },
+                    "map": {
+                        "routes": [{
+                            "line_segments": [{ "polyline": { "points_data": "abc" } }]
+                        }]
+                    }
+                }
+            }
+        });
+        let unwrapped = unwrap(response);
+        assert_eq!(
+            unwrapped.pointer("/trails/0/defaultMap/routes/0/lineSegments/0/polyline/pointsData"),
+            Some(&json!("abc"))
+        );
+        assert_eq!(
+            unwrapped.pointer("/trails/0/trailGeoStats/elevationGain"),
+            Some(&json!(213.4))
+        );
+        assert_eq!(
+            unwrapped.pointer("/trails/0/_geoloc/lat"),
+            Some(&json!(51.45))
+        );
+
+        let offline = json!({ "trails": [{ "name": "Offline" }] });
+        assert_eq!(unwrap(offline.clone()), offline);
+    }
+}
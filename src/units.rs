@@ -0,0 +1,92 @@
+//! Metric or imperial units for distances and heights in reports.
+
+use std::env;
+
+const METERS_PER_MILE: f64 = 1609.344;
+const METERS_PER_FOOT: f64 = 0.3048;
+
+/// Regions whose locales measure in miles and feet.
+const IMPERIAL_REGIONS: &[&str] = &["US", "LR", "MM"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Units {
+    /// Kilometers and meters.
+    #[default]
+    Metric,
+
+    /// Miles and feet.
+    Imperial,
+}
+
+impl Units {
+    /// The units the locale measures in, from LC_ALL, LC_MEASUREMENT or LANG in that order.
+    pub fn from_locale() -> Self {
+        ["LC_ALL", "LC_MEASUREMENT", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|locale| !locale.is_empty())
+            .map_or_else(Self::default, |locale| Self::for_locale(&locale))
+    }
+
+    /// The units for a locale name such as "en_US.UTF-8".
+    fn for_locale(locale: &str) -> Self {
+        let region = locale
+            .split(['.', '@'])
+            .next()
+            .and_then(|name| name.split_once('_'))
+            .map(|(_, region)| region);
+        if region.is_some_and(|region| IMPERIAL_REGIONS.contains(&region)) {
+            Self::Imperial
+        } else {
+            Self::Metric
+        }
+    }
+
+    /// How many meters there are in the unit for long distances, and its symbol.
+    pub fn distance_unit(self) -> (f64, &'static str) {
+        match self {
+            Self::Metric => (1000.0, "km"),
+            Self::Imperial => (METERS_PER_MILE, "mi"),
+        }
+    }
+
+    /// How many meters there are in the unit for heights, and its symbol.
+    pub fn height_unit(self) -> (f64, &'static str) {
+        match self {
+            Self::Metric => (1.0, "m"),
+            Self::Imperial => (METERS_PER_FOOT, "ft"),
+        }
+    }
+
+    /// Formats a distance given in meters, such as "12.35 km".
+    pub fn distance(self, meters: f64, decimals: usize) -> String {
+        let (per_unit, symbol) = self.distance_unit();
+        format!("{:.decimals$} {symbol}", meters / per_unit)
+    }
+
+    /// Formats a height given in meters to the nearest unit, such as "412 m".
+    pub fn height(self, meters: f64) -> String {
+        let (per_unit, symbol) = self.height_unit();
+        format!("{:.0} {symbol}", meters / per_unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_locale() {
+        assert_eq!(Units::for_locale("en_US.UTF-8"), Units::Imperial);
+        assert_eq!(Units::for_locale("en_GB.UTF-8"), Units::Metric);
+        assert_eq!(Units::for_locale("C"), Units::Metric);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Units::Metric.distance(12_345.0, 2), "12.35 km");
+        assert_eq!(Units::Imperial.distance(16_093.44, 1), "10.0 mi");
+        assert_eq!(Units::Imperial.height(100.0), "328 ft");
+    }
+}
@@ -0,0 +1,153 @@
+//! Problems that didn't stop a route being converted, but that whoever asked for it should
+//! hear about, returned in [`Conversion::warnings`](crate::Conversion::warnings).
+
+use crate::format::Layout;
+use crate::sanity::Problem;
+use std::fmt;
+
+/// How many names a warning lists before only counting the rest.
+const MAX_NAMES: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The input doesn't name the route, so it was given this name instead.
+    Unnamed { name: String },
+    /// The route looks corrupted, but [`Options::lenient`](crate::Options::lenient) was set.
+    Implausible(Problem),
+    /// The input has both a `trails` and a `maps` root, and without
+    /// [`Options::prefer_format`](crate::Options::prefer_format) saying which to read, the
+    /// other was ignored.
+    BothLayouts { read: Layout },
+    /// Things in the input that were left out of the output, named where they have names.
+    Ignored {
+        what: Ignored,
+        count: usize,
+        names: Vec<String>,
+    },
+    /// A trail bundled with the chosen one couldn't be converted.
+    BundledSkipped { name: String, reason: String },
+    /// A later input of a batch was the same trail, so it was skipped.
+    Duplicate { input: String },
+}
+
+/// What a [`Warning::Ignored`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ignored {
+    /// Trails other than the chosen one in a response listing several.
+    OtherTrails,
+    /// Routes other than the chosen one on the trail's map.
+    OtherRoutes,
+    /// Trails an offline map pack bundles with the chosen one.
+    BundledTrails,
+    /// Waypoints without a usable location.
+    UnlocatedWaypoints,
+}
+
+impl Ignored {
+    /// What's counted, in the singular and plural, and what to pass to convert it, if anything.
+    fn describe(self) -> (&'static str, &'static str, Option<&'static str>) {
+        match self {
+            Self::OtherTrails => (
+                "other trail",
+                "other trails",
+                Some("--trail-index to convert another"),
+            ),
+            Self::OtherRoutes => (
+                "other route",
+                "other routes",
+                Some("--split-routes to convert them all"),
+            ),
+            Self::BundledTrails => (
+                "bundled trail",
+                "bundled trails",
+                Some("--include-bundled to convert them"),
+            ),
+            Self::UnlocatedWaypoints => (
+                "waypoint without a location",
+                "waypoints without a location",
+                None,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unnamed { name } => {
+                write!(
+                    f,
+                    "the input doesn't name the route, so it's called {name:?}"
+                )
+            }
+            Self::Implausible(problem) => write!(f, "the route looks corrupted: {problem}"),
+            Self::BothLayouts { read } => {
+                let (read, ignored) = match read {
+                    Layout::Offline => ("trails", "maps"),
+                    Layout::Deep => ("maps", "trails"),
+                };
+                write!(
+                    f,
+                    "the input has both {read} and {ignored}, so {ignored} was ignored (pass \
+                     --prefer-format to choose)"
+                )
+            }
+            Self::Ignored { what, count, names } => {
+                let (singular, plural, hint) = what.describe();
+                let what = if *count == 1 { singular } else { plural };
+                write!(f, "ignored {count} {what}")?;
+                let listed = names.len().min(MAX_NAMES);
+                if listed > 0 {
+                    let names: Vec<String> = names[..listed]
+                        .iter()
+                        .map(|name| format!("{name:?}"))
+                        .collect();
+                    write!(f, ": {}", names.join(", "))?;
+                    if *count > listed {
+                        write!(f, " and {} more", count - listed)?;
+                    }
+                }
+                match hint {
+                    Some(hint) => write!(f, " (pass {hint})"),
+                    None => Ok(()),
+                }
+            }
+            Self::BundledSkipped { name, reason } => {
+                write!(f, "skipped bundled trail {name:?}: {reason}")
+            }
+            Self::Duplicate { input } => write!(f, "skipped {input}, which is the same trail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let ignored = |what, count, names: &[&str]| Warning::Ignored {
+            what,
+            count,
+            names: names.iter().map(|name| name.to_string()).collect(),
+        };
+        assert_eq!(
+            ignored(Ignored::OtherTrails, 1, &[]).to_string(),
+            "ignored 1 other trail (pass --trail-index to convert another)"
+        );
+        assert_eq!(
+            ignored(Ignored::OtherRoutes, 5, &["A", "B", "C", "D"]).to_string(),
+            "ignored 5 other routes: \"A\", \"B\", \"C\" and 2 more (pass --split-routes to \
+             convert them all)"
+        );
+        assert_eq!(
+            ignored(Ignored::UnlocatedWaypoints, 1, &["Nowhere"]).to_string(),
+            "ignored 1 waypoint without a location: \"Nowhere\""
+        );
+        assert_eq!(
+            Warning::BothLayouts { read: Layout::Deep }.to_string(),
+            "the input has both maps and trails, so trails was ignored (pass --prefer-format \
+             to choose)"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Just enough of an XML tokenizer to read coordinates out of KML, without pulling in a full
+//! XML parser. Namespaces are ignored, leaving only local names.
+
+use crate::text::decode_entities;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Token<'a> {
+    Start(&'a str),
+    End(&'a str),
+    Text(String),
+}
+
+pub(crate) struct Tokens<'a> {
+    rest: &'a str,
+    /// The end of a self-closing element, which is reported after its start.
+    pending_end: Option<&'a str>,
+    /// The inside of the last start tag, after its name.
+    attributes: &'a str,
+}
+
+pub(crate) fn tokens(xml: &str) -> Tokens<'_> {
+    Tokens {
+        rest: xml,
+        pending_end: None,
+        attributes: "",
+    }
+}
+
+impl<'a> Tokens<'a> {
+    /// Consumes up to and including `terminator`, returning what came before it.
+    fn take_until(&mut self, terminator: &str) -> Option<&'a str> {
+        let end = self.rest.find(terminator)?;
+        let taken = &self.rest[..end];
+        self.rest = &self.rest[end + terminator.len()..];
+        Some(taken)
+    }
+
+    /// The attributes of the element that the last [`Token::Start`] began, with their names
+    /// as written, prefix and all, and their values' entities decoded.
+    pub(crate) fn attributes(&self) -> Vec<(&'a str, String)> {
+        let mut attributes = Vec::new();
+        let mut rest = self.attributes;
+        while let Some((name, after)) = rest.split_once('=') {
+            let after = after.trim_start();
+            let Some(quote) = after.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+                break;
+            };
+            let Some((value, after)) = after[1..].split_once(quote) else {
+                break;
+            };
+            attributes.push((name.trim(), decode_entities(value)));
+            rest = after;
+        }
+        attributes
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if let Some(name) = self.pending_end.take() {
+            return Some(Token::End(name));
+        }
+
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            if let Some(rest) = self.rest.strip_prefix("<!--") {
+                self.rest = rest;
+                self.take_until("-->")?;
+            } else if let Some(rest) = self.rest.strip_prefix("<![CDATA[") {
+                self.rest = rest;
+                return self
+                    .take_until("]]>")
+                    .map(|text| Token::Text(text.to_string()));
+            } else if self.rest.starts_with("<?") || self.rest.starts_with("<!") {
+                self.take_until(">")?;
+            } else if let Some(rest) = self.rest.strip_prefix("</") {
+                self.rest = rest;
+                return self.take_until(">").map(|tag| Token::End(local_name(tag)));
+            } else if let Some(rest) = self.rest.strip_prefix('<') {
+                self.rest = rest;
+                let tag = self.take_until(">")?;
+                let name = local_name(tag);
+                let inside = match tag.strip_suffix('/') {
+                    Some(inside) => {
+                        self.pending_end = Some(name);
+                        inside
+                    }
+                    None => tag,
+                };
+                self.attributes = inside
+                    .split_once(char::is_whitespace)
+                    .map_or("", |(_, attributes)| attributes);
+                return Some(Token::Start(name));
+            } else {
+                let end = self.rest.find('<').unwrap_or(self.rest.len());
+                let text = &self.rest[..end];
+                self.rest = &self.rest[end..];
+                return Some(Token::Text(decode_entities(text)));
+            }
+        }
+    }
+}
+
+/// The element name from the inside of a tag, without attributes or namespace prefix.
+fn local_name(tag: &str) -> &str {
+    let name = tag
+        .trim_end_matches('/')
+        .split(|c: char| c.is_whitespace())
+        .next()
+        .unwrap_or("");
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        let xml =
+            r#"<?xml version="1.0"?><!-- hi --><kml:a x="1"><b/>x &amp; y<![CDATA[<z>]]></kml:a>"#;
+        assert_eq!(
+            tokens(xml).collect::<Vec<_>>(),
+            vec![
+                Token::Start("a"),
+                Token::Start("b"),
+                Token::End("b"),
+                Token::Text("x & y".to_string()),
+                Token::Text("<z>".to_string()),
+                Token::End("a"),
+            ]
+        );
+
+        let mut tokens = tokens(r#"<wpt lat="51.45" lon='-2.6' name="A &amp; B"/>"#);
+        assert_eq!(tokens.next(), Some(Token::Start("wpt")));
+        assert_eq!(
+            tokens.attributes(),
+            [
+                ("lat", "51.45".to_string()),
+                ("lon", "-2.6".to_string()),
+                ("name", "A & B".to_string())
+            ]
+        );
+    }
+}